@@ -0,0 +1,27 @@
+//! Minimal external bot, answers every request with the
+//! direction the snake is already going in
+//!
+//! Build with `cargo build --example echo_bot` and use it with
+//! `snake_control::Template::External` and an
+//! `external::Endpoint::Process` pointing at the binary.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let request: Value = serde_json::from_str(&line?)?;
+        let reply = json!({
+            "frame": request["frame"],
+            "dir": request["you"]["dir"],
+        });
+        writeln!(stdout, "{reply}")?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
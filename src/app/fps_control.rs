@@ -81,6 +81,22 @@ impl FpsCounter {
     }
 }
 
+/// Graphics framerate the game aims for
+pub const TARGET_GRAPHICS_FPS: f64 = 60.;
+
+/// Time spent on game frames per graphics frame, game frames that
+/// don't fit are skipped so that the graphics don't starve
+const UPDATE_BUDGET: Duration = Duration::from_millis(12);
+
+/// How long the game counts as behind after skipping game frames
+const BEHIND_DURATION: Duration = Duration::from_secs(1);
+
+/// Adaptive speed doesn't slow the game down below this
+const MIN_ADAPTIVE_FPS: f64 = 1.;
+
+/// How fast adaptive speed goes back up once the game keeps up
+const RECOVERY_FACTOR: f64 = 1.01;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum State {
     Playing,
@@ -104,7 +120,11 @@ pub struct FpsContext {
 
 // combines fps with game state management
 pub struct FpsControl {
+    /// The framerate that was asked for
     game_fps: f64,
+    /// The framerate the game runs at, lower than `game_fps` while
+    /// adaptive speed slows the game down
+    current_fps: f64,
     game_frame_duration: Duration,
     start: Instant,
     last_update: Instant,
@@ -116,9 +136,21 @@ pub struct FpsControl {
 
     // number of game frames that still need to be
     // performed to catch up with the current time
-    // TODO: zero this when it gets too large to prevent lag
     missed_updates: Option<usize>,
 
+    // when the current batch of missed updates started
+    // and how many of them were performed so far
+    batch_start: Instant,
+    batch_updates: usize,
+
+    // game frames skipped because they didn't fit in
+    // the update budget
+    skipped_updates: usize,
+    last_skip: Option<Instant>,
+
+    // lower the game framerate instead of skipping frames
+    adaptive_speed: bool,
+
     // counting frames
     graphics_frame_num: usize,
 
@@ -138,6 +170,7 @@ impl FpsControl {
         let now = Instant::now();
         Self {
             game_fps: fps,
+            current_fps: fps,
             game_frame_duration: Duration::from_nanos((1_000_000_000.0 / fps) as u64),
             start: now,
             last_update: now,
@@ -145,6 +178,14 @@ impl FpsControl {
 
             missed_updates: None,
 
+            batch_start: now,
+            batch_updates: 0,
+
+            skipped_updates: 0,
+            last_skip: None,
+
+            adaptive_speed: false,
+
             graphics_frame_num: 0,
 
             measured_game_fps: FpsCounter::new(fps),
@@ -165,6 +206,37 @@ impl FpsControl {
         self.game_fps
     }
 
+    /// The framerate adaptive speed slowed the game down to,
+    /// if it is currently slowed down
+    pub fn slowed_down_fps(&self) -> Option<f64> {
+        (self.current_fps < self.game_fps).then_some(self.current_fps)
+    }
+
+    pub fn adaptive_speed(&self) -> bool {
+        self.adaptive_speed
+    }
+
+    /// Instead of skipping game frames that don't fit in the update
+    /// budget, lower the game framerate to what the machine keeps
+    /// up with, the framerate goes back up when it can
+    pub fn set_adaptive_speed(&mut self, adaptive_speed: bool) {
+        self.adaptive_speed = adaptive_speed;
+        if !adaptive_speed {
+            self.set_current_fps(self.game_fps);
+        }
+    }
+
+    /// Whether game frames were skipped recently
+    pub fn is_behind(&self) -> bool {
+        self.last_skip
+            .map_or(false, |last_skip| last_skip.elapsed() < BEHIND_DURATION)
+    }
+
+    /// Total number of game frames that were skipped
+    pub fn skipped_updates(&self) -> usize {
+        self.skipped_updates
+    }
+
     pub fn context(&self) -> &FpsContext {
         &self.context
     }
@@ -189,33 +261,47 @@ impl FpsControl {
             return;
         }
 
+        self.game_fps = fps;
+        self.measured_game_fps.set_expected_fps(fps);
+        self.set_current_fps(fps);
+    }
+
+    fn set_current_fps(&mut self, fps: f64) {
+        if (self.current_fps - fps).abs() < f64::EPSILON {
+            return;
+        }
+
         // freeze frame fraction
         let frame_fraction = self.frame_fraction();
 
-        self.game_fps = fps;
+        self.current_fps = fps;
         self.game_frame_duration = Duration::from_nanos((1_000_000_000.0 / fps) as u64);
-        self.measured_game_fps.set_expected_fps(fps);
 
         // revert to saved frame fraction
         self.set_last_update_to_match_frame_fraction(frame_fraction);
     }
 
-    // repeatedly called in update() as while loop condition
-    // WARN: this will perform as many updates as the framerate requires
-    //  this can cause strong lag a high framerates
-    // TODO: automatically lower game framerate to keep up graphics framerate
+    // repeatedly called in update() as while loop condition,
+    // performs the game frames that are due as long as they
+    // fit in the update budget and skips the rest
     pub fn can_update(&mut self) -> bool {
         if self.context.game_state != State::Playing {
             return false;
         }
 
-        let can_update = match &mut self.missed_updates {
+        let can_update = match self.missed_updates {
             Some(0) => {
                 self.missed_updates = None;
+                self.end_batch(0);
+                false
+            }
+            Some(n) if self.batch_start.elapsed() > UPDATE_BUDGET => {
+                self.missed_updates = None;
+                self.end_batch(n);
                 false
             }
             Some(n) => {
-                *n -= 1;
+                self.missed_updates = Some(n - 1);
                 true
             }
             None => {
@@ -230,8 +316,8 @@ impl FpsControl {
                     self.last_update = Instant::now();
 
                     self.missed_updates = Some(missed_updates - 1);
-
-                    self.measured_game_fps.register_frames(missed_updates);
+                    self.batch_start = self.last_update;
+                    self.batch_updates = 0;
 
                     true
                 } else {
@@ -242,11 +328,37 @@ impl FpsControl {
 
         if can_update {
             self.context.game_frame_num += 1;
+            self.batch_updates += 1;
+            self.measured_game_fps.register_frames(1);
         }
 
         can_update
     }
 
+    /// Called once all the game frames of a batch were either
+    /// performed or skipped
+    fn end_batch(&mut self, skipped: usize) {
+        if skipped > 0 {
+            // the skipped frames are dropped, the game continues
+            // from here as if they never happened
+            let now = Instant::now();
+            self.last_update = now;
+            self.skipped_updates += skipped;
+            self.last_skip = Some(now);
+
+            if self.adaptive_speed {
+                // how many game frames fit in the budget of every
+                // graphics frame at the cost of the last batch
+                let cost = self.batch_start.elapsed().as_secs_f64() / self.batch_updates as f64;
+                let sustainable_fps = UPDATE_BUDGET.as_secs_f64() / cost * TARGET_GRAPHICS_FPS;
+                let fps = sustainable_fps.min(self.current_fps).max(MIN_ADAPTIVE_FPS);
+                self.set_current_fps(fps.min(self.game_fps));
+            }
+        } else if self.adaptive_speed && self.current_fps < self.game_fps && !self.is_behind() {
+            self.set_current_fps((self.current_fps * RECOVERY_FACTOR).min(self.game_fps));
+        }
+    }
+
     // call in draw()
     pub fn graphics_frame(&mut self) {
         self.measured_graphics_fps.register_frames(1);
@@ -308,3 +420,28 @@ impl FpsControl {
         self.measured_graphics_fps.fps()
     }
 }
+
+#[test]
+fn test_updates_stay_within_budget() {
+    // time is moved back instead of waiting, time that really passes
+    //  only makes the updates run out of budget sooner
+    let ago = |millis| Instant::now().checked_sub(Duration::from_millis(millis)).unwrap();
+
+    let mut fps_control = FpsControl::new(100_000.);
+    fps_control.set_adaptive_speed(true);
+    fps_control.last_update = ago(50);
+
+    // thousands of game frames are due, each takes a millisecond
+    let mut updates = 0;
+    while fps_control.can_update() {
+        updates += 1;
+        fps_control.batch_start = ago(updates);
+    }
+
+    let budget = UPDATE_BUDGET.as_millis() as u64;
+    assert!((1..=budget + 1).contains(&updates), "{updates} updates");
+    assert!(fps_control.is_behind());
+    assert!(fps_control.skipped_updates() > 1000);
+    let slowed_down_fps = fps_control.slowed_down_fps().expect("not slowed down");
+    assert!(slowed_down_fps < 10_000., "slowed down to {slowed_down_fps}");
+}
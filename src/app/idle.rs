@@ -0,0 +1,62 @@
+//! Lets a screen stop drawing while nothing on it changes
+//!
+//! While idle, the last frame stays on screen and the screen
+//! only draws again after input, when a change it scheduled is
+//! due, or while something animates. The event loop keeps running
+//! so that input is handled right away, only drawing is skipped.
+
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct Idle {
+    /// Draw on the next frame
+    woken: bool,
+    /// Earliest scheduled change on screen
+    wakeup: Option<Instant>,
+}
+
+impl Idle {
+    /// Draw on the next frame, call after input
+    pub fn wake(&mut self) {
+        self.woken = true;
+    }
+
+    /// Draw once `at` is reached, screens schedule what they
+    /// know will change every time they draw
+    pub fn schedule(&mut self, at: Instant) {
+        self.wakeup = Some(self.wakeup.map_or(at, |wakeup| wakeup.min(at)));
+    }
+
+    /// Whether the screen has to be drawn, clears the wakeups
+    /// that are due
+    pub fn should_draw(&mut self, animating: bool, now: Instant) -> bool {
+        let due = self.woken || animating || self.wakeup.map_or(false, |wakeup| wakeup <= now);
+        if due {
+            self.woken = false;
+            self.wakeup = None;
+        }
+        due
+    }
+}
+
+#[test]
+fn test_draws_only_when_woken_or_due() {
+    use std::time::Duration;
+
+    let now = Instant::now();
+    let ms = Duration::from_millis;
+    let mut idle = Idle::default();
+    assert!(!idle.should_draw(false, now));
+    assert!(idle.should_draw(true, now));
+
+    idle.wake();
+    assert!(idle.should_draw(false, now));
+    assert!(!idle.should_draw(false, now));
+
+    idle.schedule(now + ms(50));
+    idle.schedule(now + ms(20));
+    assert!(!idle.should_draw(false, now + ms(10)));
+    assert!(idle.should_draw(false, now + ms(20)));
+    // the later wakeup has to be scheduled again
+    assert!(!idle.should_draw(false, now + ms(50)));
+}
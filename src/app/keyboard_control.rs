@@ -11,6 +11,8 @@ pub struct Controls {
     pub ur: KeyCode,
     pub dl: KeyCode,
     pub dr: KeyCode,
+    /// Toggles the autopilot of this player
+    pub autopilot: KeyCode,
 }
 
 impl Controls {
@@ -27,8 +29,14 @@ impl Controls {
             ur: c.cvt(self.ur),
             dl: c.cvt(self.dl),
             dr: c.cvt(self.dr),
+            autopilot: c.cvt(self.autopilot),
         }
     }
+
+    /// Whether the key steers the snake in one of the six directions
+    pub fn is_direction_key(&self, key: KeyCode) -> bool {
+        [self.u, self.d, self.ul, self.ur, self.dl, self.dr].contains(&key)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -43,15 +51,27 @@ impl From<ControlSetup> for Controls {
         #[rustfmt::skip]
         let qwerty_controls = match (setup.keyboard_side, setup.hand) {
             (Side::Right, Side::Right) =>
-                Self { ul: J, u: K, ur: L, dl: M, d: Comma, dr: Period },
+                Self { ul: J, u: K, ur: L, dl: M, d: Comma, dr: Period, autopilot: Semicolon },
             (Side::Right, Side::Left) =>
-                Self { ul: J, u: K, ur: L, dl: N, d: M, dr: Comma },
+                Self { ul: J, u: K, ur: L, dl: N, d: M, dr: Comma, autopilot: H },
             (Side::Left, Side::Right) =>
-                Self { ul: S, u: D, ur: F, dl: X, d: C, dr: V },
+                Self { ul: S, u: D, ur: F, dl: X, d: C, dr: V, autopilot: A },
             (Side::Left, Side::Left) =>
-                Self { ul: S, u: D, ur: F, dl: Z, d: X, dr: C },
+                Self { ul: S, u: D, ur: F, dl: Z, d: X, dr: C, autopilot: A },
         };
 
         qwerty_controls.to_layout(setup.layout)
     }
 }
+
+/// Where the input of a local player comes from
+#[derive(Clone, Debug)]
+pub enum PlayerInput {
+    /// One half of the keyboard
+    Keyboard(ControlSetup),
+    /// A gamepad, gamepads are numbered in the order
+    /// in which they are first used
+    Gamepad(usize),
+    /// The snake steers towards the mouse cursor
+    Mouse,
+}
@@ -5,6 +5,7 @@ use ggez::Context;
 
 use crate::basic::Point;
 use crate::color::Color;
+use crate::snake::{PlayerId, TeamId};
 use crate::support::text_layout::TextLayoutExtension;
 
 /// Finite number of possible messages
@@ -17,11 +18,21 @@ pub enum MessageID {
     Notification,
     /// Stats about the game
     Stats,
+    /// Length and state of a player's snake,
+    /// shown when there is more than one player
+    PlayerHud(PlayerId),
+    /// Score of a team, shown in team play
+    TeamHud(TeamId),
+    /// Progress of the current time attack run
+    TimeAttack,
+    /// Average time spent in each phase of a frame
+    Profiler,
 }
 
 pub enum Position {
     TopLeft,
     TopRight,
+    BottomLeft,
 }
 
 pub struct Message {
@@ -39,6 +50,8 @@ pub struct Message {
 impl Message {
     pub const DEFAULT_MARGIN: f32 = 20.;
     pub const DEFAULT_FONT_SIZE: f32 = 50.;
+    /// Messages with a limited duration fade out at the end
+    const FADE_OUT: Duration = Duration::from_millis(200);
 
     // `layout` refers to where the text should be placed in the window
     pub fn default(text: String, position: Position, color: Color, duration: Option<Duration>) -> Self {
@@ -52,6 +65,13 @@ impl Message {
             disappear: duration.map(|d| Instant::now() + d),
         }
     }
+
+    /// When the message starts to look different, None if it never
+    /// does, `now` while it fades out
+    pub fn next_change(&self, now: Instant) -> Option<Instant> {
+        let fade_start = self.disappear? - Self::FADE_OUT;
+        Some(fade_start.max(now))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +108,13 @@ impl Message {
                 };
                 layout = TextLayout::top_right();
             }
+            Position::BottomLeft => {
+                dest = Point {
+                    x: self.h_margin,
+                    y: height - self.v_margin,
+                };
+                layout = TextLayout::bottom_left();
+            }
         }
 
         // fade out
@@ -96,10 +123,9 @@ impl Message {
             match deadline.checked_duration_since(Instant::now()) {
                 None => return None, // Message has reached its end of life
                 Some(time_left) => {
-                    let millis = time_left.as_millis();
-                    if millis < 200 {
+                    if time_left < Self::FADE_OUT {
                         // linear fade out
-                        color.a = millis as f32 / 200.;
+                        color.a = time_left.as_secs_f32() / Self::FADE_OUT.as_secs_f32();
                     }
                 }
             }
@@ -1,14 +1,16 @@
 // TODO: move this to rendering
 
-use ggez::event::EventHandler;
+use ggez::event::{Axis, Button as GamepadButton, EventHandler, MouseButton};
 use ggez::graphics::{Canvas, Rect};
+use ggez::input::gamepad::GamepadId;
 use ggez::input::keyboard::KeyInput;
 use ggez::Context;
 use itertools::Itertools;
-use keyboard_control::ControlSetup;
+use keyboard_control::PlayerInput;
 pub use palette::Palette;
 use screen::{Game, Screen};
 use snake::builder::Builder as SnakeBuilder;
+use teams::TeamMode;
 
 use crate::app::screen::{DebugScenario, StartScreen};
 use crate::apple::spawn::SpawnPolicy;
@@ -16,42 +18,75 @@ use crate::basic::CellDim;
 use crate::error::{Error, ErrorConversion, Result};
 use crate::snake::eat_mechanics::{EatBehavior, EatMechanics, Knowledge};
 use crate::snake::SegmentType;
-use crate::snake_control::pathfinder;
+use crate::snake_control::pathfinder::{self, Costs};
 use crate::{by_segment_type, by_snake_type, snake, snake_control};
 
 mod distance_grid;
 pub(crate) mod fps_control;
 pub mod game_context;
+pub(crate) mod idle;
 pub mod keyboard_control;
 pub mod message;
 mod palette;
-mod prefs;
+pub(crate) mod prefs;
+pub mod profiler;
 pub(crate) mod screen;
-mod snake_management;
+pub(crate) mod snake_management;
 pub mod stats;
+pub mod teams;
+pub mod time_attack;
 
 pub struct App {
     screen: Screen,
 }
 
 impl App {
-    pub fn new(players: Vec<ControlSetup>, ctx: &mut Context) -> Self {
-        assert_eq!(
-            players.iter().map(|cs| cs.layout).dedup().count(),
-            1,
+    pub fn new(players: Vec<PlayerInput>, team_mode: TeamMode, ctx: &mut Context) -> Self {
+        let keyboard_setups: Vec<_> = players
+            .iter()
+            .filter_map(|input| match input {
+                PlayerInput::Keyboard(control_setup) => Some(control_setup),
+                _ => None,
+            })
+            .collect();
+
+        assert!(
+            keyboard_setups.iter().map(|cs| cs.layout).unique().count() <= 1,
             "found different keyboard layouts for different players"
         );
 
         assert_eq!(
-            players.iter().map(|cs| cs.keyboard_side).dedup().count(),
-            players.len(),
+            keyboard_setups.iter().map(|cs| cs.keyboard_side).unique().count(),
+            keyboard_setups.len(),
             "found multiple players on the same side of the keyboard"
         );
 
+        let mouse_players = players
+            .iter()
+            .filter(|input| matches!(input, PlayerInput::Mouse))
+            .count();
+        assert!(mouse_players <= 1, "found multiple players using the mouse");
+
+        let gamepads: Vec<_> = players
+            .iter()
+            .filter_map(|input| match input {
+                PlayerInput::Gamepad(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            gamepads.iter().unique().count(),
+            gamepads.len(),
+            "found multiple players using the same gamepad"
+        );
+
+        let palette = Palette::dark();
+
         let seeds: Vec<_> = players
             .into_iter()
-            .map(|control_setup| {
-                let eat_mechanics = EatMechanics::new(
+            .enumerate()
+            .map(|(id, input)| {
+                let mut eat_mechanics = EatMechanics::new(
                     by_segment_type! {
                         SegmentType::DISCR_EATEN => EatBehavior::PassOver,
                         _ => EatBehavior::Crash,
@@ -66,19 +101,37 @@ impl App {
                         },
                     },
                 );
+                if let Some(team_rules) = team_mode.team_rules() {
+                    eat_mechanics = eat_mechanics.with_team_rules(team_rules);
+                }
 
                 let knowledge = Knowledge::accurate(&eat_mechanics);
 
-                SnakeBuilder::default()
-                    .snake_type(snake::Type::Player)
+                let controller = match input {
+                    PlayerInput::Keyboard(control_setup) => {
+                        snake_control::Template::Keyboard { control_setup, knowledge }
+                    }
+                    PlayerInput::Gamepad(index) => snake_control::Template::Gamepad { index },
+                    PlayerInput::Mouse => snake_control::Template::Mouse,
+                };
+
+                let mut seed = SnakeBuilder::default()
+                    .snake_type(snake::Type::Player { id })
                     .eat_mechanics(eat_mechanics)
-                    .palette(snake::PaletteTemplate::rainbow(true))
-                    // .palette(PaletteTemplate::dark_blue_to_red(false))
-                    // .palette(PaletteTemplate::zebra())
-                    .controller(snake_control::Template::Keyboard { control_setup, knowledge })
+                    .palette(snake::PaletteTemplate::rainbow(true));
+                // .palette(PaletteTemplate::dark_blue_to_red(false))
+                // .palette(PaletteTemplate::zebra())
+
+                if let Some(team) = team_mode.team_of(id) {
+                    seed = seed.team(team).palette(palette.team_palette(team));
+                }
+
+                seed.controller(controller)
                     .speed(1.)
                     .autopilot(pathfinder::Template::WithBackup {
-                        main: Box::new(pathfinder::Template::WeightedBFS),
+                        main: Box::new(pathfinder::Template::Survival {
+                            main: Box::new(pathfinder::Template::WeightedBFS(Costs::evolved_or_default())),
+                        }),
                         backup: Box::new(pathfinder::Template::SpaceFilling),
                     })
                 // .snake_control(snake_control::Template::Mouse)
@@ -101,7 +154,7 @@ impl App {
                     cell_dim,
                     3.,
                     seeds,
-                    Palette::dark(),
+                    palette,
                     SpawnPolicy::Random { apple_count: 5 },
                     ctx,
                 )),
@@ -195,6 +248,18 @@ impl EventHandler<Error> for App {
         self.screen.mouse_motion_event(ctx, x, y, dx, dy)
     }
 
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> Result {
+        self.screen.mouse_button_down_event(ctx, button, x, y)
+    }
+
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, button: GamepadButton, id: GamepadId) -> Result {
+        self.screen.gamepad_button_down_event(ctx, button, id)
+    }
+
+    fn gamepad_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) -> Result {
+        self.screen.gamepad_axis_event(ctx, axis, value, id)
+    }
+
     fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> Result {
         self.screen.key_down_event(ctx, input, repeated)
     }
@@ -208,4 +273,8 @@ impl EventHandler<Error> for App {
 
         self.screen.resize_event(ctx, width, height)
     }
+
+    fn focus_event(&mut self, ctx: &mut Context, gained: bool) -> Result {
+        self.screen.focus_event(ctx, gained)
+    }
 }
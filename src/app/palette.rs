@@ -1,6 +1,6 @@
 use ggez::graphics::Color;
 
-use crate::snake;
+use crate::snake::{self, TeamId};
 
 macro_rules! gray {
     ($lightness:expr) => {
@@ -28,6 +28,10 @@ pub struct Palette {
     pub palette_competitor: snake::PaletteTemplate,
     pub palette_killer: snake::PaletteTemplate,
     pub palette_rain: snake::PaletteTemplate,
+    pub palette_ghost: snake::PaletteTemplate,
+    /// Palettes of player snakes in team play, teams
+    /// beyond the last palette reuse the palettes
+    pub team_palettes: Vec<snake::PaletteTemplate>,
 }
 
 #[allow(dead_code)]
@@ -48,6 +52,21 @@ impl Palette {
             palette_killer: snake::PaletteTemplate::dark_blue_to_red(false),
             // palette_killer: snake::PaletteTemplate::dark_rainbow(true),
             palette_rain: snake::PaletteTemplate::gray_gradient(0.5, false),
+            palette_ghost: snake::PaletteTemplate::ghost(),
+            team_palettes: vec![
+                // red
+                snake::PaletteTemplate::hsl_gradient(-20., 20., 0.5, 0.7, true),
+                // blue
+                snake::PaletteTemplate::hsl_gradient(250., 200., 0.5, 0.7, true),
+                // green
+                snake::PaletteTemplate::hsl_gradient(150., 90., 0.4, 0.7, true),
+                // yellow
+                snake::PaletteTemplate::hsl_gradient(40., 65., 0.5, 0.7, true),
+            ],
         }
     }
+
+    pub fn team_palette(&self, team: TeamId) -> snake::PaletteTemplate {
+        self.team_palettes[team % self.team_palettes.len()]
+    }
 }
@@ -4,6 +4,7 @@ use enum_rotate::EnumRotate;
 
 use crate::basic::Food;
 use crate::rendering;
+use crate::snake_control::killer::Difficulty;
 
 #[derive(Copy, Clone, EnumRotate)]
 pub enum DrawGrid {
@@ -12,11 +13,44 @@ pub enum DrawGrid {
     None,
 }
 
+/// When a game with more than one player ends
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumRotate)]
+pub enum GameOverRule {
+    /// The game ends as soon as any snake crashes
+    FirstCrash,
+    /// Crashed players are taken out of the game, the
+    /// game ends when at most one player is left
+    LastStanding,
+}
+
+/// What the autopilot does when a player is about to crash
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumRotate)]
+pub enum Assist {
+    Off,
+    /// Shows the way out without taking it
+    Suggest,
+    /// Takes the way out, `Prefs::assist_saves` times per game,
+    /// then only shows it
+    Save,
+}
+
+/// How competitors spawned by apples choose their moves
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumRotate)]
+pub enum CompetitorAi {
+    /// Heads for the cheapest apple to get to
+    Pathfinder,
+    /// Searches a few moves ahead, expecting nearby snakes to
+    /// get in the way
+    Adversarial,
+}
+
 pub struct Prefs {
     pub draw_grid: DrawGrid,
     pub draw_border: bool,
     pub draw_distance_grid: bool,
     pub draw_player_path: bool,
+    /// Shows every player the autopilot's next move
+    pub draw_move_hints: bool,
 
     pub display_fps: bool,
     pub display_stats: bool,
@@ -27,6 +61,14 @@ pub struct Prefs {
     pub prob_spawn_competitor: f64,
     pub prob_spawn_killer: f64,
     pub prob_spawn_rain: f64,
+    /// Applies to competitors spawned from then on
+    pub competitor_ai: CompetitorAi,
+    /// Applies to killers spawned from then on
+    pub killer_difficulty: Difficulty,
+    pub game_over_rule: GameOverRule,
+    /// Only applies while the player's autopilot is off
+    pub assist: Assist,
+    pub assist_saves: usize,
 
     pub draw_style: rendering::Style,
     // pub draw_ai_debug_artifacts: bool,
@@ -40,6 +82,7 @@ impl Default for Prefs {
             draw_border: false,
             draw_distance_grid: false,
             draw_player_path: false,
+            draw_move_hints: false,
 
             display_fps: false,
             display_stats: false,
@@ -50,6 +93,11 @@ impl Default for Prefs {
             prob_spawn_competitor: 0.025,
             prob_spawn_killer: 0.015,
             prob_spawn_rain: 0.002,
+            competitor_ai: CompetitorAi::Pathfinder,
+            killer_difficulty: Difficulty::Normal,
+            game_over_rule: GameOverRule::FirstCrash,
+            assist: Assist::Off,
+            assist_saves: 3,
 
             draw_style: rendering::Style::Smooth,
             // draw_ai_debug_artifacts: false,
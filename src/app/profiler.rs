@@ -0,0 +1,302 @@
+//! Measures how long each phase of a frame takes
+//!
+//! Phases are timed with [`span`], which does nothing unless the
+//! profiler is enabled. The last frames are kept for the overlay
+//! and can be exported as a Chrome trace, which can be opened in
+//! `chrome://tracing` or Perfetto.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fs, io, mem};
+
+use serde_json::{json, Value};
+
+use crate::app::message::{self, Message};
+use crate::color::Color;
+
+/// Where the trace is exported to by default
+pub const TRACE_FILE: &str = "trace.json";
+/// Number of frames kept for the overlay and the trace
+pub const HISTORY_LEN: usize = 300;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref PROFILER: Mutex<Profiler> = Mutex::new(Profiler::new());
+}
+
+thread_local! {
+    /// Number of spans currently open on this thread
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Phase {
+    /// Everything that happens in `EventHandler::update`
+    Update,
+    /// A controller deciding on a direction, by snake type
+    Decide(&'static str),
+    Pathfinding,
+    Collisions,
+    Spawning,
+    /// Everything that happens in `EventHandler::draw`
+    Draw,
+    GridMesh,
+    BorderMesh,
+    SnakeMesh,
+    AppleMesh,
+    DistanceGridMesh,
+    PlayerPathMesh,
+    /// Drawing the meshes to the canvas and presenting it
+    Submit,
+}
+
+impl Phase {
+    /// Groups phases in the trace
+    fn category(self) -> &'static str {
+        use Phase::*;
+        match self {
+            Update | Decide(_) | Pathfinding | Collisions | Spawning => "update",
+            Draw | GridMesh | BorderMesh | SnakeMesh | AppleMesh | DistanceGridMesh | PlayerPathMesh | Submit => "draw",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Phase::*;
+        match self {
+            Update => write!(f, "update"),
+            Decide(snake_type) => write!(f, "decide ({snake_type})"),
+            Pathfinding => write!(f, "pathfinding"),
+            Collisions => write!(f, "collisions"),
+            Spawning => write!(f, "spawning"),
+            Draw => write!(f, "draw"),
+            GridMesh => write!(f, "grid mesh"),
+            BorderMesh => write!(f, "border mesh"),
+            SnakeMesh => write!(f, "snake mesh"),
+            AppleMesh => write!(f, "apple mesh"),
+            DistanceGridMesh => write!(f, "distance grid mesh"),
+            PlayerPathMesh => write!(f, "player path mesh"),
+            Submit => write!(f, "submit"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Event {
+    pub phase: Phase,
+    /// 0 for the main thread, worker threads are numbered from 1
+    pub thread: usize,
+    /// Number of spans that were already open on the same thread
+    pub depth: usize,
+    /// Relative to the start of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+pub struct Frame {
+    pub start: Instant,
+    pub duration: Duration,
+    pub events: Vec<Event>,
+}
+
+struct Profiler {
+    /// Start of the frame that is being recorded
+    frame_start: Instant,
+    events: Vec<Event>,
+    history: VecDeque<Frame>,
+}
+
+impl Profiler {
+    fn new() -> Self {
+        Self {
+            frame_start: Instant::now(),
+            events: vec![],
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    fn record(&mut self, phase: Phase, thread: usize, depth: usize, start: Instant, duration: Duration) {
+        self.events.push(Event {
+            phase,
+            thread,
+            depth,
+            start: start.saturating_duration_since(self.frame_start),
+            duration,
+        });
+    }
+
+    fn end_frame(&mut self, now: Instant) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(Frame {
+            start: self.frame_start,
+            duration: now.saturating_duration_since(self.frame_start),
+            events: mem::take(&mut self.events),
+        });
+        self.frame_start = now;
+    }
+}
+
+/// Times a phase until it is dropped
+#[must_use]
+pub struct Span {
+    phase: Phase,
+    /// None if the profiler was disabled when the span started
+    start: Option<Instant>,
+}
+
+pub fn span(phase: Phase) -> Span {
+    let start = enabled().then(|| {
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+        Instant::now()
+    });
+    Span { phase, start }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let duration = start.elapsed();
+        let depth = DEPTH.with(|depth| {
+            depth.set(depth.get() - 1);
+            depth.get()
+        });
+        let thread = rayon::current_thread_index().map_or(0, |index| index + 1);
+        PROFILER
+            .lock()
+            .unwrap()
+            .record(self.phase, thread, depth, start, duration);
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Enabling the profiler starts a new history, disabling it
+/// keeps the history so that it can still be exported
+pub fn set_enabled(enabled: bool) {
+    if enabled {
+        *PROFILER.lock().unwrap() = Profiler::new();
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Call once per frame, after drawing
+pub fn end_frame() {
+    if enabled() {
+        PROFILER.lock().unwrap().end_frame(Instant::now());
+    }
+}
+
+/// Gives access to the recorded frames, oldest first
+pub fn with_history<T>(f: impl FnOnce(&VecDeque<Frame>) -> T) -> T {
+    f(&PROFILER.lock().unwrap().history)
+}
+
+/// Average time per frame spent in each phase, slowest first,
+/// phases on worker threads overlap so they can add up to more
+/// than the frame
+pub fn summary(history: &VecDeque<Frame>) -> String {
+    let mut totals: Vec<(Phase, Duration)> = vec![];
+    for event in history.iter().flat_map(|frame| &frame.events) {
+        match totals.iter_mut().find(|(phase, _)| *phase == event.phase) {
+            Some((_, total)) => *total += event.duration,
+            None => totals.push((event.phase, event.duration)),
+        }
+    }
+    totals.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let frames = history.len().max(1) as f64;
+    totals
+        .iter()
+        .map(|(phase, total)| format!("{phase}: {:.2}ms", total.as_secs_f64() * 1000. / frames))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The summary in the top-right corner, below notifications
+pub fn get_summary_message(history: &VecDeque<Frame>) -> Message {
+    Message {
+        text: summary(history),
+        position: message::Position::TopRight,
+        h_margin: Message::DEFAULT_MARGIN,
+        v_margin: Message::DEFAULT_MARGIN * 2. + Message::DEFAULT_FONT_SIZE,
+        font_size: Message::DEFAULT_FONT_SIZE / 3.,
+        color: Color::WHITE,
+        disappear: None,
+    }
+}
+
+/// The recorded frames in the Trace Event Format, times
+/// are in microseconds since the oldest frame
+pub fn chrome_trace(history: &VecDeque<Frame>) -> Value {
+    let Some(epoch) = history.front().map(|frame| frame.start) else {
+        return json!({ "traceEvents": [] });
+    };
+    let micros = |duration: Duration| duration.as_nanos() as f64 / 1000.;
+
+    let events: Vec<_> = history
+        .iter()
+        .flat_map(|frame| {
+            let frame_offset = frame.start.saturating_duration_since(epoch);
+            frame.events.iter().map(move |event| {
+                json!({
+                    "name": event.phase.to_string(),
+                    "cat": event.phase.category(),
+                    "ph": "X",
+                    "ts": micros(frame_offset + event.start),
+                    "dur": micros(event.duration),
+                    "pid": 1,
+                    "tid": event.thread,
+                })
+            })
+        })
+        .collect();
+    json!({ "traceEvents": events })
+}
+
+pub fn export_chrome_trace(path: impl AsRef<Path>) -> io::Result<()> {
+    let trace = with_history(chrome_trace);
+    fs::write(path, format!("{trace}\n"))
+}
+
+#[test]
+fn test_chrome_trace() {
+    let mut profiler = Profiler::new();
+    let start = profiler.frame_start;
+    let ms = Duration::from_millis;
+    profiler.record(Phase::Decide("competitor"), 1, 1, start + ms(1), ms(2));
+    profiler.record(Phase::Update, 0, 0, start, ms(5));
+    profiler.end_frame(start + ms(16));
+    profiler.record(Phase::Draw, 0, 0, start + ms(17), ms(3));
+    profiler.end_frame(start + ms(32));
+
+    let history = &profiler.history;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].events[0].start, ms(1));
+    assert_eq!(
+        summary(history),
+        "update: 2.50ms\ndraw: 1.50ms\ndecide (competitor): 1.00ms"
+    );
+
+    let trace = chrome_trace(history);
+    let events = trace["traceEvents"].as_array().unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0]["name"], "decide (competitor)");
+    assert_eq!(events[0]["tid"], 1);
+    assert_eq!(events[0]["ts"], 1000.);
+    assert_eq!(events[0]["dur"], 2000.);
+    assert_eq!(events[2]["cat"], "draw");
+    assert_eq!(events[2]["ts"], 17000.);
+}
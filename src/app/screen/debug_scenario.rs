@@ -11,20 +11,31 @@ use crate::app::game_context::GameContext;
 use crate::app::prefs::Prefs;
 use crate::app::screen::board_dim::{calculate_board_dim, calculate_offset};
 use crate::app::screen::Environment;
-use crate::app::snake_management::{advance_snakes, find_collisions, handle_collisions};
+use crate::app::snake_management::{advance_snakes, find_collisions, handle_collisions, update_dirs};
 use crate::app::stats::Stats;
 use crate::app::Palette;
 use crate::apple::spawn::{spawn_apples, SpawnPolicy};
 use crate::apple::Apple;
+use crate::basic::board::Board;
 use crate::basic::{CellDim, Dir, HexDim, HexPoint, Point};
 use crate::color::Color;
 use crate::error::{Error, ErrorConversion, Result};
+use crate::rendering::{Changes, Layers};
 use crate::snake::builder::Builder as SnakeBuilder;
 use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
-use crate::snake_control::pathfinder;
-use crate::view::snakes::OtherSnakes;
+use crate::snake_control::pathfinder::{self, Costs};
+use crate::support::invert::Invert;
 use crate::{app, apple, rendering, snake, snake_control};
 
+/// Meshes of a scenario in the order they are drawn in
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum ScenarioLayer {
+    Grid,
+    Border,
+    Snakes,
+    Apples,
+}
+
 pub struct DebugScenario {
     env: Environment,
     fps_control: FpsControl,
@@ -35,6 +46,7 @@ pub struct DebugScenario {
     seeds: Vec<SnakeBuilder>,
 
     stats: Stats,
+    layers: Layers<ScenarioLayer>,
 }
 
 // Constructors
@@ -67,6 +79,7 @@ impl DebugScenario {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext {
                     board_dim: HexDim { h: 20, v: 10 },
                     cell_dim,
@@ -84,6 +97,7 @@ impl DebugScenario {
             seeds: vec![seed1, seed2],
 
             stats: Default::default(),
+            layers: Self::layers(),
         };
         this.restart();
         this.fps_control.pause();
@@ -119,6 +133,7 @@ impl DebugScenario {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext {
                     board_dim: HexDim { h: 20, v: 10 },
                     cell_dim,
@@ -136,6 +151,7 @@ impl DebugScenario {
             seeds: vec![seed1, seed2],
 
             stats: Default::default(),
+            layers: Self::layers(),
         };
         this.restart();
         this.fps_control.pause();
@@ -171,6 +187,7 @@ impl DebugScenario {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext {
                     board_dim: HexDim { h: 20, v: 10 },
                     cell_dim,
@@ -188,6 +205,7 @@ impl DebugScenario {
             seeds: vec![seed1, seed2],
 
             stats: Default::default(),
+            layers: Self::layers(),
         };
         this.restart();
         this.env.apples = vec![Apple {
@@ -203,6 +221,7 @@ impl DebugScenario {
         const NUM_SNAKES: usize = 100;
 
         let rng = &mut thread_rng();
+        let costs = Costs::evolved_or_default();
         let seeds: Vec<_> = (0..NUM_SNAKES)
             .map(|i| {
                 SnakeBuilder::default()
@@ -218,7 +237,9 @@ impl DebugScenario {
                     // .controller(snake_control::Template::AStar {
                     //     passthrough_knowledge: PassthroughKnowledge::always(false),
                     // })
-                    .controller(snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS))
+                    .controller(snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS(
+                        costs,
+                    )))
             })
             .collect();
 
@@ -226,6 +247,7 @@ impl DebugScenario {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext {
                     board_dim: HexDim { h: 0, v: 0 },
                     cell_dim: Default::default(),
@@ -243,6 +265,7 @@ impl DebugScenario {
             seeds,
 
             stats: Stats::default(),
+            layers: Self::layers(),
         };
         this.restart();
         this.fps_control.pause();
@@ -303,6 +326,7 @@ impl DebugScenario {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext {
                     board_dim: HexDim { h: 20, v: 15 },
                     cell_dim,
@@ -320,6 +344,7 @@ impl DebugScenario {
             seeds: iter::once(wall_seed).chain(crash_seeds).collect(),
 
             stats: Default::default(),
+            layers: Self::layers(),
         };
         this.restart();
         this.fps_control.pause();
@@ -328,35 +353,53 @@ impl DebugScenario {
 }
 
 impl DebugScenario {
+    fn layers() -> Layers<ScenarioLayer> {
+        let fixed = Changes::DIM | Changes::PREFS;
+        let moving = fixed | Changes::SNAKES | Changes::MOTION;
+        Layers::new([
+            (ScenarioLayer::Grid, fixed),
+            (ScenarioLayer::Border, fixed),
+            (ScenarioLayer::Snakes, moving | Changes::PLAY_STATE),
+            (ScenarioLayer::Apples, fixed | Changes::APPLES | Changes::ANIMATION),
+        ])
+    }
+
     fn update_dim(&mut self, ctx: &Context) {
         let gtx = &mut self.env.gtx;
         if self.fit_to_window {
-            gtx.board_dim = calculate_board_dim(ctx, gtx.cell_dim);
+            let board_dim = calculate_board_dim(ctx, gtx.cell_dim);
+            if gtx.board_dim != board_dim {
+                gtx.board_dim = board_dim;
+                self.layers.invalidate(Changes::DIM);
+            }
         }
         self.offset = Some(calculate_offset(ctx, gtx.board_dim, gtx.cell_dim));
     }
 
     fn restart(&mut self) {
+        self.env.clear();
         self.env.snakes = self
             .seeds
             .iter()
             .map(SnakeBuilder::build)
             .map(result::Result::unwrap)
             .collect();
-        self.env.apples = vec![];
         self.env.gtx.apple_spawn_policy.reset();
         self.fps_control.pause();
+        self.layers.invalidate(Changes::SNAKES | Changes::APPLES);
     }
 
     fn spawn_apples(&mut self) {
         spawn_apples(&mut self.env);
+        self.layers.invalidate(Changes::APPLES);
     }
 
     fn advance_snakes(&mut self, ctx: &Context) {
-        advance_snakes(&mut self.env, self.fps_control.context(), ctx);
+        advance_snakes(&mut self.env, self.fps_control.context(), Some(ctx));
 
         let collisions = find_collisions(&self.env);
         let (spawn_snakes, game_over) = handle_collisions(&mut self.env, &collisions);
+        self.layers.invalidate(Changes::SNAKES | Changes::APPLES);
 
         if game_over {
             self.fps_control.game_over();
@@ -379,37 +422,41 @@ impl EventHandler<Error> for DebugScenario {
     fn draw(&mut self, ctx: &mut Context) -> Result {
         self.fps_control.graphics_frame();
 
-        let mut canvas = Canvas::from_frame(ctx, Some(*Color::BLACK));
-
         if self.offset.is_none() {
             self.update_dim(ctx)
         }
 
-        let offset = self.offset.unwrap();
-        let draw_param = DrawParam::default().dest(offset);
+        let state = self.fps_control.state();
+        self.layers.start_frame(state);
+        if self.env.apples.iter().any(|apple| apple.apple_type.is_animated()) {
+            self.layers.invalidate(Changes::ANIMATION);
+        }
 
         let env = &mut self.env;
-
-        let grid_mesh = rendering::grid_mesh(&env.gtx, ctx)?;
-        canvas.draw(&grid_mesh, draw_param);
-
-        let border_mesh = rendering::border_mesh(&env.gtx, ctx)?;
-        canvas.draw(&border_mesh, draw_param);
-
+        let layers = &mut self.layers;
+        let stats = &mut self.stats;
         let ftx = self.fps_control.context();
 
-        for snake_index in 0..env.snakes.len() {
-            let (snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, snake_index);
-            snake.update_dir(other_snakes, &env.apples, &env.gtx, ftx, ctx);
+        if state == fps_control::State::Playing {
+            update_dirs(env, ftx, Some(ctx));
         }
 
-        let snake_mesh = rendering::snake_mesh(&mut env.snakes, &env.gtx, ftx, ctx, &mut self.stats)?;
-        canvas.draw(&snake_mesh, draw_param);
+        layers.update(ScenarioLayer::Grid, || rendering::grid_mesh(&env.gtx, ctx).map(Some))?;
+        layers.update(ScenarioLayer::Border, || {
+            rendering::border_mesh(&env.gtx, ctx).map(Some)
+        })?;
+        layers.update(ScenarioLayer::Snakes, || {
+            rendering::snake_mesh(&mut env.snakes, &env.gtx, ftx, ctx, stats).map(Some)
+        })?;
+        layers.update(ScenarioLayer::Apples, || {
+            (!env.apples.is_empty())
+                .then(|| rendering::apple_mesh(&env.apples, &env.gtx, ftx, ctx, stats))
+                .invert()
+        })?;
 
-        if !env.apples.is_empty() {
-            let apple_mesh = rendering::apple_mesh(&env.apples, &env.gtx, ftx, ctx, &mut self.stats)?;
-            canvas.draw(&apple_mesh, draw_param);
-        }
+        let mut canvas = Canvas::from_frame(ctx, Some(*Color::BLACK));
+        self.layers
+            .draw(&mut canvas, DrawParam::default().dest(self.offset.unwrap()));
 
         canvas
             .finish(ctx)
@@ -1,36 +1,63 @@
 use std::collections::HashMap;
+use std::time::Instant;
 
 use enum_rotate::EnumRotate;
-use ggez::event::EventHandler;
-use ggez::graphics::{Canvas, DrawParam, Mesh};
+use ggez::event::{Axis, Button as GamepadButton, EventHandler, MouseButton};
+use ggez::graphics::{Canvas, DrawParam};
+use ggez::input::gamepad::GamepadId;
 use ggez::input::keyboard::{KeyCode, KeyInput};
 use ggez::input::mouse;
 use ggez::Context;
+use itertools::Itertools;
 use rand::prelude::*;
 
 use crate::app::distance_grid::DistanceGrid;
 use crate::app::fps_control::{self, FpsControl};
 use crate::app::game_context::GameContext;
+use crate::app::idle::Idle;
+use crate::app::keyboard_control::Controls;
 use crate::app::message;
 use crate::app::message::{Message, MessageDrawable, MessageID};
 use crate::app::palette::Palette;
-use crate::app::prefs::{DrawGrid, Prefs};
+use crate::app::prefs::{Assist, CompetitorAi, DrawGrid, GameOverRule, Prefs};
+use crate::app::profiler::{self, Phase};
 use crate::app::screen::board_dim::{calculate_board_dim, calculate_offset};
 use crate::app::screen::Environment;
-use crate::app::snake_management::{advance_snakes, find_collisions, handle_collisions, spawn_snakes};
+use crate::app::snake_management::{
+    advance_snakes, find_collisions, handle_collisions, player_spawn_points, spawn_snakes, update_dirs, Collision,
+};
 use crate::app::stats::Stats;
+use crate::app::teams::TeamScores;
+use crate::app::time_attack::{Finish, TimeAttack};
 use crate::apple::spawn::{spawn_apples, SpawnPolicy};
 use crate::apple::{self, Apple};
-use crate::basic::{CellDim, Dir, Food, HexDim, HexPoint, Point};
+use crate::basic::board::Board;
+use crate::basic::{CellDim, Food, HexDim, HexPoint, Point};
 use crate::color::Color;
 use crate::error::{Error, ErrorConversion, Result};
 use crate::rendering;
+use crate::rendering::{Changes, Layers};
 use crate::snake::builder::Builder as SnakeBuilder;
-use crate::snake::{self, Snake};
+use crate::snake::{self, PlayerId, Snake, TeamId};
+use crate::snake_control;
+use crate::snake_control::killer::Difficulty;
 use crate::support::flip::Flip;
 use crate::support::invert::Invert;
 use crate::view::snakes::OtherSnakes;
 
+/// Meshes of the game in the order they are drawn in
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum GameLayer {
+    DistanceGrid,
+    Grid,
+    PlayerPath,
+    Snakes,
+    Apples,
+    Hints,
+    Assist,
+    Border,
+}
+
 #[derive(Copy, Clone)]
 enum Boost {
     NoBoost,
@@ -38,7 +65,7 @@ enum Boost {
 }
 
 pub struct Game {
-    env: Environment,
+    env: Environment<StdRng>,
     fps_control: FpsControl,
     boost: Boost,
 
@@ -48,23 +75,21 @@ pub struct Game {
     seeds: Vec<SnakeBuilder>,
     animated_apples: bool,
 
+    /// Gamepads in the order in which they were first used,
+    /// the position of a gamepad is its index
+    gamepads: Vec<GamepadId>,
+
+    team_scores: TeamScores,
+    /// Racing against the ghost of the best run, single player only
+    time_attack: Option<TimeAttack>,
+
     distance_grid: DistanceGrid,
 
     messages: HashMap<MessageID, Message>,
 
-    grid_mesh: Option<Mesh>,
-    border_mesh: Option<Mesh>,
-    snake_mesh: Option<Mesh>,
-    apple_mesh: Option<Mesh>,
-    distance_grid_mesh: Option<Mesh>,
-    player_path_mesh: Option<Mesh>,
-
-    // TODO: move this mechanism to Control
-    /// Consider the draw cache invalid for the
-    /// next n frames, forces a redraw even if
-    /// nothing changed, this is necessary to
-    /// avoid visual glitches
-    draw_cache_invalid: usize,
+    layers: Layers<GameLayer>,
+    /// Stops drawing while paused and nothing changes
+    idle: Idle,
 }
 
 impl Game {
@@ -83,6 +108,7 @@ impl Game {
             env: Environment {
                 snakes: vec![],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext::new(
                     // updated immediately after creation
                     HexPoint { h: 0, v: 0 },
@@ -91,7 +117,7 @@ impl Game {
                     Prefs::default(),
                     apple_spawn_policy,
                 ),
-                rng: thread_rng(),
+                rng: StdRng::from_entropy(),
             },
             fps_control: FpsControl::new(starting_fps),
             boost: Boost::NoBoost,
@@ -102,25 +128,41 @@ impl Game {
             seeds: seeds.into_iter().map(Into::into).collect(),
             animated_apples: false,
 
+            gamepads: vec![],
+
+            team_scores: TeamScores::default(),
+            time_attack: None,
+
             distance_grid: DistanceGrid::new(),
 
             messages: HashMap::new(),
 
-            grid_mesh: None,
-            border_mesh: None,
-            snake_mesh: None,
-            apple_mesh: None,
-            distance_grid_mesh: None,
-            player_path_mesh: None,
-
-            draw_cache_invalid: 0,
+            layers: Self::layers(),
+            idle: Idle::default(),
         };
         this.update_dim(ctx);
         // warning: this spawns apples before there are any snakes
         this.restart();
+        this.idle.wake();
         this
     }
 
+    /// The layers in drawing order and the changes that redraw them
+    fn layers() -> Layers<GameLayer> {
+        let fixed = Changes::DIM | Changes::PREFS;
+        let moving = fixed | Changes::SNAKES | Changes::MOTION;
+        Layers::new([
+            (GameLayer::DistanceGrid, moving),
+            (GameLayer::Grid, fixed),
+            (GameLayer::PlayerPath, moving | Changes::APPLES),
+            (GameLayer::Snakes, moving | Changes::PLAY_STATE),
+            (GameLayer::Apples, fixed | Changes::APPLES | Changes::ANIMATION),
+            (GameLayer::Hints, moving | Changes::APPLES),
+            (GameLayer::Assist, moving),
+            (GameLayer::Border, fixed),
+        ])
+    }
+
     fn update_dim(&mut self, ctx: &Context) {
         let env = &mut self.env;
 
@@ -135,7 +177,7 @@ impl Game {
             if env
                 .snakes
                 .iter()
-                .any(|s| s.snake_type == snake::Type::Player && !board_dim.contains(s.head().pos))
+                .any(|s| matches!(s.snake_type, snake::Type::Player { .. }) && !board_dim.contains(s.head().pos))
             {
                 println!("warning: player snake outside of board, restarting");
                 self.restart();
@@ -148,81 +190,50 @@ impl Game {
                 self.spawn_apples();
             }
 
-            // invalidate
-            self.grid_mesh = None;
-            self.border_mesh = None;
-            self.apple_mesh = None;
-            self.snake_mesh = None;
-            self.distance_grid_mesh = None;
+            self.layers.invalidate(Changes::DIM);
             self.distance_grid.invalidate();
-            self.player_path_mesh = None;
         }
     }
 
-    // TODO: R as a restart shortcut but only in debug mode
     fn restart(&mut self) {
-        let env = &mut self.env;
-
-        env.snakes.clear();
-        env.apples.clear();
-
-        self.snake_mesh = None;
-        self.apple_mesh = None;
-        self.distance_grid_mesh = None;
-        self.player_path_mesh = None;
-
-        // seeds without a defined spawn point
-        let unpositioned = self
-            .seeds
-            .iter()
-            .filter(|seed| !matches!(seed.snake_type, Some(snake::Type::Simulated { .. })))
-            .count();
+        if let Some(time_attack) = &mut self.time_attack {
+            // the same apples in the same places every run
+            self.env.rng = StdRng::seed_from_u64(time_attack.seed);
+            time_attack.restart();
+        }
 
-        // TODO: clean this mess
-        let mut unpositioned_dir = Dir::U;
-        let mut unpositioned_h_pos: Box<dyn Iterator<Item = isize>> = if unpositioned > 0 {
-            const DISTANCE_BETWEEN_SNAKES: isize = 1;
+        let env = &mut self.env;
 
-            let total_width = (unpositioned - 1) as isize * DISTANCE_BETWEEN_SNAKES + 1;
-            assert!(total_width < env.gtx.board_dim.h, "snakes spread too wide");
+        env.clear();
+        self.team_scores.clear();
+        self.layers.invalidate(Changes::SNAKES | Changes::APPLES);
 
-            let half = total_width / 2;
-            let middle = env.gtx.board_dim.h / 2;
-            let start = middle - half;
-            let end = start + total_width - 1;
-
-            Box::new((start..=end).step_by(DISTANCE_BETWEEN_SNAKES as usize))
-        } else {
-            Box::new(std::iter::empty())
-        };
+        // seeds without a defined spawn point are spread across the board
+        let unpositioned = self.seeds.iter().filter(|seed| seed.pos.is_none()).count();
+        let mut spawn_points = player_spawn_points(unpositioned, env.gtx.board_dim).into_iter();
 
         for seed in self.seeds.iter() {
-            match seed.snake_type {
-                Some(snake::Type::Simulated) => {
-                    // expected to have initial position, direction, and length
-                    env.snakes.push(seed.build().unwrap());
-                }
-                _ => {
-                    env.snakes.push(
-                        seed.clone()
-                            .pos(HexPoint {
-                                h: unpositioned_h_pos.next().unwrap(),
-                                v: env.gtx.board_dim.v / 2,
-                            })
-                            .dir(unpositioned_dir)
-                            .len(10)
-                            .build()
-                            .unwrap(),
-                    );
-
-                    // alternate
-                    unpositioned_dir = -unpositioned_dir;
+            let snake = match seed.pos {
+                // expected to have initial position, direction, and length
+                Some(_) => seed.build(),
+                None => {
+                    let (pos, dir) = spawn_points.next().unwrap();
+                    let mut seed = seed.clone().pos(pos).dir(dir);
+                    seed.len.get_or_insert(10);
+                    seed.build()
                 }
-            }
+            };
+            env.snakes.push(snake.unwrap());
         }
 
-        let left = unpositioned_h_pos.count();
-        assert_eq!(left, 0, "unexpected iterator length");
+        let ghost = self
+            .time_attack
+            .as_ref()
+            .and_then(|time_attack| time_attack.ghost(&env.gtx.palette));
+        if let (Some(ghost), Some(player)) = (ghost, env.snakes.first()) {
+            let ghost = ghost.pos(player.head().pos).dir(player.body.dir).len(player.body.grow);
+            env.snakes.push(ghost.build().unwrap());
+        }
 
         self.spawn_apples();
     }
@@ -230,14 +241,16 @@ impl Game {
     fn advance_snakes(&mut self, ctx: &Context) -> Result {
         let env = &mut self.env;
 
-        advance_snakes(env, self.fps_control.context(), ctx);
+        advance_snakes(env, self.fps_control.context(), Some(ctx));
 
         // if only ephemeral AIs are left, kill all other snakes
         let dying_or_ephemeral = |snake: &Snake| {
             matches!(snake.state, snake::State::Dying)
                 || matches!(
                     snake.snake_type,
-                    snake::Type::Competitor { life: Some(_) } | snake::Type::Killer { life: Some(_) }
+                    snake::Type::Competitor { life: Some(_) }
+                        | snake::Type::Killer { life: Some(_) }
+                        | snake::Type::Ghost { life: Some(_) }
                 )
         };
         if env.snakes.iter().all(dying_or_ephemeral) {
@@ -248,14 +261,21 @@ impl Game {
 
         if env.snakes.is_empty() {
             self.fps_control.game_over();
-            self.draw_cache_invalid = 5;
+            self.layers.invalidate(Changes::SNAKES);
             return Ok(());
         }
 
         let collisions = find_collisions(env);
-        let (seeds, game_over) = handle_collisions(env, &collisions);
+        self.score_apples(&collisions);
+        self.record_time_attack(&collisions);
+        let (seeds, crashed) = handle_collisions(&mut self.env, &collisions);
         self.refresh_animated_apples();
-        self.apple_mesh = None;
+        self.layers.invalidate(Changes::SNAKES | Changes::APPLES);
+
+        let game_over = match self.env.gtx.prefs.game_over_rule {
+            GameOverRule::LastStanding if self.num_players() > 1 => self.take_out_crashed_players(),
+            _ => crashed,
+        };
 
         if game_over {
             self.fps_control.game_over()
@@ -271,6 +291,8 @@ impl Game {
     /// Bounds for the length of one of the six sides of a cell
     const CELL_SIDE_MIN: f32 = 5.;
     const CELL_SIDE_MAX: f32 = 1000.;
+    /// Length the player has to reach in a time attack
+    const TIME_ATTACK_TARGET_LEN: usize = 40;
 
     fn refresh_animated_apples(&mut self) {
         self.animated_apples = self.env.apples.iter().any(|apple| apple.apple_type.is_animated());
@@ -279,7 +301,7 @@ impl Game {
     fn spawn_apples(&mut self) {
         spawn_apples(&mut self.env);
         self.refresh_animated_apples();
-        self.apple_mesh = None;
+        self.layers.invalidate(Changes::APPLES);
     }
 
     fn get_message_drawables(&mut self, ctx: &Context) -> Vec<MessageDrawable> {
@@ -324,7 +346,7 @@ impl Game {
         let graphics_fps = self.fps_control.measured_graphics_fps();
 
         let game_fps_undershoot = (self.fps_control.game_fps() - game_fps) / game_fps;
-        let graphics_fps_undershoot = (60. - graphics_fps) / graphics_fps;
+        let graphics_fps_undershoot = (fps_control::TARGET_GRAPHICS_FPS - graphics_fps) / graphics_fps;
         let color = if game_fps_undershoot > 0.05 || graphics_fps_undershoot > 0.05 {
             // > 5% undershoot: red
             Color::from_rgb(200, 0, 0)
@@ -336,14 +358,16 @@ impl Game {
             Color::WHITE
         };
 
+        let mut text = format!("u: {game_fps:.2} g: {graphics_fps:.2}");
+        if let Some(fps) = self.fps_control.slowed_down_fps() {
+            text += &format!("\nslowed down to {fps:.2}");
+        } else if self.fps_control.is_behind() {
+            text += &format!("\nbehind, {} frames skipped", self.fps_control.skipped_updates());
+        }
+
         self.messages.insert(
             MessageID::Fps,
-            Message::default(
-                format!("u: {game_fps:.2} g: {graphics_fps:.2}"),
-                message::Position::TopLeft,
-                color,
-                None,
-            ),
+            Message::default(text, message::Position::TopLeft, color, None),
         );
     }
 
@@ -351,31 +375,326 @@ impl Game {
         self.env
             .snakes
             .iter()
-            .position(|snake| snake.snake_type == snake::Type::Player)
+            .position(|snake| matches!(snake.snake_type, snake::Type::Player { .. }))
+    }
+}
+
+// Players
+impl Game {
+    fn num_players(&self) -> usize {
+        self.seeds
+            .iter()
+            .filter(|seed| matches!(seed.snake_type, Some(snake::Type::Player { .. })))
+            .count()
+    }
+
+    fn player_snake_mut(&mut self, id: PlayerId) -> Option<&mut Snake> {
+        self.env
+            .snakes
+            .iter_mut()
+            .find(|snake| snake.snake_type == snake::Type::Player { id })
+    }
+
+    /// How a player is referred to in notifications
+    fn player_name(&self, id: PlayerId) -> String {
+        format!("Player {}", id + 1)
+    }
+
+    /// Keyboard controls of all players that play on the keyboard
+    fn keyboard_controls(&self) -> impl Iterator<Item = (PlayerId, Controls)> + '_ {
+        self.seeds
+            .iter()
+            .filter_map(|seed| match (seed.snake_type, &seed.controller) {
+                (Some(snake::Type::Player { id }), Some(snake_control::Template::Keyboard { control_setup, .. })) => {
+                    Some((id, Controls::from(control_setup.clone())))
+                }
+                _ => None,
+            })
+    }
+
+    /// The player controlled by the given gamepad or by
+    /// the mouse (`gamepad == None`), if there is one
+    fn player_using(&self, gamepad: Option<usize>) -> Option<PlayerId> {
+        self.seeds
+            .iter()
+            .find_map(|seed| match (seed.snake_type, &seed.controller, gamepad) {
+                (Some(snake::Type::Player { id }), Some(snake_control::Template::Gamepad { index }), Some(gamepad))
+                    if *index == gamepad =>
+                {
+                    Some(id)
+                }
+                (Some(snake::Type::Player { id }), Some(snake_control::Template::Mouse), None) => Some(id),
+                _ => None,
+            })
+    }
+
+    fn gamepad_index(&mut self, id: GamepadId) -> usize {
+        match self.gamepads.iter().position(|gamepad| *gamepad == id) {
+            Some(index) => index,
+            None => {
+                self.gamepads.push(id);
+                self.gamepads.len() - 1
+            }
+        }
+    }
+
+    fn toggle_autopilot(&mut self, id: PlayerId) {
+        let prefix = match self.num_players() {
+            1 => String::new(),
+            _ => format!("{}: ", self.player_name(id)),
+        };
+
+        let text = match self.player_snake_mut(id) {
+            None => return,
+            Some(player_snake) if player_snake.autopilot.is_some() => {
+                if player_snake.autopilot_control.flip() {
+                    "Autopilot on"
+                } else {
+                    player_snake.controller.reset(player_snake.body.dir);
+                    "Autopilot off"
+                }
+            }
+            Some(_) => "Autopilot not available",
+        };
+        self.layers.invalidate(Changes::SNAKES);
+        self.display_notification(format!("{prefix}{text}"));
+    }
+
+    /// Teams that take part in the game, in order
+    fn teams(&self) -> Vec<TeamId> {
+        self.seeds
+            .iter()
+            .filter_map(|seed| seed.team)
+            .unique()
+            .sorted()
+            .collect()
+    }
+
+    /// Credit apples eaten by snakes on a team to that
+    /// team, must be called before the apples are removed
+    fn score_apples(&mut self, collisions: &[Collision]) {
+        for collision in collisions {
+            let Collision::Apple { snake_index, apple_index } = *collision else {
+                continue;
+            };
+            let Some(team) = self.env.snakes[snake_index].team else {
+                continue;
+            };
+            match &self.env.apples[apple_index].apple_type {
+                apple::Type::Food(food) => self.team_scores.add_food(team, *food),
+                _ => self.team_scores.add_apple(team),
+            }
+        }
+    }
+
+    /// Records the player's move, ends the run when the
+    /// player reaches the target length
+    fn record_time_attack(&mut self, collisions: &[Collision]) {
+        let Some(player_idx) = self.first_player_snake_idx() else {
+            return;
+        };
+        let Some(time_attack) = &mut self.time_attack else {
+            return;
+        };
+        let player = &self.env.snakes[player_idx];
+        if player.state != snake::State::Living {
+            return;
+        }
+
+        let food = collisions.iter().find_map(|collision| match *collision {
+            Collision::Apple { snake_index, apple_index } if snake_index == player_idx => {
+                match self.env.apples[apple_index].apple_type {
+                    apple::Type::Food(food) => Some(food),
+                    _ => None,
+                }
+            }
+            _ => None,
+        });
+
+        let text = match time_attack.record(player.body.dir, food, player.body.logical_len()) {
+            None => return,
+            Some(Finish::NewBest { frames }) => format!("New best: {frames} frames"),
+            Some(Finish::Slower { frames, best }) => format!("{frames} frames, best is {best}"),
+        };
+        self.fps_control.game_over();
+        self.display_notification(text);
+    }
+
+    /// With [`GameOverRule::LastStanding`], crashed players are
+    /// taken out of the game (they fall into a black hole), returns
+    /// whether the game is over
+    fn take_out_crashed_players(&mut self) -> bool {
+        for snake in &mut self.env.snakes {
+            if snake.state == snake::State::Crashed {
+                snake.die();
+            }
+        }
+
+        let living: Vec<_> = self
+            .env
+            .snakes
+            .iter()
+            .filter(|snake| snake.state == snake::State::Living)
+            .filter_map(|snake| match snake.snake_type {
+                snake::Type::Player { id } => Some((id, snake.team)),
+                _ => None,
+            })
+            .collect();
+
+        // the last team standing wins, even with several living players
+        let result = match living[..] {
+            [] => "Draw".to_string(),
+            [(id, None)] => format!("{} wins", self.player_name(id)),
+            [(_, Some(team)), ref rest @ ..] if rest.iter().all(|(_, other)| *other == Some(team)) => {
+                format!("Team {} wins", team + 1)
+            }
+            _ => return false,
+        };
+        self.display_notification(result);
+        true
+    }
+
+    /// Show the progress of the current run in the bottom-left corner
+    fn update_time_attack_hud(&mut self) {
+        let Some(time_attack) = &self.time_attack else {
+            return;
+        };
+        let len = self
+            .first_player_snake_idx()
+            .map_or(0, |idx| self.env.snakes[idx].body.logical_len());
+        let best = match time_attack.best_frames() {
+            Some(best) => format!(" (best {best})"),
+            None => String::new(),
+        };
+        let text = format!(
+            "Length {len}/{}, {} frames{best}",
+            time_attack.target_len,
+            time_attack.current_frames()
+        );
+
+        let message = Message {
+            font_size: Message::DEFAULT_FONT_SIZE / 2.,
+            ..Message::default(text, message::Position::BottomLeft, Color::WHITE, None)
+        };
+        self.messages.insert(MessageID::TimeAttack, message);
+    }
+
+    /// Show the length and state of each player's snake
+    /// and the score of each team in the bottom-left corner
+    fn update_player_hud(&mut self) {
+        let font_size = Message::DEFAULT_FONT_SIZE / 2.;
+        let hud_message = |text, row: usize| Message {
+            text,
+            position: message::Position::BottomLeft,
+            h_margin: Message::DEFAULT_MARGIN,
+            v_margin: Message::DEFAULT_MARGIN + row as f32 * (font_size + Message::DEFAULT_MARGIN / 2.),
+            font_size,
+            color: Color::WHITE,
+            disappear: None,
+        };
+
+        // teams at the bottom, team 1 at the top
+        let teams = self.teams();
+        for (i, &team) in teams.iter().enumerate() {
+            let text = format!("Team {}: {} points", team + 1, self.team_scores.get(team));
+            let row = teams.len() - 1 - i;
+            self.messages.insert(MessageID::TeamHud(team), hud_message(text, row));
+        }
+
+        let num_players = self.num_players();
+        for id in 0..num_players {
+            let status = match self
+                .env
+                .snakes
+                .iter()
+                .find(|snake| snake.snake_type == snake::Type::Player { id })
+            {
+                None => "out".to_string(),
+                Some(snake) => {
+                    let prefs = &self.env.gtx.prefs;
+                    let state = match snake.state {
+                        snake::State::Living if snake.autopilot_control => " (autopilot)".to_string(),
+                        snake::State::Living if prefs.assist == Assist::Save && snake.autopilot.is_some() => {
+                            let saves_left = prefs.assist_saves.saturating_sub(snake.assist_saves_used);
+                            format!(" ({saves_left} saves left)")
+                        }
+                        snake::State::Living => String::new(),
+                        snake::State::Dying | snake::State::Crashed => " (out)".to_string(),
+                    };
+                    format!("{}{state}", snake.body.logical_len())
+                }
+            };
+
+            let team = match self
+                .seeds
+                .iter()
+                .find(|seed| seed.snake_type == Some(snake::Type::Player { id }))
+            {
+                Some(SnakeBuilder { team: Some(team), .. }) => format!(" (team {})", team + 1),
+                _ => String::new(),
+            };
+            let text = format!("{}{team}: {status}", self.player_name(id));
+            // player 1 at the top, above the teams
+            let row = teams.len() + num_players - 1 - id;
+            self.messages.insert(MessageID::PlayerHud(id), hud_message(text, row));
+        }
     }
 }
 
 impl EventHandler<Error> for Game {
     fn update(&mut self, ctx: &mut Context) -> Result {
+        let _span = profiler::span(Phase::Update);
+        let state = self.fps_control.state();
         while self.fps_control.can_update() {
             self.advance_snakes(ctx).with_trace_step("Game::update")?;
             self.spawn_apples();
         }
+        // show the frame in which the game ended
+        if self.fps_control.state() != state {
+            self.idle.wake();
+        }
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> Result {
+        // fps and profiler overlays change every frame
+        let now = Instant::now();
+        let prefs = &self.env.gtx.prefs;
+        let animating =
+            self.fps_control.state() == fps_control::State::Playing || prefs.display_fps || profiler::enabled();
+        if !self.idle.should_draw(animating, now) {
+            return Ok(());
+        }
+
+        let draw_span = profiler::span(Phase::Draw);
         self.fps_control.graphics_frame();
 
         if self.env.gtx.prefs.display_fps {
             self.update_fps_message();
         }
 
+        if self.num_players() > 1 || !self.teams().is_empty() {
+            self.update_player_hud();
+        }
+
+        if self.time_attack.is_some() {
+            self.update_time_attack_hud();
+        }
+
+        let state = self.fps_control.state();
+        self.layers.start_frame(state);
+        if self.animated_apples {
+            self.layers.invalidate(Changes::ANIMATION);
+        }
+
+        // with more than one player, all players could be out of the game
+        let player_idx = self.first_player_snake_idx();
         let env = &mut self.env;
+        let layers = &mut self.layers;
+        let distance_grid = &mut self.distance_grid;
         let ftx = self.fps_control.context();
         let mut stats = Stats::default();
-        let playing = self.fps_control.state() == fps_control::State::Playing;
 
         // TODO: diagnose why the interframe interval is
         //  1ms sometimes when out of focus
@@ -388,82 +707,113 @@ impl EventHandler<Error> for Game {
         //     L = Some(Instant::now());
         // }
 
-        if playing {
+        if state == fps_control::State::Playing {
             // Update the direction of the snake early
             // to see it turning as soon as possible,
             // this could happen in the middle of a
             // game frame. Repeated update s during the
             // same game frame are blocked
-            for idx in 0..env.snakes.len() {
-                let (snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, idx);
-                snake.update_dir(other_snakes, &env.apples, &env.gtx, ftx, ctx);
-            }
+            update_dirs(env, ftx, Some(ctx));
         }
 
-        if self.grid_mesh.is_none() {
+        layers.update(GameLayer::Grid, || {
+            let _span = profiler::span(Phase::GridMesh);
             match env.gtx.prefs.draw_grid {
-                DrawGrid::Grid => self.grid_mesh = Some(rendering::grid_mesh(&env.gtx, ctx)?),
-                DrawGrid::Dots => self.grid_mesh = Some(rendering::grid_dot_mesh(&env.gtx, ctx)?),
-                _ => {}
+                DrawGrid::Grid => rendering::grid_mesh(&env.gtx, ctx).map(Some),
+                DrawGrid::Dots => rendering::grid_dot_mesh(&env.gtx, ctx).map(Some),
+                DrawGrid::None => Ok(None),
             }
-        }
-
-        if env.gtx.prefs.draw_border && self.border_mesh.is_none() {
-            self.border_mesh = Some(rendering::border_mesh(&env.gtx, ctx)?);
-        }
-
-        if self.snake_mesh.is_none() || playing {
-            self.snake_mesh = Some(rendering::snake_mesh(&mut env.snakes, &env.gtx, ftx, ctx, &mut stats)?);
-        }
-
-        if env.apples.is_empty() {
-            self.apple_mesh = None;
-        } else if self.apple_mesh.is_none() || self.animated_apples {
-            // only recompute apple mesh if there are animated apples
-            self.apple_mesh = Some(rendering::apple_mesh(&env.apples, &env.gtx, ftx, ctx, &mut stats)?);
-        }
-
-        let player_idx = self.first_player_snake_idx().expect("no player snake");
-        let env = &mut self.env;
-
-        let (player_snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, player_idx);
-
-        if env.gtx.prefs.draw_distance_grid && (self.distance_grid_mesh.is_none() || playing) {
-            self.distance_grid_mesh = Some(
-                self.distance_grid
-                    .mesh(player_snake, other_snakes, ctx, &env.gtx, ftx)?,
-            );
-        }
-
-        if env.gtx.prefs.draw_player_path && (self.player_path_mesh.is_none() || playing) {
-            // could still be None if the player snake doesn't have an autopilot
-            self.player_path_mesh =
-                rendering::player_path_mesh(player_snake, other_snakes, &env.apples, ctx, &env.gtx, &mut stats)
-                    .invert()?;
-        }
+        })?;
+
+        layers.update(GameLayer::Border, || {
+            let _span = profiler::span(Phase::BorderMesh);
+            env.gtx
+                .prefs
+                .draw_border
+                .then(|| rendering::border_mesh(&env.gtx, ctx))
+                .invert()
+        })?;
+
+        layers.update(GameLayer::Snakes, || {
+            let _span = profiler::span(Phase::SnakeMesh);
+            rendering::snake_mesh(&mut env.snakes, &env.gtx, ftx, ctx, &mut stats).map(Some)
+        })?;
+
+        layers.update(GameLayer::Apples, || {
+            let _span = profiler::span(Phase::AppleMesh);
+            (!env.apples.is_empty())
+                .then(|| rendering::apple_mesh(&env.apples, &env.gtx, ftx, ctx, &mut stats))
+                .invert()
+        })?;
+
+        layers.update(GameLayer::Assist, || match env.gtx.prefs.assist {
+            Assist::Off => Ok(None),
+            _ => rendering::assist_mesh(&env.snakes, ctx, &env.gtx, &mut stats).invert(),
+        })?;
+
+        layers.update(GameLayer::Hints, || match env.gtx.prefs.draw_move_hints {
+            true => rendering::hint_mesh(&env.snakes, ctx, &env.gtx, &mut stats).invert(),
+            false => Ok(None),
+        })?;
+
+        layers.update(GameLayer::DistanceGrid, || {
+            let Some(player_idx) = player_idx.filter(|_| env.gtx.prefs.draw_distance_grid) else {
+                return Ok(None);
+            };
+            let _span = profiler::span(Phase::DistanceGridMesh);
+            let (player_snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, player_idx);
+            distance_grid
+                .mesh(player_snake, other_snakes, ctx, &env.gtx, ftx)
+                .map(Some)
+        })?;
+
+        layers.update(GameLayer::PlayerPath, || {
+            let Some(player_idx) = player_idx.filter(|_| env.gtx.prefs.draw_player_path) else {
+                return Ok(None);
+            };
+            let _span = profiler::span(Phase::PlayerPathMesh);
+            let (player_snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, player_idx);
+            // None if the player snake doesn't have an autopilot
+            rendering::player_path_mesh(player_snake, other_snakes, &env.apples, ctx, &env.gtx, &mut stats).invert()
+        })?;
 
         if env.gtx.prefs.display_stats {
             let message = stats.get_stats_message();
             self.messages.insert(MessageID::Stats, message);
         }
 
+        let profiler_mesh = if profiler::enabled() {
+            profiler::with_history(|history| {
+                self.messages
+                    .insert(MessageID::Profiler, profiler::get_summary_message(history));
+                rendering::profiler_mesh(history, ctx)
+            })
+            .invert()?
+        } else {
+            None
+        };
+
         let message_drawables = self.get_message_drawables(ctx);
 
-        let meshes = [
-            &self.distance_grid_mesh,
-            &self.grid_mesh,
-            &self.player_path_mesh,
-            &self.snake_mesh,
-            &self.apple_mesh,
-            &self.border_mesh,
-        ];
+        for message in self.messages.values() {
+            if let Some(at) = message.next_change(now) {
+                self.idle.schedule(at);
+            }
+        }
+        if self.animated_apples {
+            let elapsed_millis = self.fps_control.context().elapsed_millis;
+            self.idle.schedule(now + rendering::next_hue_change(elapsed_millis));
+        }
 
-        if !message_drawables.is_empty() || meshes.iter().any(|mesh| mesh.is_some()) {
+        if !message_drawables.is_empty() || !self.layers.is_empty() {
+            let _span = profiler::span(Phase::Submit);
             let mut canvas = Canvas::from_frame(ctx, self.env.gtx.palette.background_color);
 
-            let draw_param = DrawParam::default().dest(self.offset);
-            for mesh in meshes.into_iter().flatten() {
-                canvas.draw(mesh, draw_param);
+            self.layers.draw(&mut canvas, DrawParam::default().dest(self.offset));
+
+            // in screen coordinates
+            if let Some(mesh) = &profiler_mesh {
+                canvas.draw(mesh, DrawParam::default());
             }
 
             for drawable in message_drawables {
@@ -473,6 +823,10 @@ impl EventHandler<Error> for Game {
             canvas.finish(ctx).map_err(Error::from).with_trace_step("Game::draw")?;
         }
 
+        // the frame ends with the draw span
+        drop(draw_span);
+        profiler::end_frame();
+
         Ok(())
     }
 
@@ -482,12 +836,41 @@ impl EventHandler<Error> for Game {
     }
 
     fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeated: bool) -> Result {
-        let prefs = &mut self.env.gtx.prefs;
-
-        if prefs.hide_cursor {
+        self.idle.wake();
+        if self.env.gtx.prefs.hide_cursor {
             mouse::set_cursor_hidden(ctx, true);
         }
 
+        // player controls take precedence over other shortcuts, this
+        // allows players on the left side of the keyboard to use keys
+        // that would otherwise toggle preferences
+        if let Some(keycode) = input.keycode {
+            let mut direction_key = false;
+            let mut autopilot_of = None;
+            for (id, controls) in self.keyboard_controls() {
+                direction_key |= controls.is_direction_key(keycode);
+                if controls.autopilot == keycode {
+                    autopilot_of = Some(id);
+                }
+            }
+
+            if let Some(id) = autopilot_of {
+                self.toggle_autopilot(id);
+                return Ok(());
+            }
+
+            if direction_key {
+                if self.fps_control.state() == fps_control::State::Playing {
+                    for snake in &mut self.env.snakes {
+                        snake.controller.key_pressed(keycode)
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        let prefs = &mut self.env.gtx.prefs;
+
         use KeyCode::*;
 
         let numeric_keys = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
@@ -507,10 +890,7 @@ impl EventHandler<Error> for Game {
                         self.restart();
                         self.fps_control.play();
                     }
-                    fps_control::State::Playing => {
-                        self.fps_control.pause();
-                        self.draw_cache_invalid = 5;
-                    }
+                    fps_control::State::Playing => self.fps_control.pause(),
                     fps_control::State::Paused => self.fps_control.play(),
                 },
                 B => {
@@ -518,7 +898,7 @@ impl EventHandler<Error> for Game {
                         true => "Border on",
                         false => "Border off",
                     };
-                    self.border_mesh = None;
+                    self.layers.invalidate(Changes::PREFS);
                     self.display_notification(text);
                 }
                 G => {
@@ -527,64 +907,121 @@ impl EventHandler<Error> for Game {
                         DrawGrid::Dots => "Dot grid",
                         DrawGrid::None => "Grid off",
                     };
-                    self.grid_mesh = None;
+                    self.layers.invalidate(Changes::PREFS);
                     self.display_notification(text);
                 }
                 D => {
-                    let text = if prefs.draw_distance_grid.flip() {
-                        "Distance grid on"
-                    } else {
-                        self.distance_grid_mesh = None;
-                        "Distance grid off"
+                    let text = match prefs.draw_distance_grid.flip() {
+                        true => "Distance grid on",
+                        false => "Distance grid off",
                     };
+                    self.layers.invalidate(Changes::PREFS);
                     self.display_notification(text);
                 }
                 P => {
-                    let text = if prefs.draw_player_path.flip() {
-                        "Path on"
+                    let text = match prefs.draw_player_path.flip() {
+                        true => "Path on",
+                        false => "Path off",
+                    };
+                    self.layers.invalidate(Changes::PREFS);
+                    self.display_notification(text);
+                }
+                R => {
+                    if self.time_attack.is_some() {
+                        self.time_attack = None;
+                        self.messages.remove(&MessageID::TimeAttack);
+                        self.display_notification("Time attack off");
+                    } else if self.num_players() > 1 {
+                        self.display_notification("Time attack is single player only");
+                        return Ok(());
                     } else {
-                        self.player_path_mesh = None;
-                        "Path off"
+                        let seed = self.env.rng.gen();
+                        self.time_attack = Some(TimeAttack::new(seed, Self::TIME_ATTACK_TARGET_LEN));
+                        self.display_notification(format!("Time attack: grow to {}", Self::TIME_ATTACK_TARGET_LEN));
+                    }
+                    self.restart();
+                    self.fps_control.play();
+                }
+                Y => {
+                    let text = match prefs.draw_move_hints.flip() {
+                        true => "Move hints on",
+                        false => "Move hints off",
+                    };
+                    self.layers.invalidate(Changes::PREFS);
+                    self.display_notification(text);
+                }
+                T => {
+                    let adaptive_speed = !self.fps_control.adaptive_speed();
+                    self.fps_control.set_adaptive_speed(adaptive_speed);
+                    let text = match adaptive_speed {
+                        true => "Adaptive speed on",
+                        false => "Adaptive speed off",
                     };
                     self.display_notification(text);
                 }
                 F => {
                     if !prefs.display_fps.flip() {
                         self.messages.remove(&MessageID::Fps);
-                        self.draw_cache_invalid = 5;
                     }
                 }
                 S => {
                     if !prefs.display_stats.flip() {
                         self.messages.remove(&MessageID::Stats);
-                        self.draw_cache_invalid = 5;
                     }
                 }
+                Q => {
+                    let enabled = !profiler::enabled();
+                    profiler::set_enabled(enabled);
+                    if !enabled {
+                        self.messages.remove(&MessageID::Profiler);
+                    }
+                }
+                E => {
+                    let text = match profiler::export_chrome_trace(profiler::TRACE_FILE) {
+                        Ok(()) => format!("Trace saved to {}", profiler::TRACE_FILE),
+                        Err(e) => format!("Failed to save trace: {e}"),
+                    };
+                    self.display_notification(text);
+                }
                 A => {
-                    // only apply if there is exactly one player snake
-                    if self.seeds.len() == 1 {
-                        let player_snake = self
-                            .env
-                            .snakes
-                            .iter_mut()
-                            .find(|snake| snake.snake_type == snake::Type::Player)
-                            .unwrap();
-
-                        if player_snake.autopilot.is_some() {
-                            let text = if player_snake.autopilot_control.flip() {
-                                "Autopilot on"
-                            } else {
-                                player_snake.controller.reset(player_snake.body.dir);
-                                "Autopilot off"
-                            };
-                            self.display_notification(text);
-                        } else {
-                            self.display_notification("Autopilot not available");
-                        }
-                    } else {
-                        self.display_notification(format!("Can't use autopilot with {} players", self.seeds.len()));
+                    // shortcut for a single player, with more players, each
+                    // player has their own autopilot key or button
+                    match self.num_players() {
+                        1 => self.toggle_autopilot(0),
+                        n => self.display_notification(format!("Use the player's own autopilot key ({n} players)")),
                     }
                 }
+                U => {
+                    let text = match prefs.killer_difficulty.rotate_next() {
+                        Difficulty::Easy => "Killers: easy",
+                        Difficulty::Normal => "Killers: normal",
+                        Difficulty::Hard => "Killers: hard",
+                    };
+                    self.display_notification(text);
+                }
+                W => {
+                    let text = match prefs.competitor_ai.rotate_next() {
+                        CompetitorAi::Pathfinder => "Competitors: pathfinder",
+                        CompetitorAi::Adversarial => "Competitors: adversarial",
+                    };
+                    self.display_notification(text);
+                }
+                I => {
+                    let text = match prefs.assist.rotate_next() {
+                        Assist::Off => "Assist off".to_string(),
+                        Assist::Suggest => "Assist: suggest".to_string(),
+                        Assist::Save => format!("Assist: {} saves per game", prefs.assist_saves),
+                    };
+                    self.layers.invalidate(Changes::PREFS);
+                    self.display_notification(text);
+                }
+                O => {
+                    let text = match prefs.game_over_rule.rotate_next() {
+                        GameOverRule::FirstCrash => "Game over: first crash",
+                        GameOverRule::LastStanding => "Game over: last standing",
+                    };
+                    self.display_notification(text);
+                }
                 LBracket => {
                     let mut new_fps = match self.fps_control.game_fps() {
                         f if f <= 0.1 => 0.05,
@@ -633,8 +1070,7 @@ impl EventHandler<Error> for Game {
                             text = "draw style: hexagon";
                         }
                     }
-                    self.snake_mesh = None;
-                    self.apple_mesh = None;
+                    self.layers.invalidate(Changes::PREFS);
                     self.display_notification(text);
                 }
                 X => {
@@ -651,7 +1087,8 @@ impl EventHandler<Error> for Game {
                                 }
                             }
                         });
-                        self.apple_mesh = None;
+                        self.refresh_animated_apples();
+                        self.layers.invalidate(Changes::APPLES);
                         "Special apples disabled"
                     };
                     self.display_notification(text);
@@ -677,6 +1114,7 @@ impl EventHandler<Error> for Game {
                     new_side_length = new_side_length.clamp(Self::CELL_SIDE_MIN, Self::CELL_SIDE_MAX);
                     self.env.gtx.cell_dim = CellDim::from(new_side_length);
                     self.update_dim(ctx);
+                    self.layers.invalidate(Changes::DIM);
                     self.display_notification(format!("Cell side: {new_side_length}"));
                 }
                 k => {
@@ -692,7 +1130,56 @@ impl EventHandler<Error> for Game {
         Ok(())
     }
 
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) -> Result {
+        self.idle.wake();
+        if button == MouseButton::Right {
+            if let Some(id) = self.player_using(None) {
+                self.toggle_autopilot(id);
+            }
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, button: GamepadButton, id: GamepadId) -> Result {
+        self.idle.wake();
+        let index = self.gamepad_index(id);
+
+        match button {
+            GamepadButton::North => {
+                if let Some(player) = self.player_using(Some(index)) {
+                    self.toggle_autopilot(player);
+                }
+            }
+            GamepadButton::Start => match self.fps_control.state() {
+                fps_control::State::GameOver => {
+                    self.restart();
+                    self.fps_control.play();
+                }
+                fps_control::State::Playing => self.fps_control.pause(),
+                fps_control::State::Paused => self.fps_control.play(),
+            },
+            button => {
+                if self.fps_control.state() == fps_control::State::Playing {
+                    for snake in &mut self.env.snakes {
+                        snake.controller.gamepad_button(index, button)
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) -> Result {
+        let index = self.gamepad_index(id);
+        for snake in &mut self.env.snakes {
+            snake.controller.gamepad_axis(index, axis, value)
+        }
+        Ok(())
+    }
+
     fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> Result {
+        self.idle.wake();
         use KeyCode::*;
 
         if let Some(Space) = input.keycode {
@@ -707,9 +1194,15 @@ impl EventHandler<Error> for Game {
 
     // TODO: forbid resizing in-game
     fn resize_event(&mut self, ctx: &mut Context, _width: f32, _height: f32) -> Result {
+        self.idle.wake();
         self.update_dim(ctx);
         let HexDim { h, v } = self.env.gtx.board_dim;
         self.display_notification(format!("{h}x{v}"));
         Ok(())
     }
+
+    fn focus_event(&mut self, _ctx: &mut Context, _gained: bool) -> Result {
+        self.idle.wake();
+        Ok(())
+    }
 }
@@ -9,6 +9,7 @@ pub use start_screen::StartScreen;
 use crate::app::game_context::GameContext;
 pub use crate::app::prefs::Prefs;
 use crate::apple::Apple;
+use crate::basic::board::Board;
 use crate::error::{Error, ErrorConversion, Result};
 use crate::snake::builder::Builder as SnakeBuilder;
 use crate::snake::Snake;
@@ -54,23 +55,63 @@ pub struct Environment<Rng = ThreadRng> {
     // TODO: keep apples in order of position to allow for binary search
     // TODO: specialized Vec for that
     pub apples: Vec<Apple>,
+    /// Where everything is, see [`Self::sync_board`]
+    pub board: Board,
     pub gtx: GameContext,
     pub rng: Rng,
 }
 
 impl<Rng> Environment<Rng> {
+    /// Whether the board tracks the current snakes, possibly
+    /// not in their latest positions
+    fn board_tracks_snakes(&self) -> bool {
+        self.board.dim() == self.gtx.board_dim && self.board.num_snakes() == self.snakes.len()
+    }
+
+    /// Brings the board up to date after snakes moved or apples
+    /// were added, the board is rebuilt from scratch if the board
+    /// size changed or if snakes were added or removed directly
+    pub fn sync_board(&mut self) {
+        if !self.board_tracks_snakes() {
+            self.board = Board::build(self.gtx.board_dim, &self.snakes, &self.apples);
+            return;
+        }
+        for (index, snake) in self.snakes.iter().enumerate() {
+            self.board.sync_snake(index, snake);
+        }
+        self.board.sync_apples(&self.apples);
+    }
+
+    /// Removes all snakes and apples
+    pub fn clear(&mut self) {
+        self.snakes.clear();
+        self.apples.clear();
+        self.board = Board::new(self.gtx.board_dim);
+    }
+
     pub fn add_snake(&mut self, snake_builder: &SnakeBuilder) -> Result {
-        self.snakes.push(
-            snake_builder
-                .build()
-                .map_err(Error::from)
-                .with_trace_step("Environment::add_snake")?,
-        );
+        let snake = snake_builder
+            .build()
+            .map_err(Error::from)
+            .with_trace_step("Environment::add_snake")?;
         // TODO: check that the snake can be added, report error if it can't
+        if self.board_tracks_snakes() {
+            self.board.add_snake(&snake);
+        }
+        self.snakes.push(snake);
         Ok(())
     }
 
+    /// Adds an apple, the board has to be in sync with the apples
+    pub fn add_apple(&mut self, apple: Apple) {
+        self.board.push_apple(&apple);
+        self.apples.push(apple);
+    }
+
     pub fn remove_snake(&mut self, index: usize) -> Snake {
+        if self.board_tracks_snakes() {
+            self.board.remove_snake(index);
+        }
         self.snakes.remove(index)
     }
 
@@ -81,5 +122,6 @@ impl<Rng> Environment<Rng> {
         indices.into_iter().rev().for_each(|i| {
             self.apples.remove(i);
         });
+        self.board.sync_apples(&self.apples);
     }
 }
@@ -18,18 +18,30 @@ use crate::app::snake_management::{find_collisions, handle_collisions};
 use crate::app::stats::Stats;
 use crate::app::{self, Screen};
 use crate::apple::spawn::{spawn_apples, SpawnPolicy, SpawnScheduleBuilder};
+use crate::basic::board::Board;
 use crate::basic::{CellDim, Dir, HexPoint, Point};
 use crate::button::{Button, ButtonDataBuilder, ButtonType, TriColor};
 use crate::color::Color;
 use crate::error::{Error, ErrorConversion, Result};
 use crate::rendering::shape::{Hexagon, Shape, TriangleArrowLeft, WideHexagon};
+use crate::rendering::{Changes, Layers};
 use crate::snake::builder::Builder as SnakeBuilder;
 use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
 use crate::snake::SegmentType;
 use crate::snake_control::Template;
+use crate::support::invert::Invert;
 use crate::view::snakes::OtherSnakes;
 use crate::{apple, by_segment_type, by_snake_type, rendering, snake};
 
+/// Meshes of a demo in the order they are drawn in
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum DemoLayer {
+    Grid,
+    Border,
+    Snake,
+    Apples,
+}
+
 // position of the snake within the demo box is relative,
 // the snake thinks it's in an absolute world at (0, 0)
 struct SnakeDemo {
@@ -42,6 +54,7 @@ struct SnakeDemo {
     right_button: Button,
 
     fps_control: Rc<RefCell<FpsControl>>,
+    layers: Layers<DemoLayer>,
 }
 
 impl SnakeDemo {
@@ -146,6 +159,7 @@ impl SnakeDemo {
             env: Environment {
                 snakes: vec![seed.build().unwrap()],
                 apples: vec![],
+                board: Board::default(),
                 gtx: GameContext::new(board_dim, cell_dim, app_palette, Prefs::default(), apple_spawn_policy),
                 rng: NoRng,
             },
@@ -157,19 +171,33 @@ impl SnakeDemo {
             right_button,
 
             fps_control: control,
+            layers: Self::layers(),
         }
     }
+
+    fn layers() -> Layers<DemoLayer> {
+        let fixed = Changes::DIM | Changes::PREFS;
+        let moving = fixed | Changes::SNAKES | Changes::MOTION;
+        Layers::new([
+            (DemoLayer::Grid, fixed),
+            (DemoLayer::Border, fixed),
+            (DemoLayer::Snake, moving | Changes::PLAY_STATE),
+            (DemoLayer::Apples, fixed | Changes::APPLES),
+        ])
+    }
 }
 
 impl SnakeDemo {
     fn prev_palette(&mut self) {
         self.current_palette = (self.current_palette + self.palettes.len() - 1) % self.palettes.len();
         self.env.snakes[0].palette = self.palettes[self.current_palette].into();
+        self.layers.invalidate(Changes::SNAKES);
     }
 
     fn next_palette(&mut self) {
         self.current_palette = (self.current_palette + 1) % self.palettes.len();
         self.env.snakes[0].palette = self.palettes[self.current_palette].into();
+        self.layers.invalidate(Changes::SNAKES);
     }
 
     fn update(&mut self, ctx: &Context) {
@@ -179,9 +207,10 @@ impl SnakeDemo {
             &self.env.apples,
             &self.env.gtx,
             self.fps_control.borrow().context(),
-            ctx,
+            Some(ctx),
         );
 
+        self.env.sync_board();
         let collisions = find_collisions(&self.env);
         let (spawn_snakes, game_over) = handle_collisions(&mut self.env, &collisions);
 
@@ -189,6 +218,7 @@ impl SnakeDemo {
         assert!(!game_over, "unexpected game over");
 
         spawn_apples(&mut self.env);
+        self.layers.invalidate(Changes::SNAKES | Changes::APPLES);
     }
 
     fn draw(&mut self, canvas: &mut Canvas, ctx: &mut Context, stats: &mut Stats) -> Result {
@@ -197,27 +227,26 @@ impl SnakeDemo {
             &[],
             &self.env.gtx,
             self.fps_control.borrow().context(),
-            ctx,
+            Some(ctx),
         );
 
-        let draw_param = DrawParam::default().dest(self.pos);
-
-        let grid_mesh = rendering::grid_mesh(&self.env.gtx, ctx)?;
-        canvas.draw(&grid_mesh, draw_param);
-
-        let border_mesh = rendering::border_mesh(&self.env.gtx, ctx)?;
-        canvas.draw(&border_mesh, draw_param);
-
         let fps_control = self.fps_control.borrow();
         let ftx = fps_control.context();
-
-        let snake_mesh = rendering::snake_mesh(&mut self.env.snakes, &self.env.gtx, ftx, ctx, stats)?;
-        canvas.draw(&snake_mesh, draw_param);
-
-        if !self.env.apples.is_empty() {
-            let apple_mesh = rendering::apple_mesh(&self.env.apples, &self.env.gtx, ftx, ctx, stats)?;
-            canvas.draw(&apple_mesh, draw_param);
-        }
+        let env = &mut self.env;
+        let layers = &mut self.layers;
+
+        layers.start_frame(fps_control.state());
+        layers.update(DemoLayer::Grid, || rendering::grid_mesh(&env.gtx, ctx).map(Some))?;
+        layers.update(DemoLayer::Border, || rendering::border_mesh(&env.gtx, ctx).map(Some))?;
+        layers.update(DemoLayer::Snake, || {
+            rendering::snake_mesh(&mut env.snakes, &env.gtx, ftx, ctx, stats).map(Some)
+        })?;
+        layers.update(DemoLayer::Apples, || {
+            (!env.apples.is_empty())
+                .then(|| rendering::apple_mesh(&env.apples, &env.gtx, ftx, ctx, stats))
+                .invert()
+        })?;
+        layers.draw(canvas, DrawParam::default().dest(self.pos));
 
         let left_clicked = self.left_button.draw(canvas, ctx)?;
         let right_clicked = self.right_button.draw(canvas, ctx)?;
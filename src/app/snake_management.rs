@@ -3,15 +3,16 @@
 
 use ggez::Context;
 use rand::distributions::uniform::SampleRange;
+use rayon::prelude::*;
 
 use crate::app::fps_control::FpsContext;
+use crate::app::profiler::{self, Phase};
 use crate::app::screen::Environment;
-use crate::basic::board::{get_occupied_cells, random_free_spot};
-use crate::basic::{Dir, HexPoint};
+use crate::basic::{Dir, HexDim, HexPoint};
 use crate::error::{Error, ErrorConversion, Result};
 use crate::snake::builder::Builder as SnakeBuilder;
-use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
-use crate::snake::{self, SegmentType, State};
+use crate::snake::eat_mechanics::{EatBehavior, EatMechanics, TeamRelation};
+use crate::snake::{self, Control, SegmentType, State};
 use crate::snake_control;
 use crate::view::snakes::OtherSnakes;
 
@@ -35,51 +36,49 @@ pub enum Collision {
     },
 }
 
+/// Expects the board to be in sync, see [`Environment::sync_board`]
 pub fn find_collisions<Rng>(env: &Environment<Rng>) -> Vec<Collision> {
+    let _span = profiler::span(Phase::Collisions);
     let mut collisions = vec![];
 
     // check whether snake1 collided with an apple or with snake2
-    'outer: for (snake1_index, snake1) in env
+    for (snake1_index, snake1) in env
         .snakes
         .iter()
         .enumerate()
         .filter(|(_, s)| !matches!(s.state, State::Crashed | State::Dying))
     {
-        for (apple_index, apple) in env.apples.iter().enumerate() {
-            if snake1.head().pos == apple.pos {
-                collisions.push(Collision::Apple {
-                    snake_index: snake1_index,
-                    apple_index,
-                })
-            }
+        let head_pos = snake1.head().pos;
+
+        // ghosts only pretend to eat, see `Replay`
+        let eats_apples = !matches!(snake1.snake_type, snake::Type::Ghost { .. });
+        if let Some(apple_index) = env.board.apple_at(head_pos).filter(|_| eats_apples) {
+            collisions.push(Collision::Apple {
+                snake_index: snake1_index,
+                apple_index,
+            })
         }
 
-        for (snake2_index, other) in env.snakes.iter().enumerate() {
-            let mut iter = other.body.segments.iter().enumerate();
-
-            // ignore head-head collision with itself
-            if snake1_index == snake2_index {
-                let _ = iter.next();
-            }
-
-            for (segment_idx, segment) in iter {
-                if snake1.head().pos == segment.pos {
-                    if snake1_index == snake2_index {
-                        collisions.push(Collision::Itself {
-                            snake_index: snake1_index,
-                            snake_segment_index: segment_idx,
-                        })
-                    } else {
-                        collisions.push(Collision::Snake {
-                            snake1_index,
-                            snake2_index,
-                            snake2_segment_index: segment_idx,
-                        });
-                    }
-
-                    continue 'outer;
-                }
-            }
+        // the first segment in snake order, ignoring head-head
+        // collision with itself
+        let hit = env
+            .board
+            .segments_at(head_pos)
+            .map(|segment| (segment.snake_index, segment.segment_index))
+            .filter(|&hit| hit != (snake1_index, 0))
+            .min();
+
+        match hit {
+            Some((snake2_index, segment_idx)) if snake2_index == snake1_index => collisions.push(Collision::Itself {
+                snake_index: snake1_index,
+                snake_segment_index: segment_idx,
+            }),
+            Some((snake2_index, segment_idx)) => collisions.push(Collision::Snake {
+                snake1_index,
+                snake2_index,
+                snake2_segment_index: segment_idx,
+            }),
+            None => {}
         }
     }
 
@@ -90,17 +89,21 @@ pub fn find_collisions<Rng>(env: &Environment<Rng>) -> Vec<Collision> {
 /// Returns `(spawn_snakes, game_over)` where
 ///  - `spawn_snakes` describes the new snakes to spawn
 /// (competitors, killers, etc.)
-///  - `game_over` tells whether a snake crashed and ended the game
+///  - `game_over` tells whether a snake crashed or a player died
+/// and ended the game
 #[must_use]
 pub fn handle_collisions<Rng: rand::Rng>(
     env: &mut Environment<Rng>,
     collisions: &[Collision],
 ) -> (Vec<SnakeBuilder>, bool) {
+    let _span = profiler::span(Phase::Collisions);
     let board_width = env.gtx.board_dim.h;
 
     let mut spawn_snakes = vec![];
     let mut to_remove = vec![];
     let mut game_over = false;
+    // for the game over rules, a player dying counts as a crash
+    let is_player = |snake: &snake::Snake| matches!(snake.snake_type, snake::Type::Player { .. });
     for collision in collisions.iter().copied() {
         use EatBehavior::*;
         let snakes = &mut env.snakes;
@@ -148,12 +151,16 @@ pub fn handle_collisions<Rng: rand::Rng>(
                 let snake2 = &snakes[snake2_index];
                 let snake2_type = snake2.snake_type;
                 let snake2_segment_type = snake2.body.segments[snake2_segment_index].segment_type.discriminant();
-                let behavior = snake1.eat_mechanics.eat_other(snake2_type, snake2_segment_type);
+                let relation = TeamRelation::between(snake1.team, snake2.team);
+                let behavior = snake1
+                    .eat_mechanics
+                    .eat_other(relation, snake2_type, snake2_segment_type);
 
                 match behavior {
                     Cut => {
                         // if it's a head-head collision, both snakes die
                         if snake2_segment_index == 0 {
+                            game_over |= is_player(&snakes[snake1_index]) || is_player(&snakes[snake2_index]);
                             snakes[snake1_index].die();
                             snakes[snake2_index].die();
                         } else {
@@ -164,7 +171,10 @@ pub fn handle_collisions<Rng: rand::Rng>(
                         snakes[snake1_index].crash();
                         game_over = true;
                     }
-                    Die => snakes[snake1_index].die(),
+                    Die => {
+                        game_over |= is_player(&snakes[snake1_index]);
+                        snakes[snake1_index].die();
+                    }
                     PassUnder => {
                         snakes[snake1_index].body.segments[0].z_index =
                             snakes[snake2_index].body.segments[snake2_segment_index].z_index - 1
@@ -185,7 +195,10 @@ pub fn handle_collisions<Rng: rand::Rng>(
                         snakes[snake_index].crash();
                         game_over = true;
                     }
-                    Die => snakes[snake_index].die(),
+                    Die => {
+                        game_over |= is_player(&snakes[snake_index]);
+                        snakes[snake_index].die();
+                    }
                     PassUnder => {
                         snakes[snake_index].body.segments[0].z_index =
                             snakes[snake_index].body.segments[snake_segment_index].z_index - 1
@@ -200,40 +213,58 @@ pub fn handle_collisions<Rng: rand::Rng>(
     }
 
     env.remove_apples(to_remove);
+    // snakes were cut or changed layers
+    env.sync_board();
 
     (spawn_snakes, game_over)
 }
 
-pub fn spawn_snakes(env: &mut Environment, snake_builders: Vec<SnakeBuilder>) -> Result {
+/// Starting positions and directions for `num` snakes that don't
+/// have a predefined position, the snakes are spread evenly along
+/// the middle row of the board and alternate between going up and
+/// going down
+pub fn player_spawn_points(num: usize, board_dim: HexDim) -> Vec<(HexPoint, Dir)> {
+    assert!(num as isize <= board_dim.h, "snakes spread too wide");
+
+    (0..num as isize)
+        .map(|i| {
+            let h = (2 * i + 1) * board_dim.h / (2 * num as isize);
+            let dir = if i % 2 == 0 { Dir::U } else { Dir::D };
+            (HexPoint { h, v: board_dim.v / 2 }, dir)
+        })
+        .collect()
+}
+
+pub fn spawn_snakes<Rng: rand::Rng>(env: &mut Environment<Rng>, snake_builders: Vec<SnakeBuilder>) -> Result {
+    let _span = profiler::span(Phase::Spawning);
     let board_dim = env.gtx.board_dim;
 
-    for mut snake_builder in snake_builders {
-        // avoid spawning too close to player snake heads
-        const PLAYER_SNAKE_HEAD_NO_SPAWN_RADIUS: usize = 7;
+    // avoid spawning too close to player snake heads
+    const PLAYER_SNAKE_HEAD_NO_SPAWN_RADIUS: usize = 7;
+    let player_heads: Vec<_> = env
+        .snakes
+        .iter()
+        .filter(|s| matches!(s.snake_type, snake::Type::Player { .. }))
+        .map(|s| s.head().pos)
+        .collect();
+    let far_from_players = |pos: HexPoint| {
+        player_heads
+            .iter()
+            .all(|&head| head.wrapping_manhattan_distance(pos, board_dim, 0) > PLAYER_SNAKE_HEAD_NO_SPAWN_RADIUS)
+    };
 
-        let mut occupied_cells = get_occupied_cells(&env.snakes, &env.apples);
-        for snake in env.snakes.iter().filter(|s| s.snake_type == snake::Type::Player) {
-            let neighborhood = snake.reachable(PLAYER_SNAKE_HEAD_NO_SPAWN_RADIUS, board_dim);
-            occupied_cells.extend_from_slice(&neighborhood);
-        }
-        occupied_cells.sort_unstable();
-        occupied_cells.dedup();
+    for mut snake_builder in snake_builders {
+        env.sync_board();
 
         match snake_builder.pos {
             Some(pos) => {
-                let is_occupied = env
-                    .snakes
-                    .iter()
-                    .flat_map(|snake| snake.body.segments.iter().map(|seg| seg.pos))
-                    .any(|p| p == pos);
-
-                if is_occupied {
+                if env.board.segments_at(pos).next().is_some() {
                     eprintln!("warning: failed to spawn snake, no free spaces left");
                     continue;
                 }
             }
             None => {
-                if let Some(pos) = random_free_spot(&occupied_cells, board_dim, &mut env.rng) {
+                if let Some(pos) = env.board.random_free_cell_where(&mut env.rng, far_from_players) {
                     snake_builder.pos = Some(pos);
                 } else {
                     eprintln!("warning: failed to spawn snake, no free spaces left");
@@ -257,27 +288,35 @@ pub fn spawn_snakes(env: &mut Environment, snake_builders: Vec<SnakeBuilder>) ->
 
 /// Returns the indices of snakes to be deleted (in reverse order so they
 /// can be deleted straight away)
-pub fn advance_snakes(env: &mut Environment, ftx: &FpsContext, ctx: &Context) {
-    let snakes = &mut env.snakes;
-
-    let mut remove_snakes = vec![];
-    for snake_idx in 0..snakes.len() {
-        // set snake to die if it ran out of life
-        match &mut snakes[snake_idx].snake_type {
-            snake::Type::Competitor { life: Some(life) } | snake::Type::Killer { life: Some(life) } => {
+pub fn advance_snakes<Rng>(env: &mut Environment<Rng>, ftx: &FpsContext, ctx: Option<&Context>) {
+    // set snakes to die if they ran out of life
+    for snake in &mut env.snakes {
+        match &mut snake.snake_type {
+            snake::Type::Competitor { life: Some(life) }
+            | snake::Type::Killer { life: Some(life) }
+            | snake::Type::Ghost { life: Some(life) } => {
                 if *life == 0 {
-                    snakes[snake_idx].die();
+                    snake.die();
                 } else {
                     *life -= 1;
                 }
             }
             _ => (),
         }
+    }
 
+    update_dirs(env, ftx, ctx);
+
+    let snakes = &mut env.snakes;
+    let mut remove_snakes = vec![];
+    for snake_idx in 0..snakes.len() {
         let (snake, other_snakes) = OtherSnakes::split_snakes(snakes, snake_idx);
 
-        // advance the snake
-        snake.advance(other_snakes, &env.apples, &env.gtx, ftx, ctx);
+        // every snake already decided where to go, before any of
+        // them moved, advancing doesn't ask the controllers again
+        snake.body.dir_grace = true;
+        snake.advance(other_snakes.on_board(&env.board), &env.apples, &env.gtx, ftx, ctx);
+        env.board.sync_snake(snake_idx, snake);
 
         // remove snake if it ran out of body
         if snake.body.visible_len() == 0 {
@@ -290,3 +329,103 @@ pub fn advance_snakes(env: &mut Environment, ftx: &FpsContext, ctx: &Context) {
         env.remove_snake(i);
     });
 }
+
+/// Asks the controllers of every snake that can turn for a direction
+///
+/// The controllers decide in parallel against the snakes as they
+/// were before any of them decided, the decisions are then applied
+/// in snake order so the result doesn't depend on how the work was
+/// spread across threads. Player snakes decide on the calling thread
+/// after the others, only their controllers get `ctx`.
+pub fn update_dirs<Rng>(env: &mut Environment<Rng>, ftx: &FpsContext, ctx: Option<&Context>) {
+    env.sync_board();
+
+    let mut controls: Vec<(usize, Control)> = env
+        .snakes
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, snake)| snake.wants_dir())
+        .map(|(index, snake)| (index, snake.take_control()))
+        .collect();
+
+    let snapshot = &env.snakes;
+    let (board, apples, gtx) = (&env.board, &env.apples, &env.gtx);
+    let decide = |(index, control): &mut (usize, Control), ctx: Option<&Context>| {
+        let _span = profiler::span(Phase::Decide(snapshot[*index].snake_type.name()));
+        let other_snakes = OtherSnakes::excluding(snapshot, *index).on_board(board);
+        control.decide(&snapshot[*index], other_snakes, apples, gtx, ftx, ctx);
+    };
+    let is_player =
+        |(index, _): &&mut (usize, Control)| matches!(snapshot[*index].snake_type, snake::Type::Player { .. });
+    controls
+        .par_iter_mut()
+        .filter(|control| !is_player(control))
+        .for_each(|control| decide(control, None));
+    controls
+        .iter_mut()
+        .filter(is_player)
+        .for_each(|control| decide(control, ctx));
+
+    for (index, control) in controls {
+        let (snake, other_snakes) = OtherSnakes::split_snakes(&mut env.snakes, index);
+        snake.return_control(control, other_snakes.on_board(&env.board), &env.gtx, ftx);
+    }
+}
+
+#[test]
+fn test_update_dirs_is_deterministic() {
+    use rand::prelude::*;
+
+    use crate::app::fps_control;
+    use crate::apple::spawn::{spawn_apples, SpawnPolicy};
+    use crate::basic::board::Board;
+    use crate::snake::PaletteTemplate;
+    use crate::snake_control::pathfinder::{self, test_gtx, Costs};
+
+    let ftx = FpsContext {
+        game_state: fps_control::State::Playing,
+        last_graphics_update: (0, 0.),
+        game_frame_num: 0,
+        elapsed_millis: 0,
+    };
+    let competitor = SnakeBuilder::default()
+        .snake_type(snake::Type::Competitor { life: None })
+        .eat_mechanics(EatMechanics::always(EatBehavior::Die))
+        .palette(PaletteTemplate::pastel_rainbow(true))
+        .controller(snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS(
+            Costs::default(),
+        )))
+        .speed(1.);
+
+    // the controllers decide on different threads every time
+    let run = || {
+        let mut gtx = test_gtx(HexDim { h: 30, v: 20 });
+        gtx.apple_spawn_policy = SpawnPolicy::Random { apple_count: 5 };
+        let mut env = Environment {
+            snakes: vec![],
+            apples: vec![],
+            board: Board::default(),
+            gtx,
+            rng: StdRng::seed_from_u64(0),
+        };
+        spawn_snakes(&mut env, vec![competitor.clone(); 8]).unwrap();
+        spawn_apples(&mut env);
+
+        let mut frames = vec![];
+        for _ in 0..40 {
+            advance_snakes(&mut env, &ftx, None);
+            let heads: Vec<_> = env
+                .snakes
+                .iter()
+                .map(|snake| (snake.head().pos, snake.body.dir))
+                .collect();
+            frames.push(heads);
+        }
+        frames
+    };
+
+    let first = run();
+    for _ in 0..3 {
+        assert_eq!(run(), first);
+    }
+}
@@ -12,6 +12,9 @@ pub struct Stats {
     /// that head and tail will have fewer).
     /// Maximum in the case of multiple snakes
     pub max_color_resolution: usize,
+    /// Number of snake segments that were recolored
+    /// instead of being tessellated again
+    pub cached_segments: usize,
     pub redrawing_apples: bool,
     pub redrawing_snakes: bool,
 }
@@ -19,8 +22,12 @@ pub struct Stats {
 impl Stats {
     pub fn get_stats_message(&self) -> Message {
         let text = format!(
-            "total polygons: {}\nmax subsegments: {}\nredrawing apples: {}\nredrawing snakes: {}",
-            self.polygons, self.max_color_resolution, self.redrawing_apples, self.redrawing_snakes,
+            "total polygons: {}\nmax subsegments: {}\ncached segments: {}\nredrawing apples: {}\nredrawing snakes: {}",
+            self.polygons,
+            self.max_color_resolution,
+            self.cached_segments,
+            self.redrawing_apples,
+            self.redrawing_snakes,
         );
         Message {
             text,
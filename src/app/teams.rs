@@ -0,0 +1,112 @@
+//! Team play: which players play together, how teammates
+//! and enemies treat each other, and how teams are scored
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+
+use crate::basic::Food;
+use crate::snake::eat_mechanics::{ByTeamRelation, EatBehavior, TeamRelation};
+use crate::snake::{PlayerId, TeamId};
+use crate::{by_segment_type, by_team_relation};
+
+/// What happens when a player runs into a teammate
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FriendlyFire {
+    /// Teammates pass over each other
+    Off,
+    /// Running into a teammate cuts off their tail
+    Cut,
+    /// Running into a teammate is a crash, same as
+    /// running into any other snake
+    On,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TeamMode {
+    /// Every player for themselves
+    FreeForAll,
+    /// Players are assigned to `count` teams in turn
+    /// (player 1 to team 1, player 2 to team 2, ...)
+    Teams {
+        count: NonZeroUsize,
+        friendly_fire: FriendlyFire,
+    },
+}
+
+impl TeamMode {
+    pub fn team_of(&self, player: PlayerId) -> Option<TeamId> {
+        match self {
+            TeamMode::FreeForAll => None,
+            TeamMode::Teams { count, .. } => Some(player % count.get()),
+        }
+    }
+
+    /// Eat behavior of players towards teammates and enemies,
+    /// interactions with neutral snakes (AIs) are unchanged
+    pub fn team_rules(&self) -> Option<ByTeamRelation> {
+        let TeamMode::Teams { friendly_fire, .. } = self else {
+            return None;
+        };
+
+        let towards_teammates = match friendly_fire {
+            FriendlyFire::Off => EatBehavior::PassOver,
+            FriendlyFire::Cut => EatBehavior::Cut,
+            FriendlyFire::On => EatBehavior::Crash,
+        };
+
+        Some(by_team_relation! {
+            TeamRelation::Teammate => by_segment_type! {
+                _ => towards_teammates,
+            },
+            TeamRelation::Enemy => by_segment_type! {
+                _ => EatBehavior::Cut,
+            },
+        })
+    }
+}
+
+/// Points scored by each team, one point per unit of
+/// food plus one point for every other apple
+#[derive(Default, Debug)]
+pub struct TeamScores(BTreeMap<TeamId, usize>);
+
+impl TeamScores {
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    pub fn add_food(&mut self, team: TeamId, food: Food) {
+        *self.0.entry(team).or_default() += food as usize;
+    }
+
+    pub fn add_apple(&mut self, team: TeamId) {
+        *self.0.entry(team).or_default() += 1;
+    }
+
+    pub fn get(&self, team: TeamId) -> usize {
+        self.0.get(&team).copied().unwrap_or(0)
+    }
+}
+
+#[test]
+fn test_team_rules() {
+    use crate::snake::SegmentType;
+
+    let count = NonZeroUsize::new(2).unwrap();
+    let team_mode = TeamMode::Teams {
+        count,
+        friendly_fire: FriendlyFire::Off,
+    };
+    assert_eq!(
+        [0, 1, 2, 3].map(|player| team_mode.team_of(player)),
+        [Some(0), Some(1), Some(0), Some(1)]
+    );
+    assert_eq!(TeamMode::FreeForAll.team_of(0), None);
+    assert!(TeamMode::FreeForAll.team_rules().is_none());
+
+    let rules = team_mode.team_rules().unwrap();
+    let get = |relation| rules.get(relation).map(|rules| rules.get(SegmentType::DISCR_NORMAL));
+    assert_eq!(get(TeamRelation::Teammate), Some(EatBehavior::PassOver));
+    assert_eq!(get(TeamRelation::Enemy), Some(EatBehavior::Cut));
+    assert_eq!(get(TeamRelation::Neutral), None);
+}
@@ -0,0 +1,152 @@
+//! Racing against the ghost of the best previous run
+//!
+//! Every run of a time attack starts from the same seed, so apples
+//! appear in the same places for as long as the player follows the
+//! same path. The fastest run to reach the target length so far is
+//! replayed as a ghost that nothing collides with.
+
+use std::sync::Arc;
+
+use crate::app::palette::Palette;
+use crate::basic::{Dir, Food};
+use crate::snake;
+use crate::snake::builder::Builder as SnakeBuilder;
+use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
+use crate::snake_control::replay::{RecordedFrame, Recording};
+use crate::snake_control::Template;
+
+/// How a finished run compares to the best one
+pub enum Finish {
+    NewBest { frames: usize },
+    Slower { frames: usize, best: usize },
+}
+
+pub struct TimeAttack {
+    pub seed: u64,
+    /// The player has to grow this long
+    pub target_len: usize,
+    current: Recording,
+    best: Option<Arc<Recording>>,
+    /// Whether the current run has reached the target length
+    finished: bool,
+}
+
+impl TimeAttack {
+    pub fn new(seed: u64, target_len: usize) -> Self {
+        Self {
+            seed,
+            target_len,
+            current: Recording::default(),
+            best: None,
+            finished: false,
+        }
+    }
+
+    /// Frames taken by the best run so far
+    pub fn best_frames(&self) -> Option<usize> {
+        self.best.as_ref().map(|best| best.frames.len())
+    }
+
+    /// Frames taken by the current run so far
+    pub fn current_frames(&self) -> usize {
+        self.current.frames.len()
+    }
+
+    pub fn restart(&mut self) {
+        self.current = Recording::default();
+        self.finished = false;
+    }
+
+    /// A ghost of the best run, to be spawned with the same
+    /// position, direction, and length as the player
+    pub fn ghost(&self, palette: &Palette) -> Option<SnakeBuilder> {
+        let best = self.best.as_ref()?;
+        Some(
+            SnakeBuilder::default()
+                .snake_type(snake::Type::Ghost { life: Some(best.frames.len()) })
+                .eat_mechanics(EatMechanics::always(EatBehavior::PassOver))
+                .palette(palette.palette_ghost)
+                .controller(Template::Replay(best.clone()))
+                .speed(1.),
+        )
+    }
+
+    /// Records the player's move in the current frame, returns
+    /// how the run compares to the best run if the player just
+    /// reached the target length
+    pub fn record(&mut self, dir: Dir, food: Option<Food>, len: usize) -> Option<Finish> {
+        if self.finished {
+            return None;
+        }
+        self.current.frames.push(RecordedFrame { dir, food });
+        if len < self.target_len {
+            return None;
+        }
+
+        self.finished = true;
+        let frames = self.current_frames();
+        match self.best_frames() {
+            Some(best) if best <= frames => Some(Finish::Slower { frames, best }),
+            _ => {
+                self.best = Some(Arc::new(self.current.clone()));
+                Some(Finish::NewBest { frames })
+            }
+        }
+    }
+}
+
+#[test]
+fn test_record_compares_with_best_run() {
+    use Dir::*;
+
+    let mut time_attack = TimeAttack::new(0, 5);
+    assert!(time_attack.record(U, None, 4).is_none());
+    assert!(matches!(
+        time_attack.record(Ur, Some(1), 5),
+        Some(Finish::NewBest { frames: 2 })
+    ));
+    assert_eq!(time_attack.best_frames(), Some(2));
+
+    time_attack.restart();
+    assert_eq!(time_attack.current_frames(), 0);
+    for _ in 0..2 {
+        assert!(time_attack.record(U, None, 4).is_none());
+    }
+    let slower = time_attack.record(U, Some(1), 5);
+    assert!(matches!(slower, Some(Finish::Slower { frames: 3, best: 2 })));
+    assert_eq!(time_attack.best_frames(), Some(2));
+
+    // a run as fast as the best one doesn't replace it
+    time_attack.restart();
+    time_attack.record(U, None, 4);
+    assert!(matches!(
+        time_attack.record(U, None, 5),
+        Some(Finish::Slower { frames: 2, best: 2 })
+    ));
+
+    time_attack.restart();
+    assert!(matches!(
+        time_attack.record(D, Some(1), 5),
+        Some(Finish::NewBest { frames: 1 })
+    ));
+    assert_eq!(time_attack.best_frames(), Some(1));
+}
+
+#[test]
+fn test_record_stops_after_finishing() {
+    let mut time_attack = TimeAttack::new(0, 3);
+    assert!(time_attack.record(Dir::U, Some(1), 3).is_some());
+
+    // the player keeps moving after reaching the target length
+    for len in 3..6 {
+        assert!(time_attack.record(Dir::U, None, len).is_none());
+    }
+    assert_eq!(time_attack.current_frames(), 1);
+    assert_eq!(time_attack.best_frames(), Some(1));
+
+    time_attack.restart();
+    assert!(matches!(
+        time_attack.record(Dir::U, None, 3),
+        Some(Finish::Slower { frames: 1, best: 1 })
+    ));
+}
@@ -1,12 +1,15 @@
+use std::time::Duration;
+
 use rand::Rng;
 
+use crate::app::prefs::CompetitorAi;
+use crate::app::profiler::{self, Phase};
 use crate::app::screen::{Environment, Prefs};
 use crate::apple::{self, Apple};
-use crate::basic::board::{get_occupied_cells, random_free_spot};
 use crate::basic::{Frames, HexPoint};
 use crate::snake::builder::Builder as SnakeBuilder;
 use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
-use crate::snake_control::pathfinder;
+use crate::snake_control::pathfinder::{self, Costs};
 use crate::{app, snake, snake_control};
 
 // #[allow(unused_macros)]
@@ -121,6 +124,18 @@ macro_rules! choose {
     };
 }
 
+fn competitor_controller(ai: CompetitorAi) -> snake_control::Template {
+    match ai {
+        CompetitorAi::Pathfinder => {
+            snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS(Costs::evolved_or_default()))
+        }
+        CompetitorAi::Adversarial => snake_control::Template::Adversarial {
+            time_budget: Duration::from_millis(5),
+            max_depth: 6,
+        },
+    }
+}
+
 // TODO: add a snake spawn policy
 // TODO: factor ai snake palettes out into game palette
 fn generate_apple_type(prefs: &Prefs, palette: &app::Palette, rng: &mut impl Rng) -> apple::Type {
@@ -139,7 +154,7 @@ fn generate_apple_type(prefs: &Prefs, palette: &app::Palette, rng: &mut impl Rng
                     .snake_type(snake::Type::Competitor { life: Some(200) })
                     .eat_mechanics(EatMechanics::always(EatBehavior::Die))
                     .palette(palette.palette_competitor)
-                    .controller(snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS))
+                    .controller(competitor_controller(prefs.competitor_ai))
                     .speed(1.)
                 ))
             },
@@ -148,7 +163,7 @@ fn generate_apple_type(prefs: &Prefs, palette: &app::Palette, rng: &mut impl Rng
                     .snake_type(snake::Type::Killer { life: Some(200) })
                     .eat_mechanics(EatMechanics::always(EatBehavior::Die))
                     .palette(palette.palette_killer)
-                    .controller(snake_control::Template::Killer)
+                    .controller(snake_control::Template::Killer(prefs.killer_difficulty.params()))
                     .speed(1.)
                 ))
             },
@@ -163,31 +178,27 @@ fn generate_apple_type(prefs: &Prefs, palette: &app::Palette, rng: &mut impl Rng
 }
 
 pub fn spawn_apples<Rng: rand::Rng>(env: &mut Environment<Rng>) {
-    // lazy
-    let mut occupied_cells = None;
-
-    let mut spawn = vec![];
+    let _span = profiler::span(Phase::Spawning);
+    env.sync_board();
 
     loop {
         let can_spawn = match &env.gtx.apple_spawn_policy {
             SpawnPolicy::None => false,
-            SpawnPolicy::Random { apple_count } => env.apples.len() + spawn.len() < *apple_count,
-            SpawnPolicy::ScheduledOnEat { apple_count, .. } => env.apples.len() + spawn.len() < *apple_count,
+            SpawnPolicy::Random { apple_count } => env.apples.len() < *apple_count,
+            SpawnPolicy::ScheduledOnEat { apple_count, .. } => env.apples.len() < *apple_count,
         };
 
         if !can_spawn {
             break;
         }
 
-        let occupied_cells = occupied_cells.get_or_insert_with(|| get_occupied_cells(&env.snakes, &env.apples));
-
         let new_apple = match &mut env.gtx.apple_spawn_policy {
             SpawnPolicy::None => panic!("shouldn't be spawning with SpawnPolicy::None"),
             SpawnPolicy::Random { apple_count } => {
-                let apple_pos = match random_free_spot(occupied_cells, env.gtx.board_dim, &mut env.rng) {
+                let apple_pos = match env.board.random_free_cell(&mut env.rng) {
                     Some(pos) => pos,
                     None => {
-                        println!(
+                        eprintln!(
                             "warning: no space left for new apples ({} apples will be missing)",
                             *apple_count - env.apples.len(),
                         );
@@ -195,12 +206,6 @@ pub fn spawn_apples<Rng: rand::Rng>(env: &mut Environment<Rng>) {
                     }
                 };
 
-                // insert at sorted position
-                match occupied_cells.binary_search(&apple_pos) {
-                    Ok(_) => panic!("Spawned apple on top of another apple at {apple_pos:?}"),
-                    Err(idx) => occupied_cells.insert(idx, apple_pos),
-                }
-
                 let apple_type = generate_apple_type(&env.gtx.prefs, &env.gtx.palette, &mut env.rng);
                 Some(Apple { pos: apple_pos, apple_type })
             }
@@ -230,10 +235,9 @@ pub fn spawn_apples<Rng: rand::Rng>(env: &mut Environment<Rng>) {
         };
 
         match new_apple {
-            Some(apple) => spawn.push(apple),
+            // the cell is taken for the next apple
+            Some(apple) => env.add_apple(apple),
             None => break,
         }
     }
-
-    env.apples.extend(spawn);
 }
@@ -1,40 +1,456 @@
-use rand::distributions::uniform::SampleRange;
+use std::collections::VecDeque;
+
 use rand::Rng;
 
 use crate::apple::Apple;
 use crate::basic::{HexDim, HexPoint};
-use crate::snake::Snake;
+use crate::snake::{self, Snake, ZIndex};
+
+/// A snake segment that is on some cell of the board
+#[derive(Copy, Clone, Debug)]
+pub struct SegmentRef {
+    /// Index of the snake in the environment
+    pub snake_index: usize,
+    /// Index of the segment in the snake, the head is 0
+    pub segment_index: usize,
+    pub snake_type: snake::Type,
+    pub z_index: ZIndex,
+}
+
+#[derive(Copy, Clone)]
+struct Occupant {
+    slot: usize,
+    /// Number of heads the snake had pushed when this segment was
+    /// its head, the segment index follows from the current count
+    seq: usize,
+    z_index: ZIndex,
+}
+
+#[derive(Default, Clone)]
+struct Cell {
+    // usually zero or one, more when snakes pass over each other
+    occupants: Vec<Occupant>,
+    /// Occupants that take up space, all but ghosts
+    solid: usize,
+    apple: Option<usize>,
+}
 
-pub fn get_occupied_cells(snakes: &[Snake], apples: &[Apple]) -> Vec<HexPoint> {
-    // upper bound
-    let max_occupied_cells = snakes.iter().map(|snake| snake.body.visible_len()).sum::<usize>() + apples.len();
-    let mut occupied_cells = Vec::with_capacity(max_occupied_cells);
-    occupied_cells.extend(apples.iter().map(|apple| apple.pos));
-    for snake in snakes {
-        occupied_cells.extend(snake.body.segments.iter().map(|hex| hex.pos));
+impl Cell {
+    fn is_occupied(&self) -> bool {
+        self.solid > 0 || self.apple.is_some()
     }
-    occupied_cells.sort_unstable();
-    occupied_cells.dedup();
-    occupied_cells
 }
 
-pub fn random_free_spot(occupied_cells: &[HexPoint], board_dim: HexDim, rng: &mut impl Rng) -> Option<HexPoint> {
-    let free_spaces = (board_dim.h * board_dim.v) as usize - occupied_cells.len();
-    if free_spaces == 0 {
-        return None;
+/// Marks cells that are not in the free list
+const NOT_FREE: usize = usize::MAX;
+
+/// How often constrained sampling tries random free cells before
+/// going through all of them
+const CONSTRAINED_SAMPLE_TRIES: usize = 16;
+
+/// What the board knows about a snake
+struct Tracked {
+    index: usize,
+    snake_type: snake::Type,
+    /// `seq` of the current head
+    head_seq: usize,
+    /// Positions of the segments as of the last sync, head first
+    positions: VecDeque<HexPoint>,
+}
+
+/// Dense grid of everything on the board, kept in sync with the
+/// snakes and apples of an [`Environment`] as they move
+///
+/// Snakes are only ever changed at the ends, syncing a snake after
+/// it moves adds its new head and drops the tail segments it lost.
+/// The free cells are kept in a list that cells are swap-removed
+/// from as they fill up, for O(1) sampling of spawn points.
+///
+/// [`Environment`]: crate::app::screen::Environment
+pub struct Board {
+    dim: HexDim,
+    cells: Vec<Cell>,
+    /// Tracked snakes by slot, slots of removed snakes are reused
+    tracked: Vec<Tracked>,
+    free_slots: Vec<usize>,
+    /// Slot of every snake, by snake index
+    slots: Vec<usize>,
+    apples: Vec<HexPoint>,
+    /// Indices of the cells that are not occupied, in no particular order
+    free: Vec<usize>,
+    /// Position of every cell in `free`, or `NOT_FREE`
+    free_index: Vec<usize>,
+}
+
+/// An empty board, for environments that don't know their size yet
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(HexPoint { h: 0, v: 0 })
     }
+}
 
-    let mut new_idx = (0..free_spaces).sample_single(rng);
-    for HexPoint { h, v } in occupied_cells {
-        let idx = (v * board_dim.h + h) as usize;
-        if idx <= new_idx {
-            new_idx += 1;
+impl Board {
+    pub fn new(dim: HexDim) -> Self {
+        let num_cells = (dim.h * dim.v) as usize;
+        Self {
+            dim,
+            cells: vec![Cell::default(); num_cells],
+            tracked: vec![],
+            free_slots: vec![],
+            slots: vec![],
+            apples: vec![],
+            free: (0..num_cells).collect(),
+            free_index: (0..num_cells).collect(),
         }
     }
 
-    assert!(new_idx < (board_dim.h * board_dim.v) as usize);
-    Some(HexPoint {
-        h: new_idx as isize % board_dim.h,
-        v: new_idx as isize / board_dim.h,
-    })
+    /// A board of the given size with all the snakes and apples on it
+    pub fn build(dim: HexDim, snakes: &[Snake], apples: &[Apple]) -> Self {
+        let mut board = Self::new(dim);
+        snakes.iter().for_each(|snake| board.add_snake(snake));
+        board.sync_apples(apples);
+        board
+    }
+
+    pub fn dim(&self) -> HexDim {
+        self.dim
+    }
+
+    /// Number of tracked snakes
+    pub fn num_snakes(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Cells outside of the board are always empty, snakes can be
+    /// partly outside for a moment when the board shrinks
+    fn cell_index(&self, pos: HexPoint) -> Option<usize> {
+        self.dim.contains(pos).then(|| (pos.v * self.dim.h + pos.h) as usize)
+    }
+
+    fn cell(&self, pos: HexPoint) -> Option<&Cell> {
+        self.cell_index(pos).map(|idx| &self.cells[idx])
+    }
+
+    fn pos_of(&self, idx: usize) -> HexPoint {
+        HexPoint {
+            h: idx as isize % self.dim.h,
+            v: idx as isize / self.dim.h,
+        }
+    }
+
+    /// Moves a cell in or out of the free list after it changed
+    fn update_free(&mut self, idx: usize) {
+        let occupied = self.cells[idx].is_occupied();
+        let listed = self.free_index[idx] != NOT_FREE;
+        if occupied && listed {
+            let i = self.free_index[idx];
+            self.free.swap_remove(i);
+            if let Some(&moved) = self.free.get(i) {
+                self.free_index[moved] = i;
+            }
+            self.free_index[idx] = NOT_FREE;
+        } else if !occupied && !listed {
+            self.free_index[idx] = self.free.len();
+            self.free.push(idx);
+        }
+    }
+
+    /// The snake segments on a cell
+    pub fn segments_at(&self, pos: HexPoint) -> impl Iterator<Item = SegmentRef> + '_ {
+        let occupants = self.cell(pos).map_or(&[][..], |cell| cell.occupants.as_slice());
+        occupants.iter().map(move |occupant| {
+            let tracked = &self.tracked[occupant.slot];
+            SegmentRef {
+                snake_index: tracked.index,
+                segment_index: tracked.head_seq - occupant.seq,
+                snake_type: tracked.snake_type,
+                z_index: occupant.z_index,
+            }
+        })
+    }
+
+    /// Index of the apple on a cell
+    pub fn apple_at(&self, pos: HexPoint) -> Option<usize> {
+        self.cell(pos)?.apple
+    }
+
+    /// Whether a cell has an apple or a snake on it, ghosts don't
+    /// take up any space
+    pub fn is_occupied(&self, pos: HexPoint) -> bool {
+        self.cell(pos).map_or(false, Cell::is_occupied)
+    }
+
+    pub fn num_free_cells(&self) -> usize {
+        self.free.len()
+    }
+
+    /// A uniformly random cell that is not occupied (see
+    /// [`Self::is_occupied`])
+    pub fn random_free_cell(&self, rng: &mut impl Rng) -> Option<HexPoint> {
+        if self.free.is_empty() {
+            return None;
+        }
+        Some(self.pos_of(self.free[rng.gen_range(0..self.free.len())]))
+    }
+
+    /// A uniformly random free cell that satisfies `allowed`, if
+    /// random free cells keep failing the check, all of them are
+    /// checked to find the ones that pass
+    pub fn random_free_cell_where(&self, rng: &mut impl Rng, allowed: impl Fn(HexPoint) -> bool) -> Option<HexPoint> {
+        for _ in 0..CONSTRAINED_SAMPLE_TRIES {
+            let pos = self.random_free_cell(rng)?;
+            if allowed(pos) {
+                return Some(pos);
+            }
+        }
+
+        let candidates: Vec<_> = self
+            .free
+            .iter()
+            .map(|&idx| self.pos_of(idx))
+            .filter(|&pos| allowed(pos))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[rng.gen_range(0..candidates.len())])
+    }
+
+    fn insert(&mut self, pos: HexPoint, occupant: Occupant) {
+        let Some(idx) = self.cell_index(pos) else { return };
+        let ghost = matches!(self.tracked[occupant.slot].snake_type, snake::Type::Ghost { .. });
+        let cell = &mut self.cells[idx];
+        cell.occupants.push(occupant);
+        if !ghost {
+            cell.solid += 1;
+            self.update_free(idx);
+        }
+    }
+
+    fn remove(&mut self, pos: HexPoint, slot: usize, seq: usize) {
+        let Some(idx) = self.cell_index(pos) else { return };
+        let ghost = matches!(self.tracked[slot].snake_type, snake::Type::Ghost { .. });
+        let cell = &mut self.cells[idx];
+        if let Some(i) = cell.occupants.iter().position(|o| o.slot == slot && o.seq == seq) {
+            cell.occupants.swap_remove(i);
+            if !ghost {
+                cell.solid -= 1;
+                self.update_free(idx);
+            }
+        }
+    }
+
+    /// Starts tracking a snake that was pushed to the end of the snakes
+    pub fn add_snake(&mut self, snake: &Snake) {
+        let tracked = Tracked {
+            index: self.slots.len(),
+            snake_type: snake.snake_type,
+            head_seq: snake.body.segments.len(),
+            positions: snake.body.segments.iter().map(|segment| segment.pos).collect(),
+        };
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.tracked[slot] = tracked;
+                slot
+            }
+            None => {
+                self.tracked.push(tracked);
+                self.tracked.len() - 1
+            }
+        };
+        self.slots.push(slot);
+
+        let head_seq = snake.body.segments.len();
+        for (k, segment) in snake.body.segments.iter().enumerate() {
+            let occupant = Occupant {
+                slot,
+                seq: head_seq - k,
+                z_index: segment.z_index,
+            };
+            self.insert(segment.pos, occupant);
+        }
+    }
+
+    /// Stops tracking a snake, the indices of the following
+    /// snakes shift down by one
+    pub fn remove_snake(&mut self, index: usize) {
+        let slot = self.slots.remove(index);
+        let positions = std::mem::take(&mut self.tracked[slot].positions);
+        let head_seq = self.tracked[slot].head_seq;
+        for (k, pos) in positions.into_iter().enumerate() {
+            self.remove(pos, slot, head_seq - k);
+        }
+        self.free_slots.push(slot);
+
+        for &slot in &self.slots[index..] {
+            self.tracked[slot].index -= 1;
+        }
+    }
+
+    /// Catches up with the changes to a snake since the last sync,
+    /// a snake that moved has a new head and may have lost some of
+    /// its tail, a snake that was cut only lost its tail
+    pub fn sync_snake(&mut self, index: usize, snake: &Snake) {
+        let slot = self.slots[index];
+        let segments = &snake.body.segments;
+        self.tracked[slot].snake_type = snake.snake_type;
+
+        let tracked = &self.tracked[slot];
+        let moved = match (segments.front(), tracked.positions.front()) {
+            (Some(head), Some(&old_head)) => head.pos != old_head,
+            _ => false,
+        };
+        if moved {
+            let seq = tracked.head_seq + 1;
+            let head = segments[0];
+            self.insert(head.pos, Occupant { slot, seq, z_index: head.z_index });
+            let tracked = &mut self.tracked[slot];
+            tracked.head_seq = seq;
+            tracked.positions.push_front(head.pos);
+        }
+
+        while self.tracked[slot].positions.len() > segments.len() {
+            let tracked = &mut self.tracked[slot];
+            let pos = tracked.positions.pop_back().unwrap();
+            // the popped segment had the index the length is now
+            let seq = tracked.head_seq - tracked.positions.len();
+            self.remove(pos, slot, seq);
+        }
+
+        // only the head changes layers, when passing over or under
+        if let Some(idx) = segments.front().and_then(|head| self.cell_index(head.pos)) {
+            let head_seq = self.tracked[slot].head_seq;
+            let occupants = &mut self.cells[idx].occupants;
+            if let Some(occupant) = occupants.iter_mut().find(|o| o.slot == slot && o.seq == head_seq) {
+                occupant.z_index = segments[0].z_index;
+            }
+        }
+
+        debug_assert_eq!(
+            self.tracked[slot].positions.len(),
+            segments.len(),
+            "snake {index} out of sync"
+        );
+    }
+
+    /// Catches up with the apples, only the apples after the
+    /// first one that changed are updated
+    pub fn sync_apples(&mut self, apples: &[Apple]) {
+        let unchanged = self
+            .apples
+            .iter()
+            .zip(apples)
+            .take_while(|(&pos, apple)| pos == apple.pos)
+            .count();
+
+        let changed = self.apples.split_off(unchanged);
+        for (apple_index, pos) in changed.into_iter().enumerate().map(|(i, pos)| (unchanged + i, pos)) {
+            let Some(idx) = self.cell_index(pos) else { continue };
+            // another apple could have been put there already
+            if self.cells[idx].apple == Some(apple_index) {
+                self.cells[idx].apple = None;
+                self.update_free(idx);
+            }
+        }
+        for apple in &apples[unchanged..] {
+            self.push_apple(apple);
+        }
+    }
+
+    /// Adds an apple after the ones the board already has
+    pub fn push_apple(&mut self, apple: &Apple) {
+        if let Some(idx) = self.cell_index(apple.pos) {
+            self.cells[idx].apple = Some(self.apples.len());
+            self.update_free(idx);
+        }
+        self.apples.push(apple.pos);
+    }
+}
+
+#[test]
+fn test_sync_follows_snake() {
+    use crate::app::fps_control::FpsControl;
+    use crate::basic::Dir;
+    use crate::snake::builder::Builder as SnakeBuilder;
+    use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
+    use crate::snake::PaletteTemplate;
+    use crate::snake_control::pathfinder::test_gtx;
+    use crate::snake_control::Template;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexPoint { h: 10, v: 10 };
+    let gtx = test_gtx(board_dim);
+    let ftx = FpsControl::new(1.);
+    let mut snake = SnakeBuilder::default()
+        .snake_type(snake::Type::Simulated)
+        .eat_mechanics(EatMechanics::always(EatBehavior::Crash))
+        .palette(PaletteTemplate::rainbow(true))
+        .controller(Template::Programmed(vec![]))
+        .pos(HexPoint { h: 5, v: 0 })
+        .dir(Dir::U)
+        .len(4)
+        .speed(1.)
+        .build()
+        .unwrap();
+
+    let mut board = Board::new(board_dim);
+    board.add_snake(&snake);
+    // grows to 4 segments, then keeps moving and wraps around
+    for _ in 0..6 {
+        snake.advance(OtherSnakes::empty(), &[], &gtx, ftx.context(), None);
+        board.sync_snake(0, &snake);
+    }
+    snake.cut_at(2);
+    board.sync_snake(0, &snake);
+
+    let expected: Vec<_> = snake.body.segments.iter().map(|segment| segment.pos).collect();
+    assert_eq!(board.num_free_cells(), 100 - expected.len());
+    for (segment_index, &pos) in expected.iter().enumerate() {
+        let found: Vec<_> = board
+            .segments_at(pos)
+            .map(|s| (s.snake_index, s.segment_index))
+            .collect();
+        assert_eq!(found, vec![(0, segment_index)]);
+    }
+
+    board.remove_snake(0);
+    assert_eq!(board.num_free_cells(), 100);
+}
+
+#[test]
+fn test_free_cell_sampling() {
+    use rand::prelude::*;
+
+    use crate::apple;
+
+    let mut board = Board::new(HexPoint { h: 4, v: 4 });
+    // everything but the last column
+    let apples: Vec<_> = (0..4)
+        .flat_map(|v| (0..3).map(move |h| HexPoint { h, v }))
+        .map(|pos| Apple {
+            pos,
+            apple_type: apple::Type::Food(1),
+        })
+        .collect();
+    board.sync_apples(&apples);
+    assert_eq!(board.num_free_cells(), 4);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..20 {
+        let pos = board.random_free_cell(&mut rng).unwrap();
+        assert_eq!(pos.h, 3);
+        let pos = board.random_free_cell_where(&mut rng, |pos| pos.v >= 2).unwrap();
+        assert_eq!((pos.h, pos.v >= 2), (3, true));
+    }
+    assert_eq!(board.random_free_cell_where(&mut rng, |pos| pos.h == 0), None);
+
+    // removing the first apple frees its cell
+    board.sync_apples(&apples[1..]);
+    assert_eq!(board.num_free_cells(), 5);
+    assert!(!board.is_occupied(HexPoint { h: 0, v: 0 }));
+    assert_eq!(board.apple_at(HexPoint { h: 1, v: 0 }), Some(0));
+
+    board.push_apple(&apples[0]);
+    assert_eq!(board.num_free_cells(), 4);
+    assert_eq!(board.apple_at(HexPoint { h: 0, v: 0 }), Some(11));
 }
@@ -95,6 +95,29 @@ impl HexPoint {
         (dh + dv_overflow) as usize
     }
 
+    // O(1)
+    // lower bound on the number of steps needed to leave the board,
+    //  every step changes h and v by at most 1
+    pub fn distance_to_edge(self, board_dim: HexDim) -> usize {
+        [self.h, board_dim.h - 1 - self.h, self.v, board_dim.v - 1 - self.v]
+            .into_iter()
+            .min()
+            .unwrap()
+            .max(0) as usize
+    }
+
+    // O(1)
+    // manhattan_distance that considers wrapping, each teleport costs
+    //  an extra teleport_cost
+    // this is a lower bound, it assumes the snake can reappear at any
+    //  edge, so it never overestimates the real cost (useful for A*)
+    pub fn wrapping_manhattan_distance(self, other: Self, board_dim: HexDim, teleport_cost: usize) -> usize {
+        let direct = self.manhattan_distance(other);
+        // get to an edge, step over it, then come in from an edge
+        let wrapped = self.distance_to_edge(board_dim) + 1 + teleport_cost + other.distance_to_edge(board_dim);
+        direct.min(wrapped)
+    }
+
     // obviously oblivious to teleportation, only works on the plane
     // all cells within a manhattan distance of radius (including self)
     // guarantees no duplicates, not sorted
@@ -304,3 +327,24 @@ fn test_manhattan_distance() {
         assert_eq!(p1.manhattan_distance(p2), d);
     });
 }
+
+#[test]
+fn test_wrapping_manhattan_distance() {
+    let board_dim = HexPoint { h: 20, v: 10 };
+    [
+        // far apart on the plane, close across the edge
+        ((0, 0), (0, 9), 0, 1),
+        ((0, 0), (0, 9), 15, 9),
+        ((0, 0), (19, 9), 15, 16),
+        // closer on the plane than across the edge
+        ((1, 5), (18, 5), 100, 17),
+        ((5, 5), (6, 5), 15, 1),
+    ]
+    .iter()
+    .for_each(|&((h1, v1), (h2, v2), teleport_cost, d)| {
+        let p1 = HexPoint { h: h1, v: v1 };
+        let p2 = HexPoint { h: h2, v: v2 };
+        assert_eq!(p1.wrapping_manhattan_distance(p2, board_dim, teleport_cost), d);
+        assert_eq!(p2.wrapping_manhattan_distance(p1, board_dim, teleport_cost), d);
+    });
+}
@@ -0,0 +1,279 @@
+//! Evolves the costs of the search-based pathfinders
+//!
+//! Every genome plays the same headless games alone on the board
+//! and scores a point for every apple plus up to `SURVIVAL_POINTS`
+//! for lasting the whole game. Each generation keeps the best
+//! genomes and fills up the population with mutated crossovers of
+//! genomes picked by tournament selection. The best genome is saved
+//! after every generation, see [`Costs::load`] for reading it back.
+//! Run `hex_snake evolve --help` for options.
+//!
+//! The killer's [`Params`](crate::snake_control::killer::Params) are
+//! not evolved, they set how hard the killer is to escape and are
+//! picked by the player through the difficulty. Evolving them would
+//! only find the hardest killer, which is already known: one that
+//! reacts immediately and looks as far ahead as possible.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process;
+
+use itertools::Itertools;
+use rand::prelude::*;
+
+use crate::basic::{HexDim, HexPoint};
+use crate::snake_control::pathfinder::{self, Costs};
+use crate::snake_control::Template;
+use crate::tournament::{self, Contestant};
+
+/// Points for surviving a whole game, less for dying earlier
+const SURVIVAL_POINTS: f64 = 10.;
+/// The best genomes of each generation survive unchanged
+const ELITES: usize = 2;
+/// Parents are the best of this many random genomes
+const SELECTION_SIZE: usize = 3;
+/// Upper bound for every cost
+const MAX_COST: usize = 100;
+
+pub struct Settings {
+    pub population: usize,
+    pub generations: usize,
+    /// Every genome is scored on the same games
+    pub games: u64,
+    pub first_seed: u64,
+    pub board_dim: HexDim,
+    pub max_steps: usize,
+    pub apple_count: usize,
+    pub start_len: usize,
+}
+
+fn fitness(costs: Costs, settings: &Settings) -> f64 {
+    let tournament_settings = tournament::Settings {
+        contestants: vec![Contestant {
+            name: "genome".to_string(),
+            controller: Template::Algorithm(pathfinder::Template::WeightedBFS(costs)),
+        }],
+        board_dims: vec![settings.board_dim],
+        seeds: settings.first_seed..settings.first_seed + settings.games,
+        max_steps: settings.max_steps,
+        apple_count: settings.apple_count,
+        start_len: settings.start_len,
+    };
+
+    let records = tournament::run(&tournament_settings);
+    if records.is_empty() {
+        return 0.;
+    }
+    let total: f64 = records
+        .iter()
+        .map(|record| record.apples as f64 + SURVIVAL_POINTS * record.survival as f64 / settings.max_steps as f64)
+        .sum();
+    total / records.len() as f64
+}
+
+fn random_genome(rng: &mut impl Rng) -> Costs {
+    Costs {
+        turn: rng.gen_range(0..=MAX_COST),
+        teleport: rng.gen_range(0..=MAX_COST),
+    }
+}
+
+/// Each cost comes from either parent
+fn crossover(a: Costs, b: Costs, rng: &mut impl Rng) -> Costs {
+    Costs {
+        turn: if rng.gen() { a.turn } else { b.turn },
+        teleport: if rng.gen() { a.teleport } else { b.teleport },
+    }
+}
+
+/// Nudges each cost with probability 1/2, by up to a quarter of its value
+fn mutate(costs: Costs, rng: &mut impl Rng) -> Costs {
+    let mut nudge = |cost: usize| {
+        if rng.gen() {
+            return cost;
+        }
+        let max_step = (cost / 4 + 1) as isize;
+        (cost as isize + rng.gen_range(-max_step..=max_step)).clamp(0, MAX_COST as isize) as usize
+    };
+    Costs {
+        turn: nudge(costs.turn),
+        teleport: nudge(costs.teleport),
+    }
+}
+
+/// Returns the best genome and its fitness
+pub fn evolve(settings: &Settings, output: &PathBuf, rng: &mut impl Rng) -> (Costs, f64) {
+    // the hand-picked defaults compete from the start
+    let mut population = vec![Costs::default()];
+    population.extend((1..settings.population).map(|_| random_genome(rng)));
+
+    // the games are the same every time, no need to replay them
+    let mut scores: HashMap<Costs, f64> = HashMap::new();
+    let mut best = (Costs::default(), f64::NEG_INFINITY);
+
+    for generation in 0..settings.generations {
+        let ranked: Vec<(Costs, f64)> = population
+            .iter()
+            .map(|&costs| (costs, *scores.entry(costs).or_insert_with(|| fitness(costs, settings))))
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .collect();
+
+        if ranked[0].1 > best.1 {
+            best = ranked[0];
+            if let Err(e) = best.0.save(output) {
+                eprintln!("warning: failed to write {}: {e}", output.display());
+            }
+        }
+        eprintln!(
+            "generation {generation}: best {:?} scored {:.2}, median {:.2}",
+            ranked[0].0,
+            ranked[0].1,
+            ranked[ranked.len() / 2].1,
+        );
+
+        population = next_generation(&ranked, settings.population, rng);
+    }
+
+    best
+}
+
+/// The elites of `ranked`, which is sorted best first, followed by
+/// mutated crossovers of parents picked by tournament selection
+fn next_generation(ranked: &[(Costs, f64)], size: usize, rng: &mut impl Rng) -> Vec<Costs> {
+    let select = |rng: &mut _| {
+        // ranked is sorted, the lowest index wins
+        let winner = (0..SELECTION_SIZE)
+            .map(|_| rng.gen_range(0..ranked.len()))
+            .min()
+            .unwrap();
+        ranked[winner].0
+    };
+
+    let mut population: Vec<_> = ranked.iter().take(ELITES).map(|(costs, _)| *costs).collect();
+    while population.len() < size {
+        let child = crossover(select(rng), select(rng), rng);
+        population.push(mutate(child, rng));
+    }
+    population
+}
+
+const USAGE: &str = "\
+usage: hex_snake evolve [options]
+
+options:
+  --population N         genomes per generation (default 16)
+  --generations N        number of generations (default 10)
+  --games N              games played by every genome (default 20)
+  --seed N               first seed of the games, also seeds the
+                         evolution (default 0)
+  --board WxH            board size (default 30x20)
+  --steps N              maximum length of a game in frames (default 2000)
+  --apples N             number of apples on the board (default 5)
+  --len N                starting length of the snake (default 5)
+  --output PATH          where to write the best genome (default genome.json)";
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {message}\n\n{USAGE}");
+    process::exit(2)
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(format!("missing value for {option}")));
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value for {option}: {value:?}")))
+}
+
+/// Entry point of `hex_snake evolve`, `args` excludes the subcommand
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut settings = Settings {
+        population: 16,
+        generations: 10,
+        games: 20,
+        first_seed: 0,
+        board_dim: HexPoint { h: 30, v: 20 },
+        max_steps: 2000,
+        apple_count: 5,
+        start_len: 5,
+    };
+    let mut output = PathBuf::from(Costs::GENOME_FILE);
+
+    while let Some(option) = args.next() {
+        match option.as_str() {
+            "--population" => settings.population = parse(&option, args.next()),
+            "--generations" => settings.generations = parse(&option, args.next()),
+            "--games" => settings.games = parse(&option, args.next()),
+            "--seed" => settings.first_seed = parse(&option, args.next()),
+            "--board" => {
+                let value: String = parse(&option, args.next());
+                settings.board_dim =
+                    tournament::parse_board(&value).unwrap_or_else(|| fail(format!("invalid board size {value:?}")));
+            }
+            "--steps" => settings.max_steps = parse(&option, args.next()),
+            "--apples" => settings.apple_count = parse(&option, args.next()),
+            "--len" => settings.start_len = parse(&option, args.next()),
+            "--output" => output = parse(&option, args.next()),
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            other => fail(format!("unknown option {other:?}")),
+        }
+    }
+
+    if settings.population <= ELITES {
+        fail(format!("the population has to be larger than {ELITES}"));
+    }
+    if settings.generations == 0 || settings.games == 0 {
+        fail("nothing to do".to_string());
+    }
+
+    let mut rng = StdRng::seed_from_u64(settings.first_seed);
+    let (best, score) = evolve(&settings, &output, &mut rng);
+    eprintln!(
+        "best genome: turn cost {}, teleport cost {} (scored {score:.2}), saved to {}",
+        best.turn,
+        best.teleport,
+        output.display()
+    );
+}
+
+#[test]
+fn test_offspring_stays_in_bounds() {
+    let rng = &mut StdRng::seed_from_u64(0);
+    let extremes = [
+        Costs { turn: 0, teleport: 0 },
+        Costs { turn: MAX_COST, teleport: MAX_COST },
+    ];
+
+    for _ in 0..1000 {
+        let a = random_genome(rng);
+        let b = extremes[rng.gen_range(0..extremes.len())];
+        let child = crossover(a, b, rng);
+        assert!([a.turn, b.turn].contains(&child.turn), "{child:?}");
+        assert!([a.teleport, b.teleport].contains(&child.teleport), "{child:?}");
+
+        let mutant = mutate(child, rng);
+        for (before, after) in [(child.turn, mutant.turn), (child.teleport, mutant.teleport)] {
+            assert!(after <= MAX_COST, "{mutant:?}");
+            assert!(before.abs_diff(after) <= before / 4 + 1, "{child:?} -> {mutant:?}");
+        }
+    }
+}
+
+#[test]
+fn test_next_generation_keeps_elites() {
+    let rng = &mut StdRng::seed_from_u64(0);
+    let ranked: Vec<_> = (0..8)
+        .map(|i| (Costs { turn: i, teleport: 10 * i }, 10. - i as f64))
+        .collect();
+    let elites: Vec<_> = ranked[..ELITES].iter().map(|(costs, _)| *costs).collect();
+    let in_bounds = |costs: &Costs| costs.turn <= MAX_COST && costs.teleport <= MAX_COST;
+
+    for size in [ELITES + 1, 8, 20] {
+        let population = next_generation(&ranked, size, rng);
+        assert_eq!(population.len(), size);
+        assert_eq!(population[..ELITES], elites[..]);
+        assert!(population.iter().all(in_bounds));
+    }
+}
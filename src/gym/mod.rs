@@ -0,0 +1,422 @@
+//! Headless, Gym-style environment for training agents
+//!
+//! The game is stepped one frame at a time without a window,
+//! agents are player snakes that follow the [`Action`]s passed
+//! to [`Gym::step`]. A trained agent can be put back into the
+//! game with [`snake_control::Template::Policy`]. Players can
+//! also be controlled by any other controller, which is how
+//! AIs are compared in [`crate::tournament`]. Programs in other
+//! languages can use the environment through [`server`].
+
+use observation::{Encoding, Observation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use reward::{AppleAndCrash, Cause, Event, RewardShaping};
+
+use crate::app::fps_control::{self, FpsContext};
+use crate::app::game_context::GameContext;
+use crate::app::screen::{Environment, Prefs};
+use crate::app::snake_management::{
+    advance_snakes, find_collisions, handle_collisions, player_spawn_points, spawn_snakes, Collision,
+};
+use crate::app::Palette;
+use crate::apple::spawn::{spawn_apples, SpawnPolicy};
+use crate::basic::board::Board;
+use crate::basic::{CellDim, HexDim, HexPoint};
+use crate::error::{Error, ErrorConversion, Result};
+use crate::snake::builder::Builder as SnakeBuilder;
+use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
+use crate::snake::{self, PlayerId, SegmentType, Snake, State};
+pub use crate::snake_control::agent::Action;
+use crate::snake_control::agent::ActionSlot;
+use crate::snake_control::pathfinder::{self, Costs};
+use crate::view::snakes::OtherSnakes;
+use crate::{apple, by_segment_type, by_snake_type, snake_control};
+
+pub mod observation;
+pub mod reward;
+pub mod server;
+
+pub struct Config {
+    pub board_dim: HexDim,
+    /// Number of snakes controlled through [`Gym::step`]
+    pub agents: usize,
+    /// Players with their own controllers, numbered after the agents
+    pub contestants: Vec<snake_control::Template>,
+    /// Starting length of agents and contestants
+    pub agent_len: usize,
+    /// Other snakes on the board, placed randomly unless they have a position
+    pub opponents: Vec<SnakeBuilder>,
+    pub apple_count: usize,
+    /// Whether apples can spawn competitors, killers, and rain
+    pub special_apples: bool,
+    pub encoding: Encoding,
+    pub reward: Box<dyn RewardShaping + Send>,
+    /// The episode ends after this many steps
+    pub max_steps: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            board_dim: HexPoint { h: 30, v: 20 },
+            agents: 1,
+            contestants: vec![],
+            agent_len: 5,
+            opponents: vec![Self::weighted_bfs_competitor()],
+            apple_count: 5,
+            special_apples: false,
+            encoding: Encoding::Grid,
+            reward: Box::new(AppleAndCrash::default()),
+            max_steps: Some(10_000),
+        }
+    }
+}
+
+impl Config {
+    /// An apple-seeking AI that stays for the whole episode
+    pub fn weighted_bfs_competitor() -> SnakeBuilder {
+        SnakeBuilder::default()
+            .snake_type(snake::Type::Competitor { life: None })
+            .eat_mechanics(EatMechanics::always(EatBehavior::Die))
+            .palette(Palette::dark().palette_competitor)
+            .controller(snake_control::Template::Algorithm(pathfinder::Template::WeightedBFS(
+                Costs::default(),
+            )))
+            .speed(1.)
+    }
+}
+
+/// Result of [`Gym::step`], observations and rewards are per agent
+pub struct Step {
+    pub observations: Vec<Observation>,
+    pub rewards: Vec<f32>,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// Information about all players, agents first, then contestants
+#[derive(Debug)]
+pub struct Info {
+    /// Number of steps since the last reset
+    pub step: usize,
+    /// What happened to each player during the step
+    pub events: Vec<Vec<Event>>,
+    /// Current length of each player's snake, 0 once it's gone
+    pub lengths: Vec<usize>,
+}
+
+pub struct Gym {
+    config: Config,
+    env: Environment<StdRng>,
+    /// One per agent, indexed by player id
+    actions: Vec<ActionSlot>,
+    step: usize,
+}
+
+impl Gym {
+    /// Call [`Gym::reset`] before the first step
+    pub fn new(config: Config) -> Self {
+        let prefs = Prefs {
+            special_apples: config.special_apples,
+            ..Prefs::default()
+        };
+        let gtx = GameContext::new(
+            config.board_dim,
+            // irrelevant without a window
+            CellDim::from(1.),
+            Palette::dark(),
+            prefs,
+            SpawnPolicy::Random { apple_count: config.apple_count },
+        );
+
+        Self {
+            actions: (0..config.agents).map(|_| ActionSlot::default()).collect(),
+            config,
+            env: Environment {
+                snakes: vec![],
+                apples: vec![],
+                board: Board::default(),
+                gtx,
+                rng: StdRng::seed_from_u64(0),
+            },
+            step: 0,
+        }
+    }
+
+    /// Start a new episode, the same seed always leads to the same
+    /// episode given the same actions
+    pub fn reset(&mut self, seed: u64) -> Result<Vec<Observation>> {
+        let num_players = self.num_players();
+        let env = &mut self.env;
+        env.rng = StdRng::seed_from_u64(seed);
+        env.clear();
+        env.gtx.apple_spawn_policy.reset();
+        self.step = 0;
+
+        let controllers = self
+            .actions
+            .iter()
+            .map(|slot| {
+                slot.set(Action::Straight);
+                snake_control::Template::Agent(slot.clone())
+            })
+            .chain(self.config.contestants.iter().cloned());
+
+        let spawn_points = player_spawn_points(num_players, env.gtx.board_dim);
+        for ((id, controller), (pos, dir)) in controllers.enumerate().zip(spawn_points) {
+            let eat_mechanics = EatMechanics::new(
+                by_segment_type! {
+                    SegmentType::DISCR_EATEN => EatBehavior::PassOver,
+                    _ => EatBehavior::Crash,
+                },
+                by_snake_type! {
+                    _ => by_segment_type! {
+                        _ => EatBehavior::Crash,
+                    },
+                },
+            );
+            let seed = SnakeBuilder::default()
+                .snake_type(snake::Type::Player { id })
+                .eat_mechanics(eat_mechanics)
+                .palette(snake::PaletteTemplate::rainbow(true))
+                .controller(controller)
+                .speed(1.)
+                .pos(pos)
+                .dir(dir)
+                .len(self.config.agent_len);
+            env.add_snake(&seed).with_trace_step("Gym::reset")?;
+        }
+
+        spawn_snakes(env, self.config.opponents.clone()).with_trace_step("Gym::reset")?;
+        spawn_apples(env);
+
+        Ok(self.observations())
+    }
+
+    /// Advance the game by one frame, `actions` has one entry per agent
+    pub fn step(&mut self, actions: &[Action]) -> Result<Step> {
+        assert_eq!(actions.len(), self.config.agents, "expected one action per agent");
+        for (slot, action) in self.actions.iter().zip(actions) {
+            slot.set(*action);
+        }
+
+        self.step += 1;
+        let ftx = FpsContext {
+            game_state: fps_control::State::Playing,
+            last_graphics_update: (self.step, 0.),
+            game_frame_num: self.step,
+            elapsed_millis: 0,
+        };
+
+        advance_snakes(&mut self.env, &ftx, None);
+
+        let mut events = vec![vec![]; self.num_players()];
+        // what each snake ran into, by snake index
+        let mut causes = vec![None; self.env.snakes.len()];
+
+        let collisions = find_collisions(&self.env);
+        for collision in &collisions {
+            match *collision {
+                Collision::Apple { snake_index, apple_index } => {
+                    let snake::Type::Player { id } = self.env.snakes[snake_index].snake_type else {
+                        continue;
+                    };
+                    events[id].push(match self.env.apples[apple_index].apple_type {
+                        apple::Type::Food(food) => Event::Ate(food),
+                        _ => Event::AteSpecial,
+                    });
+                }
+                Collision::Snake {
+                    snake1_index,
+                    snake2_index,
+                    snake2_segment_index: 0,
+                } => {
+                    causes[snake1_index].get_or_insert(Cause::HeadOn);
+                    causes[snake2_index].get_or_insert(Cause::HeadOn);
+                }
+                Collision::Snake { snake1_index, .. } => {
+                    causes[snake1_index].get_or_insert(Cause::OtherBody);
+                }
+                Collision::Itself { snake_index, .. } => {
+                    causes[snake_index].get_or_insert(Cause::Itself);
+                }
+            }
+        }
+
+        let living_before: Vec<_> = (0..self.num_players())
+            .map(|id| {
+                self.player_snake(id)
+                    .map_or(false, |snake| snake.state == State::Living)
+            })
+            .collect();
+
+        let (seeds, _) = handle_collisions(&mut self.env, &collisions);
+
+        // crashed snakes can't advance, they're taken out of the game
+        for (snake, cause) in self.env.snakes.iter_mut().zip(causes) {
+            let cause = cause.unwrap_or(Cause::Other);
+            if snake.state == State::Crashed {
+                if let snake::Type::Player { id } = snake.snake_type {
+                    events[id].push(Event::Crashed(cause));
+                }
+                snake.die();
+            } else if let snake::Type::Player { id } = snake.snake_type {
+                if living_before[id] && snake.state == State::Dying {
+                    events[id].push(Event::Died(cause));
+                }
+            }
+        }
+
+        spawn_snakes(&mut self.env, seeds)
+            .map_err(Error::from)
+            .with_trace_step("Gym::step")?;
+        spawn_apples(&mut self.env);
+
+        let rewards = events[..self.config.agents]
+            .iter()
+            .enumerate()
+            .map(|(id, events)| {
+                let snake = self
+                    .env
+                    .snakes
+                    .iter()
+                    .find(|snake| snake.snake_type == snake::Type::Player { id });
+                self.config.reward.reward(events, snake)
+            })
+            .collect();
+
+        // the episode is over when all agents are out, or all
+        //  contestants if there are no agents
+        let watched = match self.config.agents {
+            0 => self.num_players(),
+            agents => agents,
+        };
+        let any_watched_living = (0..watched).any(|id| {
+            self.player_snake(id)
+                .map_or(false, |snake| snake.state == State::Living)
+        });
+        let out_of_time = self.config.max_steps.map_or(false, |max| self.step >= max);
+        let done = out_of_time || !any_watched_living || self.env.snakes.is_empty();
+
+        Ok(Step {
+            observations: self.observations(),
+            rewards,
+            done,
+            info: Info {
+                step: self.step,
+                events,
+                lengths: (0..self.num_players())
+                    .map(|id| self.player_snake(id).map_or(0, |snake| snake.body.logical_len()))
+                    .collect(),
+            },
+        })
+    }
+
+    fn num_players(&self) -> usize {
+        self.config.agents + self.config.contestants.len()
+    }
+
+    fn player_snake(&self, id: PlayerId) -> Option<&Snake> {
+        self.env
+            .snakes
+            .iter()
+            .find(|snake| snake.snake_type == snake::Type::Player { id })
+    }
+
+    fn observations(&self) -> Vec<Observation> {
+        let board_dim = self.env.gtx.board_dim;
+        (0..self.config.agents)
+            .map(|id| {
+                let index = self
+                    .env
+                    .snakes
+                    .iter()
+                    .position(|snake| snake.snake_type == snake::Type::Player { id });
+                match index {
+                    Some(index) => self.config.encoding.encode(
+                        &self.env.snakes[index].body,
+                        &OtherSnakes::excluding(&self.env.snakes, index),
+                        &self.env.apples,
+                        board_dim,
+                    ),
+                    // the snake is gone, there is nothing to see
+                    None => Observation::zeros(self.config.encoding.shape(board_dim)),
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_reset_is_deterministic() {
+    let actions = [Action::Straight, Action::Turn(1), Action::Straight, Action::Turn(-1)];
+    let run = |gym: &mut Gym| {
+        let mut observations = gym.reset(7).unwrap();
+        let mut lengths = vec![];
+        for action in actions.iter().cycle().take(60) {
+            let step = gym.step(&[*action]).unwrap();
+            observations.extend(step.observations);
+            lengths.push(step.info.lengths);
+            if step.done {
+                break;
+            }
+        }
+        (observations.into_iter().map(|o| o.data).collect::<Vec<_>>(), lengths)
+    };
+
+    let mut gym = Gym::new(Config::default());
+    let first = run(&mut gym);
+    // the same gym again and a fresh one
+    assert_eq!(run(&mut gym), first);
+    assert_eq!(run(&mut Gym::new(Config::default())), first);
+}
+
+#[test]
+fn test_step_crash() {
+    let mut gym = Gym::new(Config {
+        opponents: vec![],
+        apple_count: 0,
+        agent_len: 10,
+        ..Config::default()
+    });
+    gym.reset(0).unwrap();
+
+    // turning every step goes around in a circle of 6 cells
+    for _ in 0..10 {
+        let step = gym.step(&[Action::Turn(1)]).unwrap();
+        if step.done {
+            assert_eq!(step.rewards, [-1.]);
+            assert_eq!(step.info.events[0], [Event::Crashed(Cause::Itself)]);
+            return;
+        }
+        assert_eq!(step.rewards, [0.]);
+    }
+    panic!("the snake didn't crash into itself");
+}
+
+#[test]
+fn test_step_apple() {
+    use crate::basic::Dir;
+
+    let mut gym = Gym::new(Config {
+        opponents: vec![],
+        apple_count: 0,
+        ..Config::default()
+    });
+    gym.reset(0).unwrap();
+
+    let head = gym.player_snake(0).unwrap().head().pos;
+    let board_dim = gym.env.gtx.board_dim;
+    gym.env.apples.push(apple::Apple {
+        pos: head.wrapping_translate(Dir::U, 1, board_dim),
+        apple_type: apple::Type::Food(1),
+    });
+    gym.env.sync_board();
+
+    let step = gym.step(&[Action::Straight]).unwrap();
+    assert_eq!(step.rewards, [1.]);
+    assert_eq!(step.info.events[0], [Event::Ate(1)]);
+    assert!(!step.done);
+    assert!(gym.env.apples.is_empty());
+}
@@ -0,0 +1,197 @@
+//! Ways to present the board to an agent as a tensor
+//!
+//! All encodings have the same channels, see [`channel`]
+
+use crate::apple::Apple;
+use crate::basic::{Dir, HexDim, HexPoint};
+use crate::snake::Body;
+use crate::view::snakes::Snakes;
+
+/// Indices of the channels of an [`Observation`], a cell
+/// has 1 in a channel if it contains that thing, 0 otherwise
+pub mod channel {
+    pub const OWN_BODY: usize = 0;
+    pub const OWN_HEAD: usize = 1;
+    pub const OTHER_BODY: usize = 2;
+    pub const OTHER_HEAD: usize = 3;
+    pub const APPLE: usize = 4;
+    pub const COUNT: usize = 5;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Encoding {
+    /// The whole board with shape `[channel::COUNT, height, width]`,
+    /// indexed by `[channel, v, h]`
+    Grid,
+    /// The cells around the head, turned so that the snake always
+    /// faces up, with shape `[channel::COUNT, 2 * radius + 1, 2 * radius + 1]`
+    ///
+    /// The view is indexed by axial hex coordinates `[channel, r, q]`
+    /// relative to the head (at `[_, radius, radius]`), `q` steps go
+    /// down-right and `r` steps go down, before turning
+    Egocentric { radius: usize },
+}
+
+/// A dense, row-major tensor of rank 3
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub shape: [usize; 3],
+    pub data: Vec<f32>,
+}
+
+impl Observation {
+    pub fn zeros(shape: [usize; 3]) -> Self {
+        Self {
+            shape,
+            data: vec![0.; shape.iter().product()],
+        }
+    }
+
+    fn index(&self, [a, b, c]: [usize; 3]) -> usize {
+        (a * self.shape[1] + b) * self.shape[2] + c
+    }
+
+    pub fn get(&self, index: [usize; 3]) -> f32 {
+        self.data[self.index(index)]
+    }
+
+    pub fn set(&mut self, index: [usize; 3], value: f32) {
+        let index = self.index(index);
+        self.data[index] = value;
+    }
+}
+
+impl Encoding {
+    pub fn shape(&self, board_dim: HexDim) -> [usize; 3] {
+        match self {
+            Encoding::Grid => [channel::COUNT, board_dim.v as usize, board_dim.h as usize],
+            Encoding::Egocentric { radius } => [channel::COUNT, 2 * radius + 1, 2 * radius + 1],
+        }
+    }
+
+    /// What the snake with the given body sees
+    pub fn encode(&self, body: &Body, other_snakes: &dyn Snakes, apples: &[Apple], board_dim: HexDim) -> Observation {
+        let grid = encode_grid(body, other_snakes, apples, board_dim);
+        match self {
+            Encoding::Grid => grid,
+            Encoding::Egocentric { radius } => encode_egocentric(&grid, *radius, body, board_dim),
+        }
+    }
+}
+
+fn encode_grid(body: &Body, other_snakes: &dyn Snakes, apples: &[Apple], board_dim: HexDim) -> Observation {
+    let mut grid = Observation::zeros(Encoding::Grid.shape(board_dim));
+    let mut mark = |channel, HexPoint { h, v }: HexPoint| grid.set([channel, v as usize, h as usize], 1.);
+
+    for (i, segment) in body.segments.iter().enumerate() {
+        mark(if i == 0 { channel::OWN_HEAD } else { channel::OWN_BODY }, segment.pos);
+    }
+    for snake in other_snakes.iter() {
+        for (i, segment) in snake.body.segments.iter().enumerate() {
+            mark(
+                if i == 0 {
+                    channel::OTHER_HEAD
+                } else {
+                    channel::OTHER_BODY
+                },
+                segment.pos,
+            );
+        }
+    }
+    for apple in apples {
+        mark(channel::APPLE, apple.pos);
+    }
+
+    grid
+}
+
+/// Take `steps` steps in `dir` (backwards if negative), wrapping around the board
+fn walk(pos: HexPoint, dir: Dir, steps: isize, board_dim: HexDim) -> HexPoint {
+    let dir = if steps < 0 { -dir } else { dir };
+    (0..steps.unsigned_abs()).fold(pos, |pos, _| pos.wrapping_translate(dir, 1, board_dim))
+}
+
+fn encode_egocentric(grid: &Observation, radius: usize, body: &Body, board_dim: HexDim) -> Observation {
+    let mut view = Observation::zeros(Encoding::Egocentric { radius }.shape(board_dim));
+    let head = body.segments[0].pos;
+
+    // turning the axes turns the view, so that forward is always up
+    let turn = body.dir as u8;
+    let q_dir = Dir::Dr + turn;
+    let r_dir = Dir::D + turn;
+
+    let radius = radius as isize;
+    for r in -radius..=radius {
+        for q in -radius..=radius {
+            let HexPoint { h, v } = walk(walk(head, q_dir, q, board_dim), r_dir, r, board_dim);
+            for channel in 0..channel::COUNT {
+                let value = grid.get([channel, v as usize, h as usize]);
+                view.set([channel, (r + radius) as usize, (q + radius) as usize], value);
+            }
+        }
+    }
+
+    view
+}
+
+#[test]
+fn test_grid_encoding() {
+    use crate::snake_control::pathfinder::{test_apples, test_snake};
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 10, v: 8 };
+    let p = |h, v| HexPoint { h, v };
+    let snakes = [
+        test_snake(&[p(2, 3), p(2, 4), p(2, 5)], Dir::U),
+        test_snake(&[p(7, 1), p(7, 0)], Dir::D),
+    ];
+    let apples = test_apples(&[p(9, 7)]);
+
+    let grid = Encoding::Grid.encode(&snakes[0].body, &OtherSnakes::excluding(&snakes, 0), &apples, board_dim);
+    assert_eq!(grid.shape, [channel::COUNT, 8, 10]);
+
+    let expected = [
+        (channel::OWN_HEAD, p(2, 3)),
+        (channel::OWN_BODY, p(2, 4)),
+        (channel::OWN_BODY, p(2, 5)),
+        (channel::OTHER_HEAD, p(7, 1)),
+        (channel::OTHER_BODY, p(7, 0)),
+        (channel::APPLE, p(9, 7)),
+    ];
+    for (channel, pos) in expected {
+        assert_eq!(
+            grid.get([channel, pos.v as usize, pos.h as usize]),
+            1.,
+            "{channel} at {pos:?}"
+        );
+    }
+    assert_eq!(grid.data.iter().sum::<f32>(), expected.len() as f32);
+}
+
+#[test]
+fn test_egocentric_encoding_faces_forward() {
+    use crate::snake_control::pathfinder::{test_apples, test_body};
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 12, v: 12 };
+    let radius = 2;
+    let encoding = Encoding::Egocentric { radius };
+    let head = HexPoint { h: 6, v: 6 };
+
+    for dir in [Dir::U, Dir::Dr, Dir::Dl] {
+        let body = test_body(&[head, head.wrapping_translate(-dir, 1, board_dim)], dir);
+        // one apple ahead, one ahead and to the right
+        let apples = test_apples(&[
+            head.wrapping_translate(dir, 1, board_dim),
+            head.wrapping_translate(dir + 1, 1, board_dim),
+        ]);
+
+        let view = encoding.encode(&body, &OtherSnakes::empty(), &apples, board_dim);
+        assert_eq!(view.shape, [channel::COUNT, 5, 5]);
+        assert_eq!(view.get([channel::OWN_HEAD, radius, radius]), 1., "facing {dir:?}");
+        assert_eq!(view.get([channel::OWN_BODY, radius + 1, radius]), 1., "facing {dir:?}");
+        assert_eq!(view.get([channel::APPLE, radius - 1, radius]), 1., "facing {dir:?}");
+        // up-right is a step down-right and a step up
+        assert_eq!(view.get([channel::APPLE, radius - 1, radius + 1]), 1., "facing {dir:?}");
+    }
+}
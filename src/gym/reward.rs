@@ -0,0 +1,71 @@
+use crate::basic::Food;
+use crate::snake::{Snake, State};
+
+/// Something that happened to an agent's snake during a step
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Event {
+    /// Ate an apple with this much food
+    Ate(Food),
+    /// Ate a special apple (one that spawns other snakes)
+    AteSpecial,
+    /// Ran into another snake or into itself
+    Crashed(Cause),
+    /// Fell into a black hole, e.g. after a head-to-head collision
+    Died(Cause),
+}
+
+/// What a snake ran into when it crashed or died
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Cause {
+    Itself,
+    /// The body of another snake
+    OtherBody,
+    /// Head-to-head collision with another snake
+    HeadOn,
+    /// Something other than a collision
+    Other,
+}
+
+/// Decides how much reward an agent gets for a step
+pub trait RewardShaping {
+    /// `snake` is None once the agent's snake has disappeared
+    fn reward(&mut self, events: &[Event], snake: Option<&Snake>) -> f32;
+}
+
+/// Rewards eating and punishes crashing
+#[derive(Copy, Clone, Debug)]
+pub struct AppleAndCrash {
+    pub per_food: f32,
+    pub special_apple: f32,
+    /// Applies to crashing and dying alike
+    pub crash: f32,
+    /// Given for every step the snake is still alive, can
+    /// be negative to encourage eating quickly
+    pub per_step: f32,
+}
+
+impl Default for AppleAndCrash {
+    fn default() -> Self {
+        Self {
+            per_food: 1.,
+            special_apple: 0.,
+            crash: -1.,
+            per_step: 0.,
+        }
+    }
+}
+
+impl RewardShaping for AppleAndCrash {
+    fn reward(&mut self, events: &[Event], snake: Option<&Snake>) -> f32 {
+        let alive = snake.map_or(false, |snake| snake.state == State::Living);
+        let mut reward = if alive { self.per_step } else { 0. };
+        for event in events {
+            reward += match event {
+                Event::Ate(food) => self.per_food * *food as f32,
+                Event::AteSpecial => self.special_apple,
+                Event::Crashed(_) | Event::Died(_) => self.crash,
+            };
+        }
+        reward
+    }
+}
@@ -0,0 +1,232 @@
+//! `hex_snake gym`: the environment over stdin/stdout, so that
+//! agents can be trained in any language
+//!
+//! Every request is one line of JSON and is answered with one line:
+//!
+//! ```json
+//! { "reset": 42 }
+//! { "observations": [{ "shape": [5, 20, 30], "data": [0, 0, 1, ...] }] }
+//!
+//! { "step": ["straight", "Ur", -1] }
+//! { "observations": [...], "rewards": [0.0], "done": false,
+//!   "info": { "step": 1, "events": [[{ "ate": 1 }]], "lengths": [6] } }
+//! ```
+//!
+//! Actions are `"straight"`, an absolute direction (`U`, `Ur`, `Dr`,
+//! `D`, `Dl`, `Ul`), or a turn in sixths of a full turn (positive is
+//! clockwise). Events are `{ "ate": food }`, `"ate_special"`,
+//! `{ "crashed": cause }` and `{ "died": cause }`. Invalid requests
+//! are answered with `{ "error": message }`. Run `hex_snake gym --help`
+//! for options.
+
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use serde_json::{json, Value};
+
+use super::observation::{Encoding, Observation};
+use super::reward::{AppleAndCrash, Cause, Event};
+use super::{Action, Config, Gym, Step};
+use crate::snake_control::external::parse_dir;
+use crate::tournament::parse_board;
+
+const USAGE: &str = "\
+usage: hex_snake gym [options]
+
+options:
+  --board WxH            board size (default 30x20)
+  --agents N             number of snakes controlled by the trainer (default 1)
+  --opponents N          number of weighted_bfs competitors (default 1)
+  --apples N             number of apples on the board (default 5)
+  --len N                starting length of the agents (default 5)
+  --steps N              maximum length of an episode (default 10000)
+  --egocentric R         observe the cells within R of the head instead
+                         of the whole board
+  --food-reward X        reward per unit of food eaten (default 1)
+  --crash-reward X       reward for crashing or dying (default -1)
+  --step-reward X        reward for every step alive (default 0)
+
+requests and replies are line-delimited JSON on stdin/stdout,
+see src/gym/server.rs";
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {message}\n\n{USAGE}");
+    process::exit(2)
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(format!("missing value for {option}")));
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value for {option}: {value:?}")))
+}
+
+fn parse_action(json: &Value) -> Option<Action> {
+    match json {
+        Value::String(name) if name == "straight" => Some(Action::Straight),
+        Value::String(name) => parse_dir(name).map(Action::Go),
+        Value::Number(sixths) => Some(Action::Turn(sixths.as_i64()?.rem_euclid(6) as i8)),
+        _ => None,
+    }
+}
+
+fn cause_json(cause: Cause) -> Value {
+    json!(match cause {
+        Cause::Itself => "itself",
+        Cause::OtherBody => "other_body",
+        Cause::HeadOn => "head_on",
+        Cause::Other => "other",
+    })
+}
+
+fn event_json(event: Event) -> Value {
+    match event {
+        Event::Ate(food) => json!({ "ate": food }),
+        Event::AteSpecial => json!("ate_special"),
+        Event::Crashed(cause) => json!({ "crashed": cause_json(cause) }),
+        Event::Died(cause) => json!({ "died": cause_json(cause) }),
+    }
+}
+
+fn observations_json(observations: &[Observation]) -> Value {
+    observations
+        .iter()
+        .map(|observation| json!({ "shape": observation.shape, "data": observation.data }))
+        .collect()
+}
+
+fn step_json(step: &Step) -> Value {
+    json!({
+        "observations": observations_json(&step.observations),
+        "rewards": step.rewards,
+        "done": step.done,
+        "info": {
+            "step": step.info.step,
+            "events": step
+                .info
+                .events
+                .iter()
+                .map(|events| events.iter().copied().map(event_json).collect::<Value>())
+                .collect::<Value>(),
+            "lengths": step.info.lengths,
+        },
+    })
+}
+
+/// Answers one request
+fn handle(gym: &mut Gym, request: &Value) -> Value {
+    let reply = if let Some(seed) = request.get("reset") {
+        let Some(seed) = seed.as_u64() else {
+            return json!({ "error": format!("invalid seed {seed}") });
+        };
+        gym.reset(seed)
+            .map(|observations| json!({ "observations": observations_json(&observations) }))
+    } else if let Some(actions) = request.get("step").and_then(Value::as_array) {
+        let actions: Option<Vec<_>> = actions.iter().map(parse_action).collect();
+        let Some(actions) = actions else {
+            return json!({ "error": format!("invalid actions {}", request["step"]) });
+        };
+        if actions.len() != gym.config.agents {
+            return json!({ "error": format!("expected {} actions, got {}", gym.config.agents, actions.len()) });
+        }
+        gym.step(&actions).map(|step| step_json(&step))
+    } else {
+        return json!({ "error": format!("unknown request {request}") });
+    };
+
+    reply.unwrap_or_else(|e| json!({ "error": e.to_string() }))
+}
+
+/// Answers requests from `input` until it ends or `output` is closed
+fn serve(gym: &mut Gym, input: impl BufRead, mut output: impl Write) {
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let reply = match serde_json::from_str(&line) {
+            Ok(request) => handle(gym, &request),
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        if writeln!(output, "{reply}").and_then(|()| output.flush()).is_err() {
+            break;
+        }
+    }
+}
+
+/// Entry point of `hex_snake gym`, `args` excludes the subcommand
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut config = Config::default();
+    let mut opponents = 1;
+    let mut reward = AppleAndCrash::default();
+
+    while let Some(option) = args.next() {
+        match option.as_str() {
+            "--board" => {
+                let value: String = parse(&option, args.next());
+                config.board_dim = parse_board(&value).unwrap_or_else(|| fail(format!("invalid board size {value:?}")));
+            }
+            "--agents" => config.agents = parse(&option, args.next()),
+            "--opponents" => opponents = parse(&option, args.next()),
+            "--apples" => config.apple_count = parse(&option, args.next()),
+            "--len" => config.agent_len = parse(&option, args.next()),
+            "--steps" => config.max_steps = Some(parse(&option, args.next())),
+            "--egocentric" => config.encoding = Encoding::Egocentric { radius: parse(&option, args.next()) },
+            "--food-reward" => reward.per_food = parse(&option, args.next()),
+            "--crash-reward" => reward.crash = parse(&option, args.next()),
+            "--step-reward" => reward.per_step = parse(&option, args.next()),
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            other => fail(format!("unknown option {other:?}")),
+        }
+    }
+    config.opponents = vec![Config::weighted_bfs_competitor(); opponents];
+    config.reward = Box::new(reward);
+
+    serve(&mut Gym::new(config), io::stdin().lock(), io::stdout().lock());
+}
+
+#[test]
+fn test_handle_requests() {
+    let mut gym = Gym::new(Config {
+        opponents: vec![],
+        ..Config::default()
+    });
+
+    let reply = handle(&mut gym, &json!({ "reset": 3 }));
+    assert_eq!(reply["observations"][0]["shape"], json!([5, 20, 30]));
+
+    let reply = handle(&mut gym, &json!({ "step": ["Ur"] }));
+    assert_eq!(reply["rewards"].as_array().unwrap().len(), 1);
+    assert_eq!(reply["info"]["step"], 1);
+    assert_eq!(reply["done"], false);
+
+    assert!(handle(&mut gym, &json!({ "step": ["sideways"] }))["error"].is_string());
+    assert!(handle(&mut gym, &json!({ "step": [] }))["error"].is_string());
+    assert!(handle(&mut gym, &json!({ "jump": true }))["error"].is_string());
+
+    assert_eq!(parse_action(&json!(-1)), Some(Action::Turn(5)));
+    assert_eq!(parse_action(&json!("straight")), Some(Action::Straight));
+}
+
+#[test]
+fn test_serve_with_opponents() {
+    let mut gym = Gym::new(Config::default());
+    assert_eq!(gym.config.opponents.len(), 1);
+
+    let mut requests = vec![json!({ "reset": 7 }).to_string()];
+    requests.extend((0..50).map(|i| json!({ "step": [i % 3 - 1] }).to_string()));
+    let mut output = vec![];
+    serve(&mut gym, requests.join("\n").as_bytes(), &mut output);
+
+    let replies: Vec<Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("reply is not JSON"))
+        .collect();
+    assert_eq!(replies.len(), requests.len());
+    for reply in replies {
+        assert!(reply.get("error").is_none(), "{reply}");
+    }
+}
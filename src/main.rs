@@ -3,6 +3,7 @@
 #![feature(try_blocks)]
 #![feature(never_type)]
 #![feature(exhaustive_patterns)]
+#![feature(test)]
 #![deny(unused_must_use)]
 // #![deny(unsafe_code)]
 #![feature(const_fn_floating_point_arithmetic)]
@@ -16,12 +17,17 @@ extern crate derive_more;
 #[macro_use]
 extern crate lazy_static;
 extern crate core;
+#[cfg(test)]
+extern crate test;
+
+use std::num::NonZeroUsize;
 
 use ggez::conf::{FullscreenType, NumSamples, WindowMode, WindowSetup};
 use ggez::event::run;
 use ggez::ContextBuilder;
 
-use crate::app::keyboard_control::ControlSetup;
+use crate::app::keyboard_control::{ControlSetup, PlayerInput};
+use crate::app::teams::{FriendlyFire, TeamMode};
 use crate::app::App;
 use crate::basic::Side;
 use crate::keyboard_layout::Layout;
@@ -39,22 +45,119 @@ mod view;
 mod apple;
 mod button;
 mod error;
+mod evolve;
+mod gym;
 mod rendering;
 pub mod snake_control;
+mod tournament;
 
 // TODO: upgrading to ggez 0.8 made the colors duller, fix that
 
 // TODO
 //  - untie frame_fraction from graphics
-//  - factor out lazy redrawing code (the whole mess with Some(grid_mesh)...)
 //  - smooth animation when cutting
-//  - diagnose high cpu use when paused
 
 // TODO
 //  make rain snakes ignore food
 //  make head-to-head collision with rain also ignore
 
+const USAGE: &str = "\
+usage: hex_snake [options]
+       hex_snake tournament [options]
+       hex_snake evolve [options]
+       hex_snake gym [options]
+
+options:
+  --player INPUT         add a local player, can be repeated, INPUT is
+                         keyboard-right (default), keyboard-left, gamepad,
+                         or mouse
+  --teams N              split the players into N teams
+  --friendly-fire MODE   what running into a teammate does: off (pass
+                         over, default), cut, or on (crash)";
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {message}\n\n{USAGE}");
+    std::process::exit(2)
+}
+
+/// Players and teams chosen on the command line, `args`
+/// excludes the program name
+fn game_options(mut args: impl Iterator<Item = String>) -> (Vec<PlayerInput>, TeamMode) {
+    let keyboard = |side| {
+        PlayerInput::Keyboard(ControlSetup {
+            layout: Layout::Qwerty,
+            keyboard_side: side,
+            hand: side,
+        })
+    };
+
+    let mut players = vec![];
+    let mut gamepads = 0;
+    let mut teams: Option<NonZeroUsize> = None;
+    let mut friendly_fire = FriendlyFire::Off;
+
+    while let Some(option) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(format!("missing value for {option}")))
+        };
+        match option.as_str() {
+            "--player" => {
+                let player = match value().as_str() {
+                    "keyboard-right" => keyboard(Side::Right),
+                    "keyboard-left" => keyboard(Side::Left),
+                    "gamepad" => {
+                        gamepads += 1;
+                        PlayerInput::Gamepad(gamepads - 1)
+                    }
+                    "mouse" => PlayerInput::Mouse,
+                    other => fail(format!("unknown input {other:?}")),
+                };
+                players.push(player);
+            }
+            "--teams" => {
+                let value = value();
+                let count = value
+                    .parse()
+                    .unwrap_or_else(|_| fail(format!("invalid number of teams {value:?}")));
+                teams = Some(count);
+            }
+            "--friendly-fire" => {
+                friendly_fire = match value().as_str() {
+                    "off" => FriendlyFire::Off,
+                    "cut" => FriendlyFire::Cut,
+                    "on" => FriendlyFire::On,
+                    other => fail(format!("unknown friendly fire mode {other:?}")),
+                }
+            }
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                std::process::exit(0)
+            }
+            other => fail(format!("unknown option {other:?}")),
+        }
+    }
+
+    if players.is_empty() {
+        players.push(keyboard(Side::Right));
+    }
+    let team_mode = match teams {
+        None => TeamMode::FreeForAll,
+        Some(count) => TeamMode::Teams { count, friendly_fire },
+    };
+    (players, team_mode)
+}
+
 fn main() {
+    // headless subcommands
+    match std::env::args().nth(1).as_deref() {
+        Some("tournament") => return tournament::main(std::env::args().skip(2)),
+        Some("evolve") => return evolve::main(std::env::args().skip(2)),
+        Some("gym") => return gym::server::main(std::env::args().skip(2)),
+        _ => {}
+    }
+    let (players, team_mode) = game_options(std::env::args().skip(1));
+
     let width = 2000.;
     let height = 1600.;
 
@@ -89,14 +192,7 @@ fn main() {
         .build()
         .unwrap();
 
-    let app = App::new(
-        vec![ControlSetup {
-            layout: Layout::Qwerty,
-            keyboard_side: Side::Right,
-            hand: Side::Right,
-        }],
-        &mut ctx,
-    );
+    let app = App::new(players, team_mode, &mut ctx);
 
     println!("start");
 
@@ -1,8 +1,10 @@
+use std::time::Duration;
+
 use ggez::graphics::{Color, DrawMode, Mesh, MeshBuilder};
 use ggez::Context;
 use hsl::HSL;
 
-use crate::app::fps_control::FpsContext;
+use crate::app::fps_control::{self, FpsContext};
 use crate::app::game_context::GameContext;
 use crate::app::stats::Stats;
 use crate::apple::Apple;
@@ -10,6 +12,27 @@ use crate::error::{ErrorConversion, Result};
 use crate::rendering;
 use crate::rendering::shape::{Hexagon, Shape};
 
+/// Animated apples go through all hues once per cycle, smoothly
+/// while the game is drawn every frame, otherwise in steps so that
+/// the mesh only changes a few times per cycle
+const HUE_CYCLE_MILLIS: u128 = 1000;
+const HUE_STEPS: u128 = 36;
+
+fn animated_hue(elapsed_millis: u128, smooth: bool) -> f64 {
+    if smooth {
+        return 360. * (elapsed_millis % HUE_CYCLE_MILLIS) as f64 / HUE_CYCLE_MILLIS as f64;
+    }
+    let step = elapsed_millis * HUE_STEPS / HUE_CYCLE_MILLIS % HUE_STEPS;
+    360. * step as f64 / HUE_STEPS as f64
+}
+
+/// Time until animated apples change color again
+pub fn next_hue_change(elapsed_millis: u128) -> Duration {
+    let next_step = elapsed_millis * HUE_STEPS / HUE_CYCLE_MILLIS + 1;
+    let next_millis = (next_step * HUE_CYCLE_MILLIS).div_ceil(HUE_STEPS);
+    Duration::from_millis((next_millis - elapsed_millis) as u64)
+}
+
 pub fn apple_mesh(
     apples: &[Apple],
     gtx: &GameContext,
@@ -29,8 +52,12 @@ pub fn apple_mesh(
             let color = match apple.apple_type {
                 Food(_) => gtx.palette.apple_color,
                 SpawnSnake(_) | SpawnRain => {
-                    let hue = 360. * (ftx.elapsed_millis as f64 / 1000. % 1.);
-                    let hsl = HSL { h: hue, s: 1., l: 0.3 };
+                    let smooth = ftx.game_state == fps_control::State::Playing;
+                    let hsl = HSL {
+                        h: animated_hue(ftx.elapsed_millis, smooth),
+                        s: 1.,
+                        l: 0.3,
+                    };
                     Color::from(hsl.to_rgb())
                 }
             };
@@ -55,3 +82,32 @@ pub fn apple_mesh(
     };
     res.with_trace_step("apple_mesh")
 }
+
+#[test]
+fn test_hue_changes_when_scheduled() {
+    let mut elapsed = 0;
+    let mut changes = 0;
+    while elapsed < HUE_CYCLE_MILLIS {
+        let wait = next_hue_change(elapsed).as_millis();
+        assert!(wait > 0);
+        // no change until the wakeup, a change right at it
+        assert_eq!(animated_hue(elapsed, false), animated_hue(elapsed + wait - 1, false));
+        assert_ne!(animated_hue(elapsed, false), animated_hue(elapsed + wait, false));
+        elapsed += wait;
+        changes += 1;
+    }
+    assert_eq!(changes, HUE_STEPS);
+}
+
+#[test]
+fn test_smooth_hue_leads_steps() {
+    assert_eq!(animated_hue(250, true), 90.);
+    assert_eq!(animated_hue(1250, true), 90.);
+
+    // the steps trail the smooth hue by less than a step
+    let step = 360. / HUE_STEPS as f64;
+    for millis in 0..HUE_CYCLE_MILLIS {
+        let lead = animated_hue(millis, true) - animated_hue(millis, false);
+        assert!((-1e-9..step).contains(&lead), "{lead} at {millis}ms");
+    }
+}
@@ -0,0 +1,141 @@
+//! Meshes that are only rebuilt when something they show changed
+//!
+//! Every layer declares the [`Changes`] it depends on, screens
+//! report what changed with [`Layers::invalidate`] and a layer is
+//! rebuilt the next time it is updated after one of its changes.
+
+use ggez::graphics::{Canvas, DrawParam, Mesh};
+
+use crate::app::fps_control;
+use crate::error::Result;
+
+/// A set of things that can make a mesh out of date
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, BitOr, BitOrAssign)]
+pub struct Changes(u8);
+
+impl Changes {
+    /// The board or the cells were resized
+    pub const DIM: Self = Self(1 << 0);
+    /// A preference that affects what is drawn
+    pub const PREFS: Self = Self(1 << 1);
+    /// Snakes were added, removed, or changed
+    pub const SNAKES: Self = Self(1 << 2);
+    /// Apples were added, removed, or changed
+    pub const APPLES: Self = Self(1 << 3);
+    /// A graphics frame passed while playing, snakes move
+    /// smoothly between cells
+    pub const MOTION: Self = Self(1 << 4);
+    /// A graphics frame passed while apples are animated
+    pub const ANIMATION: Self = Self(1 << 5);
+    /// The game was paused, resumed, or ended
+    pub const PLAY_STATE: Self = Self(1 << 6);
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+struct Layer<Id> {
+    id: Id,
+    depends_on: Changes,
+    dirty: bool,
+    mesh: Option<Mesh>,
+}
+
+/// The layers of a screen in the order they are drawn in
+pub struct Layers<Id> {
+    layers: Vec<Layer<Id>>,
+    /// State of the game when the layers were last updated
+    play_state: Option<fps_control::State>,
+}
+
+impl<Id: Copy + Eq> Layers<Id> {
+    /// Every layer starts out dirty
+    pub fn new(layers: impl IntoIterator<Item = (Id, Changes)>) -> Self {
+        Self {
+            layers: layers
+                .into_iter()
+                .map(|(id, depends_on)| Layer {
+                    id,
+                    depends_on,
+                    dirty: true,
+                    mesh: None,
+                })
+                .collect(),
+            play_state: None,
+        }
+    }
+
+    pub fn invalidate(&mut self, changes: Changes) {
+        for layer in &mut self.layers {
+            layer.dirty |= layer.depends_on.intersects(changes);
+        }
+    }
+
+    /// Call at the start of every graphics frame, while playing,
+    /// everything that moves is rebuilt
+    pub fn start_frame(&mut self, state: fps_control::State) {
+        if self.play_state != Some(state) {
+            self.play_state = Some(state);
+            self.invalidate(Changes::PLAY_STATE);
+        }
+        if state == fps_control::State::Playing {
+            self.invalidate(Changes::MOTION);
+        }
+    }
+
+    /// Rebuilds the layer if it is out of date, `build` returns
+    /// None when there is nothing to draw, returns whether
+    /// the layer was rebuilt
+    pub fn update(&mut self, id: Id, build: impl FnOnce() -> Result<Option<Mesh>>) -> Result<bool> {
+        let layer = self.layer_mut(id);
+        if !layer.dirty {
+            return Ok(false);
+        }
+        layer.mesh = build()?;
+        layer.dirty = false;
+        Ok(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(|layer| layer.mesh.is_none())
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, draw_param: DrawParam) {
+        for mesh in self.layers.iter().filter_map(|layer| layer.mesh.as_ref()) {
+            canvas.draw(mesh, draw_param);
+        }
+    }
+
+    fn layer_mut(&mut self, id: Id) -> &mut Layer<Id> {
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.id == id)
+            .expect("no layer with this id")
+    }
+}
+
+#[test]
+fn test_layers_rebuild_after_their_changes() {
+    let mut layers = Layers::new([(0, Changes::DIM), (1, Changes::SNAKES | Changes::MOTION)]);
+    let rebuilt = |layers: &mut Layers<i32>| -> Vec<i32> {
+        [0, 1]
+            .into_iter()
+            .filter(|&id| layers.update(id, || Ok(None)).unwrap())
+            .collect()
+    };
+
+    assert_eq!(rebuilt(&mut layers), [0, 1]);
+    assert!(rebuilt(&mut layers).is_empty());
+
+    layers.invalidate(Changes::SNAKES | Changes::APPLES);
+    assert_eq!(rebuilt(&mut layers), [1]);
+
+    // nothing moves while paused
+    layers.start_frame(fps_control::State::Paused);
+    assert!(rebuilt(&mut layers).is_empty());
+    layers.start_frame(fps_control::State::Paused);
+    layers.start_frame(fps_control::State::Playing);
+    assert_eq!(rebuilt(&mut layers), [1]);
+    assert!(layers.is_empty());
+}
@@ -1,12 +1,16 @@
-pub use apple_mesh::apple_mesh;
+pub use apple_mesh::{apple_mesh, next_hue_change};
 pub use grid_mesh::{border_mesh, grid_dot_mesh, grid_mesh};
-pub use player_path_mesh::player_path_mesh;
-pub use snake_mesh::snake_mesh;
+pub use layers::{Changes, Layers};
+pub use player_path_mesh::{assist_mesh, hint_mesh, player_path_mesh};
+pub use profiler_mesh::profiler_mesh;
+pub use snake_mesh::{snake_mesh, SnakeMeshCache};
 
 mod apple_mesh;
 mod clean_arc;
 mod grid_mesh;
+mod layers;
 mod player_path_mesh;
+mod profiler_mesh;
 pub mod segments;
 pub mod shape;
 mod snake_mesh;
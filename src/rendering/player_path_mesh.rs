@@ -3,17 +3,39 @@ use std::iter;
 
 use ggez::graphics::{Color, DrawMode, Mesh, MeshBuilder};
 use ggez::Context;
+use itertools::Itertools;
 
 use crate::app::game_context::GameContext;
 use crate::app::stats::Stats;
 use crate::apple::Apple;
-use crate::basic::{Dir, Point};
+use crate::basic::{Dir, HexPoint, Point};
 use crate::error::{ErrorConversion, Result};
 use crate::rendering::shape::ShapePoints;
 use crate::snake::eat_mechanics::Knowledge;
-use crate::snake::Snake;
+use crate::snake::{self, assist, Snake};
 use crate::view::snakes::OtherSnakes;
 
+/// An arrow head around a circle of `radius` at `dest`, pointing in `dir`
+fn arrow_points(dest: Point, radius: f32, dir: Dir) -> ShapePoints {
+    // the angle of the point of the arrow
+    const THETA: f32 = PI * 3. / 8.;
+
+    let cos = radius * (THETA / 2.).cos();
+    let sin = radius * (THETA / 2.).sin();
+
+    // pointing down
+    ShapePoints::from(vec![
+        Point {
+            x: 0.,
+            y: radius / (THETA / 2.).sin(),
+        },
+        Point { x: cos, y: sin },
+        Point { x: -cos, y: sin },
+    ])
+    .rotate_clockwise(Point::zero(), Dir::D.clockwise_angle_to(dir))
+    .translate(dest)
+}
+
 pub fn player_path_mesh(
     player_snake: &mut Snake,
     other_snakes: OtherSnakes,
@@ -48,25 +70,7 @@ pub fn player_path_mesh(
             stats.polygons += 1;
 
             if let Some(dir) = arrow {
-                // the angle of the point of the arrow
-                const THETA: f32 = PI * 3. / 8.;
-
-                let cos = radius * (THETA / 2.).cos();
-                let sin = radius * (THETA / 2.).sin();
-
-                // pointing down
-                let points = ShapePoints::from(vec![
-                    Point {
-                        x: 0.,
-                        y: radius / (THETA / 2.).sin(),
-                    },
-                    Point { x: cos, y: sin },
-                    Point { x: -cos, y: sin },
-                ])
-                .rotate_clockwise(Point::zero(), Dir::D.clockwise_angle_to(dir))
-                .translate(dest);
-
-                builder.polygon(DrawMode::fill(), &points, Color::WHITE)?;
+                builder.polygon(DrawMode::fill(), &arrow_points(dest, radius, dir), Color::WHITE)?;
                 stats.polygons += 1;
             }
 
@@ -80,3 +84,104 @@ pub fn player_path_mesh(
     let mesh = Mesh::from_data(ctx, builder.build());
     Some(Ok(mesh))
 }
+
+/// Arrows showing players which way the autopilot would go to avoid
+/// crashing, None if there is nothing to show
+pub fn assist_mesh(snakes: &[Snake], ctx: &Context, gtx: &GameContext, stats: &mut Stats) -> Option<Result<Mesh>> {
+    let mut builder = MeshBuilder::new();
+    let mut empty = true;
+
+    for snake in snakes {
+        let Some(dir) = snake.assist_suggestion else {
+            continue;
+        };
+        let dest = snake.head().pos.to_cartesian(gtx.cell_dim) + gtx.cell_dim.center();
+        let radius = gtx.cell_dim.side / 2.;
+        if let Err(e) = builder.polygon(DrawMode::fill(), &arrow_points(dest, radius, dir), Color::YELLOW) {
+            return Some(Err(e).with_trace_step("assist_mesh"));
+        }
+        stats.polygons += 1;
+        empty = false;
+    }
+
+    (!empty).then(|| Ok(Mesh::from_data(ctx, builder.build())))
+}
+
+/// The autopilot's next move at the head of every player that is in
+/// control of their snake, and the player's queued turns in red when
+/// they lead into a crash
+pub fn hint_mesh(snakes: &[Snake], ctx: &Context, gtx: &GameContext, stats: &mut Stats) -> Option<Result<Mesh>> {
+    let players = snakes.iter().positions(|snake| {
+        matches!(snake.snake_type, snake::Type::Player { .. })
+            && snake.state == snake::State::Living
+            && !snake.autopilot_control
+    });
+
+    let mut builder = MeshBuilder::new();
+    let mut empty = true;
+    let radius = gtx.cell_dim.side / 2.5;
+    let center = |pos: HexPoint| pos.to_cartesian(gtx.cell_dim) + gtx.cell_dim.center();
+
+    let res: Result<()> = try {
+        for idx in players {
+            let player_snake = &snakes[idx];
+            let other_snakes = OtherSnakes::excluding(snakes, idx);
+            let Some(autopilot) = player_snake.autopilot.as_ref() else {
+                continue;
+            };
+            let head = player_snake.head().pos;
+
+            // the autopilot decides alongside the player in every cell,
+            // its path may still start at the previous head
+            let recommended = autopilot.current_path().and_then(|path| {
+                let i = path.iter().position(|&pos| pos == head)?;
+                let next = *path.get(i + 1)?;
+                head.single_step_dir_to(next, gtx.board_dim)
+            });
+            if let Some(dir) = recommended {
+                builder.polygon(DrawMode::fill(), &arrow_points(center(head), radius, dir), Color::WHITE)?;
+                stats.polygons += 1;
+                empty = false;
+            }
+
+            let queued: Vec<Dir> = match player_snake.controller.queued_dirs() {
+                Some(queue) if !queue.is_empty() => queue.iter().copied().collect(),
+                _ => continue,
+            };
+            if queued.first() == recommended.as_ref() {
+                continue;
+            }
+
+            // the snake keeps going after the last queued turn
+            let last = queued[queued.len() - 1];
+            let moves = queued
+                .iter()
+                .copied()
+                .chain(iter::repeat(last))
+                .take(queued.len().max(assist::LOOKAHEAD));
+            let crashes = assist::crashes_along(
+                &player_snake.body,
+                player_snake.team,
+                &player_snake.eat_mechanics,
+                &other_snakes,
+                moves,
+                gtx.board_dim,
+            );
+            if crashes {
+                let mut pos = head;
+                for dir in queued {
+                    builder.polygon(DrawMode::fill(), &arrow_points(center(pos), radius, dir), Color::RED)?;
+                    stats.polygons += 1;
+                    pos = pos.wrapping_translate(dir, 1, gtx.board_dim);
+                }
+                empty = false;
+            }
+        }
+    };
+
+    match res {
+        Err(e) => Some(Err(e.with_trace_step("hint_mesh"))),
+        Ok(()) if empty => None,
+        Ok(()) => Some(Ok(Mesh::from_data(ctx, builder.build()))),
+    }
+}
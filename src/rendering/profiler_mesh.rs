@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ggez::graphics::{Color, DrawMode, Mesh, MeshBuilder, Rect};
+use ggez::Context;
+
+use crate::app::fps_control::TARGET_GRAPHICS_FPS;
+use crate::app::profiler::{self, Frame, Phase};
+use crate::basic::Point;
+use crate::error::{ErrorConversion, Result};
+
+const WIDTH: f32 = 600.;
+const BARS_HEIGHT: f32 = 120.;
+const ROW_HEIGHT: f32 = 10.;
+const MARGIN: f32 = 20.;
+
+fn phase_color(phase: Phase) -> Color {
+    use Phase::*;
+    match phase {
+        Update => Color::from_rgb(60, 160, 60),
+        Decide(_) => Color::from_rgb(230, 160, 40),
+        Pathfinding => Color::from_rgb(230, 90, 40),
+        Collisions | Spawning => Color::from_rgb(140, 210, 100),
+        Draw => Color::from_rgb(60, 100, 200),
+        GridMesh | BorderMesh | SnakeMesh | AppleMesh | DistanceGridMesh | PlayerPathMesh => {
+            Color::from_rgb(100, 170, 230)
+        }
+        Submit => Color::from_rgb(160, 80, 200),
+    }
+}
+
+fn time_in(frame: &Frame, phase: Phase) -> Duration {
+    frame
+        .events
+        .iter()
+        .filter(|event| event.phase == phase)
+        .map(|event| event.duration)
+        .sum()
+}
+
+/// Bars with the update and draw time of the recorded frames
+/// above a flame graph of the latest frame, with one row per
+/// thread and nesting depth, in the bottom-right corner of
+/// the window (screen coordinates)
+pub fn profiler_mesh(history: &VecDeque<Frame>, ctx: &Context) -> Option<Result<Mesh>> {
+    let latest = history.back()?;
+    let (width, height) = ctx.gfx.drawable_size();
+
+    let mut rows: Vec<_> = latest.events.iter().map(|event| (event.thread, event.depth)).collect();
+    rows.sort_unstable();
+    rows.dedup();
+
+    let left = width - WIDTH - MARGIN;
+    let flame_top = height - MARGIN - rows.len() as f32 * ROW_HEIGHT;
+    let bars_bottom = flame_top - MARGIN / 2.;
+    let top = bars_bottom - BARS_HEIGHT;
+
+    // the target frame time is halfway up
+    let target_secs = 1. / TARGET_GRAPHICS_FPS as f32;
+    let bar_len = |duration: Duration| (duration.as_secs_f32() / target_secs * BARS_HEIGHT / 2.).min(BARS_HEIGHT);
+    let bar_width = WIDTH / profiler::HISTORY_LEN as f32;
+
+    let mut builder = MeshBuilder::new();
+
+    let res: Result<Mesh> = try {
+        let background = Rect::new(left, top, WIDTH, height - MARGIN - top);
+        builder.rectangle(DrawMode::fill(), background, Color::new(0., 0., 0., 0.6))?;
+
+        // newest frame on the right
+        let first_x = left + WIDTH - history.len() as f32 * bar_width;
+        for (i, frame) in history.iter().enumerate() {
+            let x = first_x + i as f32 * bar_width;
+            let update = bar_len(time_in(frame, Phase::Update));
+            let draw = bar_len(time_in(frame, Phase::Draw)).min(BARS_HEIGHT - update);
+            let total = bar_len(frame.duration);
+
+            // idle time in gray behind the update and draw time
+            let stacked = [
+                (0., total, Color::new(0.4, 0.4, 0.4, 1.)),
+                (0., update, phase_color(Phase::Update)),
+                (update, draw, phase_color(Phase::Draw)),
+            ];
+            for (bottom, len, color) in stacked {
+                if len > 0. {
+                    let bar = Rect::new(x, bars_bottom - bottom - len, bar_width, len);
+                    builder.rectangle(DrawMode::fill(), bar, color)?;
+                }
+            }
+        }
+
+        let target_y = bars_bottom - BARS_HEIGHT / 2.;
+        builder.line(
+            &[Point { x: left, y: target_y }, Point { x: left + WIDTH, y: target_y }],
+            1.,
+            Color::RED,
+        )?;
+
+        let frame_secs = latest.duration.as_secs_f32().max(f32::EPSILON);
+        for event in &latest.events {
+            let row = rows.binary_search(&(event.thread, event.depth)).unwrap();
+            let x = left + event.start.as_secs_f32() / frame_secs * WIDTH;
+            let w = (event.duration.as_secs_f32() / frame_secs * WIDTH).max(1.);
+            let block = Rect::new(x, flame_top + row as f32 * ROW_HEIGHT, w, ROW_HEIGHT - 1.);
+            builder.rectangle(DrawMode::fill(), block, phase_color(event.phase))?;
+        }
+
+        Mesh::from_data(ctx, builder.build())
+    };
+    Some(res.with_trace_step("profiler_mesh"))
+}
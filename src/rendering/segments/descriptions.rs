@@ -3,7 +3,6 @@
 use crate::basic::{CellDim, Dir, Point};
 use crate::color::Color;
 use crate::rendering;
-use crate::snake::palette::SegmentStyle;
 use crate::snake::{SegmentType, ZIndex};
 
 // A full (solid) segment starts at 0. and ends at 1.
@@ -95,7 +94,6 @@ pub struct SegmentDescription {
     pub prev_fraction: Option<SegmentFraction>,
     pub draw_style: rendering::Style,
     pub segment_type: SegmentType,
-    pub segment_style: SegmentStyle,
     pub z_index: ZIndex,
     pub cell_dim: CellDim,
 }
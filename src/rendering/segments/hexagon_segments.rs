@@ -1,5 +1,6 @@
 use std::iter;
 
+use crate::color::Color;
 use crate::rendering::segments::descriptions::{Polygon, RoundHeadDescription, SegmentDescription};
 use crate::rendering::segments::point_factory::SegmentRenderer;
 use crate::rendering::shape::{Hexagon, Shape};
@@ -11,18 +12,20 @@ pub struct HexagonSegments;
 /// shouldn't be used directly outside of `render_segment` and instead implements
 /// `render_segment` directly
 impl SegmentRenderer for HexagonSegments {
-    fn render_segment(
-        description: &SegmentDescription,
+    fn render_segment<'a>(
+        description: &'a SegmentDescription,
         _: f32,
         _: RoundHeadDescription,
         _: usize,
-    ) -> Box<dyn Iterator<Item = Polygon> + '_> {
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> Box<dyn Iterator<Item = Polygon> + 'a> {
         let points = Hexagon::new(description.cell_dim)
             .translate(description.destination)
             .into();
         let poylgon = Polygon {
             points,
-            color: description.segment_style.first_color(),
+            // the color at the end closer to the head
+            color: color_at(1.),
         };
         Box::new(iter::once(poylgon))
     }
@@ -2,3 +2,4 @@ pub mod descriptions;
 mod hexagon_segments;
 pub mod point_factory;
 mod smooth_segments;
+pub mod tessellation;
@@ -1,48 +1,82 @@
-use ggez::graphics::{DrawMode, MeshBuilder};
+use std::iter;
 
+use ggez::graphics::{DrawMode, MeshBuilder, MeshData};
+use ggez::GameError;
+
+use crate::color::Color;
 use crate::error::{Error, ErrorConversion, Result};
 use crate::rendering;
 use crate::rendering::segments::descriptions::{Polygon, RoundHeadDescription, SegmentDescription};
 use crate::rendering::segments::hexagon_segments::HexagonSegments;
 use crate::rendering::segments::smooth_segments::SmoothSegments;
+use crate::rendering::segments::tessellation::Tessellation;
 
 impl SegmentDescription {
     /// Render the segment into a list of drawable subsegments
     /// each represented as a list of points and a color,
     /// `snake_len` is used to calculate how many subsegments
     /// there should be (longer snakes have lower subsegment
-    /// resolution)
-    pub fn render(&self, color_resolution: usize, turn_fraction: f32) -> Box<dyn Iterator<Item = Polygon> + '_> {
+    /// resolution), `color_at` gives the color at a fraction
+    /// of the segment
+    pub fn render<'a>(
+        &'a self,
+        color_resolution: usize,
+        turn_fraction: f32,
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> Box<dyn Iterator<Item = Polygon> + 'a> {
         // TODO: pass prefs or some fragment of it instead of random arguments
         match self.draw_style {
-            rendering::Style::Hexagon => HexagonSegments::render_segment(self, 0.0, RoundHeadDescription::Gone, 0),
+            rendering::Style::Hexagon => {
+                HexagonSegments::render_segment(self, 0.0, RoundHeadDescription::Gone, 0, color_at)
+            }
 
             rendering::Style::Smooth => {
                 let round_head = self.fraction.round_head_description(self.prev_fraction, self.cell_dim);
 
-                SmoothSegments::render_segment(self, turn_fraction, round_head, color_resolution)
+                SmoothSegments::render_segment(self, turn_fraction, round_head, color_resolution, color_at)
             }
         }
     }
 
-    /// Returns number of polygons built
-    pub fn build(self, builder: &mut MeshBuilder, color_resolution: usize) -> Result<usize> {
-        let mut polygons = 0;
-        let turn_fraction = self.turn.fraction;
-        self.render(color_resolution, turn_fraction)
+    /// The colors of the polygons returned by [`Self::render`],
+    /// without rendering them
+    pub fn polygon_colors<'a>(
+        &'a self,
+        color_resolution: usize,
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> Box<dyn Iterator<Item = Color> + 'a> {
+        match self.draw_style {
+            rendering::Style::Hexagon => Box::new(iter::once(color_at(1.))),
+            rendering::Style::Smooth => Box::new(
+                self.get_subsegments(color_resolution, color_at)
+                    .map(|subsegment| subsegment.color),
+            ),
+        }
+    }
+
+    /// Render the segment and tessellate the polygons
+    pub fn tessellate(&self, color_resolution: usize, color_at: &dyn Fn(f64) -> Color) -> Result<Tessellation> {
+        let mut builder = MeshBuilder::new();
+        let mut polygons = vec![];
+        self.render(color_resolution, self.turn.fraction, color_at)
             .try_for_each(|Polygon { points, color }| {
+                let start = builder.build().vertices.len();
+                // TODO: warn about polygons with < 3 points (and switch to log levels)
                 if points.len() >= 3 {
-                    polygons += 1;
-                    builder.polygon(DrawMode::fill(), &points, *color).map(|_| ())
-                } else {
-                    // TODO: re-enable (and switch to log levels)
-                    // eprintln!("warning: SegmentDescription::render returned a Vec with < 3 points");
-                    Ok(())
+                    builder.polygon(DrawMode::fill(), &points, *color)?;
                 }
+                polygons.push(start..builder.build().vertices.len());
+                Ok::<_, GameError>(())
             })
             .map_err(Error::from)
-            .with_trace_step("SegmentDescription::build")?;
-        Ok(polygons)
+            .with_trace_step("SegmentDescription::tessellate")?;
+
+        let MeshData { vertices, indices } = builder.build();
+        Ok(Tessellation {
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+            polygons,
+        })
     }
 }
 
@@ -77,10 +111,11 @@ pub trait SegmentRenderer {
     /// Render a segment, rotate it and reflect it to match the desired
     /// coming-from and going-to directions, and translate it to match
     /// the desired position
-    fn render_segment(
-        description: &SegmentDescription,
+    fn render_segment<'a>(
+        description: &'a SegmentDescription,
         turn_fraction: f32,
         round_head: RoundHeadDescription,
         color_resolution: usize,
-    ) -> Box<dyn Iterator<Item = Polygon> + '_>;
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> Box<dyn Iterator<Item = Polygon> + 'a>;
 }
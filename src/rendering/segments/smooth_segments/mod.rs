@@ -5,6 +5,7 @@ use itertools::Itertools;
 use lyon_geom::{Angle, Arc};
 
 use crate::basic::{CellDim, Dir, Point};
+use crate::color::Color;
 use crate::rendering::clean_arc::CleanArc;
 use crate::rendering::segments::descriptions::{
     Polygon, RoundHeadDescription, SegmentDescription, SegmentFraction, TurnDirection, TurnType,
@@ -338,18 +339,19 @@ fn render_subsegment(
 }
 
 impl SegmentRenderer for SmoothSegments {
-    fn render_segment(
-        description: &SegmentDescription,
+    fn render_segment<'a>(
+        description: &'a SegmentDescription,
         turn_fraction: f32,
         round_head: RoundHeadDescription,
         color_resolution: usize,
-    ) -> Box<dyn Iterator<Item = Polygon> + '_> {
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> Box<dyn Iterator<Item = Polygon> + 'a> {
         let mut end = description.fraction.start;
 
         // TODO: in general, this isn't pretty
         Box::new(
             description
-                .get_subsegments(color_resolution)
+                .get_subsegments(color_resolution, color_at)
                 .map(move |subsegment| {
                     let start = end;
                     end = subsegment.end;
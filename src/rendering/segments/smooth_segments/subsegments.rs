@@ -12,13 +12,16 @@ pub struct Subsegment {
 impl SegmentDescription {
     /// Split a single segment description into `n` subsegments,
     /// this is used to assign a solid color to each subsegment and thus
-    /// simulate a smooth gradient
-    pub fn get_subsegments(&self, num_subsegments: usize) -> impl Iterator<Item = Subsegment> + '_ {
+    /// simulate a smooth gradient, `color_at` is only sampled for the
+    /// subsegments that are visible
+    pub fn get_subsegments<'a>(
+        &'a self,
+        num_subsegments: usize,
+        color_at: &'a dyn Fn(f64) -> Color,
+    ) -> impl Iterator<Item = Subsegment> + 'a {
         let SegmentFraction { start, end } = self.fraction;
         let segment_size = self.fraction.end - self.fraction.start;
 
-        let get_color = self.segment_style.color_at_fraction();
-
         let start_subsegment = (num_subsegments as f32 * start) as usize;
         let end_subsegment = (num_subsegments as f32 * end).ceil() as usize;
         // the actual number of subsegments (partial segments will
@@ -34,7 +37,7 @@ impl SegmentDescription {
             .enumerate()
             .rev()
             .map(move |(subsegment_idx, subsegment)| {
-                (subsegment_idx, get_color(subsegment as f64 / num_subsegments as f64))
+                (subsegment_idx, color_at(subsegment as f64 / num_subsegments as f64))
             })
             .enumerate()
             .map(move |(i, (subsegment_idx, color))| {
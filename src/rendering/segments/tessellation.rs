@@ -0,0 +1,78 @@
+use std::ops::Range;
+
+use ggez::graphics::{MeshData, Vertex};
+
+use crate::color::Color;
+
+/// Triangles of a rendered segment, ready to be copied into a mesh
+///
+/// The vertex range of every polygon is kept so that the segment
+/// can be recolored without tessellating it again.
+#[derive(Clone, Default)]
+pub struct Tessellation {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Vertex ranges of the polygons in the order they were
+    /// rendered, empty for polygons that were skipped
+    pub polygons: Vec<Range<usize>>,
+}
+
+impl Tessellation {
+    pub fn from_mesh_data(data: MeshData) -> Self {
+        Self {
+            vertices: data.vertices.to_vec(),
+            indices: data.indices.to_vec(),
+            polygons: vec![0..data.vertices.len()],
+        }
+    }
+
+    /// Number of polygons that were actually tessellated
+    pub fn num_polygons(&self) -> usize {
+        self.polygons.iter().filter(|range| !range.is_empty()).count()
+    }
+
+    /// Paints each polygon in turn, `colors` has to be in the
+    /// order the polygons were rendered in
+    pub fn recolor(&mut self, colors: impl Iterator<Item = Color>) {
+        for (range, color) in self.polygons.iter().zip(colors) {
+            let color: [f32; 4] = (*color).into();
+            for vertex in &mut self.vertices[range.clone()] {
+                vertex.color = color;
+            }
+        }
+    }
+
+    /// Appends the triangles to those of a larger mesh
+    pub fn append_to(&self, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>) {
+        let offset = vertices.len() as u32;
+        vertices.extend_from_slice(&self.vertices);
+        indices.extend(self.indices.iter().map(|index| index + offset));
+    }
+}
+
+#[test]
+fn test_recolor_and_append() {
+    let vertex = |x| Vertex {
+        position: [x, 0.],
+        uv: [0., 0.],
+        color: [0., 0., 0., 1.],
+    };
+    let mut tessellation = Tessellation {
+        vertices: (0..6).map(|x| vertex(x as f32)).collect(),
+        indices: vec![0, 1, 2, 3, 4, 5],
+        polygons: vec![0..3, 3..3, 3..6],
+    };
+    assert_eq!(tessellation.num_polygons(), 2);
+
+    tessellation.recolor([Color::RED, Color::GREEN, Color::BLUE].into_iter());
+    let colors: Vec<_> = tessellation.vertices.iter().map(|vertex| vertex.color).collect();
+    assert_eq!(colors[..3], [<[f32; 4]>::from(*Color::RED); 3]);
+    // the skipped polygon takes its color with it
+    assert_eq!(colors[3..], [<[f32; 4]>::from(*Color::BLUE); 3]);
+
+    let mut vertices = vec![vertex(-1.)];
+    let mut indices = vec![0];
+    tessellation.append_to(&mut vertices, &mut indices);
+    assert_eq!(vertices.len(), 7);
+    assert_eq!(indices, [0, 1, 2, 3, 4, 5, 6]);
+}
@@ -1,6 +1,9 @@
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::mem;
 
-use ggez::graphics::{Color, DrawMode, Mesh, MeshBuilder};
+use ggez::graphics::{DrawMode, Mesh, MeshBuilder, MeshData};
 use ggez::Context;
 use rayon::prelude::*;
 use static_assertions::assert_impl_all;
@@ -8,9 +11,14 @@ use static_assertions::assert_impl_all;
 use crate::app::fps_control::FpsContext;
 use crate::app::game_context::GameContext;
 use crate::app::stats::Stats;
+use crate::basic::{CellDim, Dir, HexPoint};
+use crate::color::Color;
 use crate::error::{Error, ErrorConversion, Result};
-use crate::rendering::segments::descriptions::{SegmentDescription, SegmentFraction, TurnDescription};
-use crate::snake::palette::SegmentStyle;
+use crate::rendering;
+use crate::rendering::segments::descriptions::{
+    RoundHeadDescription, SegmentDescription, SegmentFraction, TurnDescription,
+};
+use crate::rendering::segments::tessellation::Tessellation;
 use crate::snake::{Body, Segment, SegmentType, Snake};
 use crate::support::partial_min_max::partial_min;
 
@@ -20,7 +28,6 @@ fn segment_description(
     body: &Body,
     prev_fraction: Option<SegmentFraction>,
     frame_fraction: f32,
-    segment_style: SegmentStyle,
     gtx: &GameContext,
 ) -> SegmentDescription {
     let coming_from = segment.coming_from;
@@ -95,12 +102,97 @@ fn segment_description(
         fraction,
         draw_style: gtx.prefs.draw_style,
         segment_type: segment.segment_type,
-        segment_style,
         z_index: segment.z_index,
         cell_dim: gtx.cell_dim,
     }
 }
 
+/// Identifies a segment that keeps its shape from one frame to
+/// the next, only its color changes
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct SegmentKey {
+    pos: HexPoint,
+    coming_from: Dir,
+    going_to: Dir,
+    color_resolution: usize,
+}
+
+impl SegmentKey {
+    /// `None` for segments that change shape every frame (the head,
+    /// the tail, turning segments, and black holes)
+    fn of(segment: &Segment, desc: &SegmentDescription, color_resolution: usize) -> Option<Self> {
+        let SegmentFraction { start, end } = desc.fraction;
+        let solid = start == 0. && end == 1. && desc.turn.fraction == 1.;
+        let round_head = match desc.draw_style {
+            rendering::Style::Hexagon => false,
+            rendering::Style::Smooth => !matches!(
+                desc.fraction.round_head_description(desc.prev_fraction, desc.cell_dim),
+                RoundHeadDescription::Gone
+            ),
+        };
+        let black_hole = matches!(desc.segment_type, SegmentType::BlackHole { .. });
+
+        (solid && !round_head && !black_hole).then_some(Self {
+            pos: segment.pos,
+            coming_from: desc.turn.coming_from,
+            going_to: desc.turn.going_to,
+            color_resolution,
+        })
+    }
+}
+
+/// Tessellated segments of a snake, kept from one frame to the next
+///
+/// Only the head, the tail, and turning segments change shape as
+/// the snake moves, the other segments are tessellated once and
+/// recolored every frame (gradients move along the snake).
+#[derive(Default)]
+pub struct SnakeMeshCache {
+    /// What the cached segments were tessellated for
+    drawn_with: Option<(CellDim, rendering::Style)>,
+    segments: HashMap<SegmentKey, Tessellation>,
+    /// Segments drawn in the current frame, the others are
+    /// dropped at the end of the frame
+    used: HashMap<SegmentKey, Tessellation>,
+}
+
+impl SnakeMeshCache {
+    fn start_frame(&mut self, gtx: &GameContext) {
+        let drawn_with = Some((gtx.cell_dim, gtx.prefs.draw_style));
+        if self.drawn_with != drawn_with {
+            self.drawn_with = drawn_with;
+            self.segments.clear();
+        }
+    }
+
+    /// Returns the segment in the colors given by `color_at` and
+    /// whether it was found in the cache
+    fn segment(
+        &mut self,
+        key: SegmentKey,
+        desc: &SegmentDescription,
+        color_at: &dyn Fn(f64) -> Color,
+    ) -> Result<(&Tessellation, bool)> {
+        let (tessellation, cached) = match self.used.entry(key) {
+            Entry::Occupied(entry) => (entry.into_mut(), true),
+            Entry::Vacant(entry) => match self.segments.remove(&key) {
+                Some(tessellation) => (entry.insert(tessellation), true),
+                None => {
+                    let tessellation = desc.tessellate(key.color_resolution, color_at)?;
+                    return Ok((entry.insert(tessellation), false));
+                }
+            },
+        };
+        tessellation.recolor(desc.polygon_colors(key.color_resolution, color_at));
+        Ok((tessellation, cached))
+    }
+
+    fn end_frame(&mut self) {
+        mem::swap(&mut self.segments, &mut self.used);
+        self.used.clear();
+    }
+}
+
 // TODO: the draw order is actually more complicated
 //  heads of non-dying snakes that are going towards
 //  the black hole need to be drawn on top of it but
@@ -117,11 +209,6 @@ pub fn snake_mesh(
 
     let frame_fraction = ftx.last_graphics_update.1;
 
-    // Desired total number of subsegments for the whole snake
-    // smaller snakes have higher resolution to show more detail
-    // (this is intended to work with rainbows)
-    const TOTAL_SUBSEGMENTS: usize = 1000;
-
     // Bounds on the number of subsegments per segment to avoid
     // very high numbers of polygons or empty segments
     const MIN_SUBSEGMENTS: usize = 1;
@@ -132,11 +219,16 @@ pub fn snake_mesh(
 
     // TODO (advanced): make resolution depend on color darkness
     //  (it's easier to discern small differences in blues than in yellows)
-    // resolution = solid color subsegments per snake segment
+    // resolution = solid color subsegments per snake segment,
+    //  as many as the palette needs to look smooth
     let color_resolutions: Vec<_> = snakes
-        .iter()
+        .iter_mut()
         .map(|snake| {
-            let resolution = (TOTAL_SUBSEGMENTS / snake.body.visible_len()).clamp(MIN_SUBSEGMENTS, MAX_SUBSEGMENTS);
+            snake.palette.prepare(&snake.body);
+            let resolution = snake
+                .palette
+                .color_stops(&snake.body)
+                .clamp(MIN_SUBSEGMENTS, MAX_SUBSEGMENTS);
 
             if resolution > stats.max_color_resolution {
                 stats.max_color_resolution = resolution;
@@ -146,8 +238,6 @@ pub fn snake_mesh(
         })
         .collect();
 
-    let mut builder = MeshBuilder::new();
-
     // The draw order priority list is:
     //  - higher z-index
     //  - black hole
@@ -160,11 +250,11 @@ pub fn snake_mesh(
 
     let mut descs: Vec<_> = snakes
         // .par_iter_mut()
-        // .zip(styles.into_par_iter())
         // .zip(color_resolutions.par_iter())
-        .iter_mut()
+        .iter()
+        .enumerate()
         .zip(color_resolutions.iter())
-        .flat_map(|(snake, resolution)| {
+        .flat_map(|((snake_idx, snake), resolution)| {
             let body = &snake.body;
             let mut prev_fraction = None;
             snake
@@ -172,13 +262,10 @@ pub fn snake_mesh(
                 .segments
                 // .par_iter()
                 // .enumerate()
-                // .zip(style.into_par_iter())
                 .iter()
                 .enumerate()
-                .zip(snake.palette.segment_styles(body, frame_fraction))
-                .map(move |((segment_idx, segment), style)| {
-                    let desc =
-                        segment_description(segment, segment_idx, body, prev_fraction, frame_fraction, style, gtx);
+                .map(move |(segment_idx, segment)| {
+                    let desc = segment_description(segment, segment_idx, body, prev_fraction, frame_fraction, gtx);
 
                     prev_fraction = Some(desc.fraction);
 
@@ -186,23 +273,26 @@ pub fn snake_mesh(
                     //     heads.lock().unwrap().push(desc.clone());
                     // }
 
-                    (desc, *resolution)
+                    let key = SegmentKey::of(segment, &desc, *resolution);
+                    (snake_idx, desc, key, *resolution)
                 })
         })
         .collect();
 
-    descs.par_sort_unstable_by(|(desc1, _), (desc2, _)| match desc1.z_index.cmp(&desc2.z_index) {
-        Ordering::Equal => {
-            if let SegmentType::BlackHole { .. } = desc1.segment_type {
-                Ordering::Greater
-            } else if let SegmentType::BlackHole { .. } = desc2.segment_type {
-                Ordering::Less
-            } else {
-                Ordering::Equal
+    descs.par_sort_unstable_by(
+        |(_, desc1, ..), (_, desc2, ..)| match desc1.z_index.cmp(&desc2.z_index) {
+            Ordering::Equal => {
+                if let SegmentType::BlackHole { .. } = desc1.segment_type {
+                    Ordering::Greater
+                } else if let SegmentType::BlackHole { .. } = desc2.segment_type {
+                    Ordering::Less
+                } else {
+                    Ordering::Equal
+                }
             }
-        }
-        ordering => ordering,
-    });
+            ordering => ordering,
+        },
+    );
 
     // for desc in heads.into_inner().unwrap() {
     //     let mut dest = desc.destination + gtx.cell_dim.center();
@@ -220,23 +310,20 @@ pub fn snake_mesh(
     //     );
     //     translate(slice::from_mut(&mut dest), delta);
     //
-    //     let color = match desc.segment_style {
-    //         SegmentStyle::Solid(c) => c,
-    //         SegmentStyle::RGBGradient { start_color: c, .. } => c,
-    //         SegmentStyle::HSLGradient { start_hue: h, lightness: l, .. } => {
-    //             HSL { h, s: 1.0, l }.to_color()
-    //         }
-    //         SegmentStyle::OkLabGradient { start_hue: h, lightness: l, .. } => {
-    //             OkLab::from_lch(l, 1.0, h).to_color()
-    //         }
-    //     };
+    //     let color = snake.palette.color_at(&snake.body, 0, 1., frame_fraction);
     //
     //     builder.circle(DrawMode::fill(), dest, gtx.cell_dim.side / 2., 0.1, *color)?;
     // }
 
+    for snake in snakes.iter_mut() {
+        snake.mesh_cache.start_frame(gtx);
+    }
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
     descs
         .into_iter()
-        .try_for_each(|(desc, resolution)| {
+        .try_for_each(|(snake_idx, desc, key, resolution)| {
             // TODO: animate black hole in
             if let SegmentType::BlackHole { .. } = desc.segment_type {
                 let destination = desc.destination + gtx.cell_dim.center();
@@ -250,13 +337,134 @@ pub fn snake_mesh(
                     gtx.cell_dim
                 };
                 stats.polygons += 1;
-                builder.circle(DrawMode::fill(), destination, real_cell_dim.side, 0.1, black_hole_color)?;
+                let mut builder = MeshBuilder::new();
+                builder.circle(
+                    DrawMode::fill(),
+                    destination,
+                    real_cell_dim.side,
+                    0.1,
+                    *black_hole_color,
+                )?;
+                Tessellation::from_mesh_data(builder.build()).append_to(&mut vertices, &mut indices);
             }
 
-            stats.polygons += desc.build(&mut builder, resolution)?;
+            let snake = &mut snakes[snake_idx];
+            let (body, palette) = (&snake.body, &snake.palette);
+            let color_at = |fraction: f64| palette.color_at(body, desc.segment_idx, fraction, frame_fraction);
+            match key {
+                Some(key) => {
+                    let (tessellation, cached) = snake.mesh_cache.segment(key, &desc, &color_at)?;
+                    if cached {
+                        stats.cached_segments += 1;
+                    }
+                    stats.polygons += tessellation.num_polygons();
+                    tessellation.append_to(&mut vertices, &mut indices);
+                }
+                None => {
+                    let tessellation = desc.tessellate(resolution, &color_at)?;
+                    stats.polygons += tessellation.num_polygons();
+                    tessellation.append_to(&mut vertices, &mut indices);
+                }
+            }
             Ok::<_, Error>(())
         })
         .with_trace_step("snake_mesh")?;
 
-    Ok(Mesh::from_data(ctx, builder.build()))
+    for snake in snakes.iter_mut() {
+        snake.mesh_cache.end_frame();
+    }
+
+    Ok(Mesh::from_data(
+        ctx,
+        MeshData {
+            vertices: &vertices,
+            indices: &indices,
+        },
+    ))
+}
+
+#[cfg(test)]
+fn test_descriptions(body: &Body, gtx: &GameContext) -> Vec<SegmentDescription> {
+    let mut prev_fraction = None;
+    let segments = body.segments.iter().enumerate();
+    segments
+        .map(|(segment_idx, segment)| {
+            let desc = segment_description(segment, segment_idx, body, prev_fraction, 0.5, gtx);
+            prev_fraction = Some(desc.fraction);
+            desc
+        })
+        .collect()
+}
+
+#[test]
+fn test_segment_keys() {
+    use crate::basic::HexDim;
+    use crate::snake_control::pathfinder::{test_body, test_gtx};
+
+    let mut gtx = test_gtx(HexDim { h: 20, v: 20 });
+    let column: Vec<_> = (5..10).map(|v| HexPoint { h: 10, v }).collect();
+
+    for style in [rendering::Style::Hexagon, rendering::Style::Smooth] {
+        gtx.prefs.draw_style = style;
+        let keyed = |body: &Body| {
+            let descs = test_descriptions(body, &gtx);
+            let segments = body.segments.iter().zip(&descs);
+            segments
+                .map(|(segment, desc)| SegmentKey::of(segment, desc, 1).is_some())
+                .collect::<Vec<_>>()
+        };
+
+        // the head appears and the tail disappears
+        let mut body = test_body(&column, Dir::U);
+        assert_eq!(keyed(&body), [false, true, true, true, false], "{style:?}");
+
+        // a growing snake leaves its tail where it is
+        body.grow = 1;
+        assert_eq!(keyed(&body), [false, true, true, true, true], "{style:?}");
+
+        body.segments[2].segment_type = SegmentType::BlackHole { just_created: false };
+        assert_eq!(keyed(&body), [false, true, false, true, true], "{style:?}");
+
+        // a segment in the middle of turning
+        let mut desc = test_descriptions(&body, &gtx).swap_remove(1);
+        assert!(SegmentKey::of(&body.segments[1], &desc, 1).is_some());
+        desc.turn.fraction = 0.5;
+        assert!(SegmentKey::of(&body.segments[1], &desc, 1).is_none());
+    }
+}
+
+#[test]
+fn test_mesh_cache_reuses_segments() {
+    use crate::basic::HexDim;
+    use crate::snake_control::pathfinder::{test_body, test_gtx};
+
+    let mut gtx = test_gtx(HexDim { h: 20, v: 20 });
+    let column: Vec<_> = (5..10).map(|v| HexPoint { h: 10, v }).collect();
+    let body = test_body(&column, Dir::U);
+    let desc = test_descriptions(&body, &gtx).swap_remove(2);
+    let key = SegmentKey::of(&body.segments[2], &desc, 2).unwrap();
+
+    // whether the segment came from the cache, None if it wasn't drawn
+    let color_at = |_: f64| Color::WHITE;
+    let frame = |cache: &mut SnakeMeshCache, gtx: &GameContext, draws: usize| {
+        cache.start_frame(gtx);
+        let cached: Vec<_> = (0..draws)
+            .map(|_| cache.segment(key, &desc, &color_at).unwrap().1)
+            .collect();
+        cache.end_frame();
+        cached
+    };
+
+    let mut cache = SnakeMeshCache::default();
+    assert_eq!(frame(&mut cache, &gtx, 2), [false, true]);
+    assert_eq!(frame(&mut cache, &gtx, 1), [true]);
+
+    // segments that aren't drawn in a frame are dropped
+    assert!(frame(&mut cache, &gtx, 0).is_empty());
+    assert_eq!(frame(&mut cache, &gtx, 1), [false]);
+
+    // and all of them are when the cells change size
+    gtx.cell_dim = CellDim::from(2.);
+    assert_eq!(frame(&mut cache, &gtx, 1), [false]);
+    assert_eq!(frame(&mut cache, &gtx, 1), [true]);
 }
@@ -0,0 +1,64 @@
+//! Lets the autopilot save a player that is about to crash, see
+//! [`crate::app::prefs::Assist`]
+
+use std::iter;
+
+use crate::basic::{Dir, HexDim};
+use crate::snake::eat_mechanics::{EatBehavior, EatMechanics, TeamRelation};
+use crate::snake::{Body, TeamId};
+use crate::snake_control::pathfinder::occupancy::Occupancy;
+use crate::view::snakes::Snakes;
+
+/// How many cells ahead the assist looks for danger
+pub const LOOKAHEAD: usize = 2;
+
+/// Whether the snake crashes within [`LOOKAHEAD`] cells if it
+/// keeps going in `dir`
+pub fn crashes_ahead(
+    body: &Body,
+    team: Option<TeamId>,
+    eat_mechanics: &EatMechanics,
+    other_snakes: &dyn Snakes,
+    dir: Dir,
+    board_dim: HexDim,
+) -> bool {
+    let dirs = iter::repeat(dir).take(LOOKAHEAD);
+    crashes_along(body, team, eat_mechanics, other_snakes, dirs, board_dim)
+}
+
+/// Whether the snake crashes while making the moves in `dirs`,
+/// other snakes are assumed to stay where they are
+pub fn crashes_along(
+    body: &Body,
+    team: Option<TeamId>,
+    eat_mechanics: &EatMechanics,
+    other_snakes: &dyn Snakes,
+    dirs: impl IntoIterator<Item = Dir>,
+    board_dim: HexDim,
+) -> bool {
+    let deadly = |behavior| matches!(behavior, EatBehavior::Crash | EatBehavior::Die);
+    // segment k of a body of length n is still there after
+    //  d frames if k + d < n, or longer while the snake grows
+    let still_there = |body: &Body, k: usize, frames: usize| k + frames < body.segments.len() + Occupancy::growth(body);
+
+    let mut pos = body.segments[0].pos;
+    dirs.into_iter().enumerate().any(|(i, dir)| {
+        let frames = i + 1;
+        pos = pos.wrapping_translate(dir, 1, board_dim);
+
+        let own = body.segments.iter().enumerate().skip(1).any(|(k, seg)| {
+            seg.pos == pos
+                && still_there(body, k, frames)
+                && deadly(eat_mechanics.eat_self(seg.segment_type.discriminant()))
+        });
+        let other = other_snakes.iter().any(|snake| {
+            let relation = TeamRelation::between(team, snake.team);
+            snake.body.segments.iter().enumerate().any(|(k, seg)| {
+                seg.pos == pos
+                    && still_there(&snake.body, k, frames)
+                    && deadly(eat_mechanics.eat_other(relation, snake.snake_type, seg.segment_type.discriminant()))
+            })
+        });
+        own || other
+    })
+}
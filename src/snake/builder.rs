@@ -17,6 +17,7 @@ impl Display for BuilderError {
 #[derive(Default, Clone, Debug)]
 pub struct Builder {
     pub snake_type: Option<Type>,
+    pub team: Option<TeamId>,
     pub eat_mechanics: Option<EatMechanics>,
 
     pub pos: Option<HexPoint>,
@@ -39,6 +40,13 @@ impl Builder {
         self
     }
 
+    #[inline(always)]
+    #[must_use]
+    pub fn team(mut self, value: TeamId) -> Self {
+        self.team = Some(value);
+        self
+    }
+
     #[inline(always)]
     #[must_use]
     pub fn eat_mechanics(mut self, value: EatMechanics) -> Self {
@@ -146,6 +154,7 @@ impl Builder {
             snake_type: self
                 .snake_type
                 .ok_or_else(|| BuilderError(Box::new(self.clone()), "missing field `snake_type`"))?,
+            team: self.team,
             eat_mechanics: *self
                 .eat_mechanics
                 .as_ref()
@@ -170,6 +179,9 @@ impl Builder {
                 controller_template.into_controller(dir)
             }),
             autopilot_control: self.autopilot_control,
+            assist_saves_used: 0,
+            assist_suggestion: None,
+            mesh_cache: SnakeMeshCache::default(),
         })
     }
 }
@@ -1,6 +1,6 @@
 use std::mem::Discriminant;
 
-use crate::snake::{self, Segment, SegmentType};
+use crate::snake::{self, Segment, SegmentType, TeamId};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum EatBehavior {
@@ -56,6 +56,21 @@ macro_rules! by_snake_type {
     }};
 }
 
+#[macro_export]
+macro_rules! by_team_relation {
+    (@ $this:ident; $variant:path => $val:expr $(, $($rest:tt)* )?) => {{
+        $this.set($variant, $val);
+        by_team_relation!(@ $this; $( $($rest)* )?);
+    }};
+    (@ $this:ident;) => {};
+    (@ $($wrong:tt)*) => { compile_error!(stringify!($($wrong)*)) };
+    ($($rest:tt)*) => {{
+        let mut this = $crate::snake::eat_mechanics::ByTeamRelation::default();
+        by_team_relation!(@ this; $($rest)*);
+        this
+    }};
+}
+
 // INVARIANT: at least one field is Some
 #[derive(Default, Debug, Copy, Clone)]
 pub struct BySegmentType {
@@ -102,6 +117,7 @@ pub struct BySnakeType {
     competitor: Option<BySegmentType>,
     killer: Option<BySegmentType>,
     rain: Option<BySegmentType>,
+    ghost: Option<BySegmentType>,
     default: Option<BySegmentType>,
 }
 
@@ -109,11 +125,16 @@ impl BySnakeType {
     pub fn get(&self, snake_type: snake::Type) -> BySegmentType {
         use snake::Type::*;
         let by_segment_type = match snake_type {
-            Player => self.player,
+            Player { .. } => self.player,
             Simulated => self.simulated,
             Competitor { .. } => self.competitor,
             Killer { .. } => self.killer,
             Rain => self.rain,
+            // unlike the other snake types, ghosts are passed over
+            //  unless specified otherwise
+            Ghost { .. } => self.ghost.or(Some(by_segment_type! {
+                _ => EatBehavior::PassOver,
+            })),
         };
         by_segment_type
             .or(self.default)
@@ -124,37 +145,107 @@ impl BySnakeType {
     pub fn get_mut(&mut self, snake_type: Option<snake::Type>) -> &mut BySegmentType {
         use snake::Type::*;
         let by_segment_type = match snake_type {
-            Some(Player) => &mut self.player,
+            Some(Player { .. }) => &mut self.player,
             Some(Simulated) => &mut self.simulated,
             Some(Competitor { .. }) => &mut self.competitor,
             Some(Killer { .. }) => &mut self.killer,
             Some(Rain) => &mut self.rain,
+            Some(Ghost { .. }) => &mut self.ghost,
             None => &mut self.default,
         };
         by_segment_type.get_or_insert_with(Default::default)
     }
 }
 
+/// How two snakes relate to each other in team play
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TeamRelation {
+    /// Both snakes are on the same team
+    Teammate,
+    /// The snakes are on different teams
+    Enemy,
+    /// At least one of the snakes isn't on any team (e.g. AI snakes)
+    Neutral,
+}
+
+impl TeamRelation {
+    pub fn between(team1: Option<TeamId>, team2: Option<TeamId>) -> Self {
+        match (team1, team2) {
+            (Some(a), Some(b)) if a == b => Self::Teammate,
+            (Some(_), Some(_)) => Self::Enemy,
+            _ => Self::Neutral,
+        }
+    }
+}
+
+// Unlike `BySnakeType`, all fields can be None, in which
+//  case the behavior is resolved by snake type
+#[derive(Default, Debug, Copy, Clone)]
+pub struct ByTeamRelation {
+    teammate: Option<BySegmentType>,
+    enemy: Option<BySegmentType>,
+    neutral: Option<BySegmentType>,
+}
+
+impl ByTeamRelation {
+    pub fn get(&self, relation: TeamRelation) -> Option<BySegmentType> {
+        match relation {
+            TeamRelation::Teammate => self.teammate,
+            TeamRelation::Enemy => self.enemy,
+            TeamRelation::Neutral => self.neutral,
+        }
+    }
+
+    pub fn set(&mut self, relation: TeamRelation, by_segment_type: BySegmentType) {
+        let value = match relation {
+            TeamRelation::Teammate => &mut self.teammate,
+            TeamRelation::Enemy => &mut self.enemy,
+            TeamRelation::Neutral => &mut self.neutral,
+        };
+        *value = Some(by_segment_type);
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct EatMechanics {
     eat_self: BySegmentType,
     eat_other: BySnakeType,
+    /// Takes precedence over `eat_other` for the relations it defines
+    eat_team: Option<ByTeamRelation>,
 }
 
 impl EatMechanics {
     pub fn new(eat_self: BySegmentType, eat_other: BySnakeType) -> Self {
         // assert that eat_self and eat_other are not empty
         eat_self.get(SegmentType::DISCR_NORMAL);
-        eat_other.get(snake::Type::Player);
-        Self { eat_self, eat_other }
+        eat_other.get(snake::Type::Player { id: 0 });
+        Self { eat_self, eat_other, eat_team: None }
+    }
+
+    #[must_use]
+    pub fn with_team_rules(mut self, eat_team: ByTeamRelation) -> Self {
+        self.eat_team = Some(eat_team);
+        self
     }
 
     pub fn eat_self(&self, segment_type: Discriminant<SegmentType>) -> EatBehavior {
         self.eat_self.get(segment_type)
     }
 
-    pub fn eat_other(&self, snake_type: snake::Type, segment_type: Discriminant<SegmentType>) -> EatBehavior {
-        self.eat_other.get(snake_type).get(segment_type)
+    pub fn eat_other(
+        &self,
+        relation: TeamRelation,
+        snake_type: snake::Type,
+        segment_type: Discriminant<SegmentType>,
+    ) -> EatBehavior {
+        // ghosts aren't on any team
+        let eat_team = self
+            .eat_team
+            .filter(|_| !matches!(snake_type, snake::Type::Ghost { .. }));
+        eat_team
+            .and_then(|eat_team| eat_team.get(relation))
+            .unwrap_or_else(|| self.eat_other.get(snake_type))
+            .get(segment_type)
     }
 }
 
@@ -176,10 +267,67 @@ impl EatMechanics {
                     _ => behavior,
                 },
             },
+            eat_team: None,
         }
     }
 }
 
+#[test]
+fn test_eat_other_by_team_relation() {
+    let player = snake::Type::Player { id: 1 };
+    let competitor = snake::Type::Competitor { life: None };
+    let normal = SegmentType::DISCR_NORMAL;
+
+    let eat_mechanics = EatMechanics::new(
+        by_segment_type! {
+            _ => EatBehavior::Crash,
+        },
+        by_snake_type! {
+            snake::Type::Rain => by_segment_type! {
+                _ => EatBehavior::PassUnder,
+            },
+            _ => by_segment_type! {
+                _ => EatBehavior::Crash,
+            },
+        },
+    );
+    // without team rules, two players can't be told apart
+    for relation in [TeamRelation::Teammate, TeamRelation::Enemy] {
+        assert_eq!(eat_mechanics.eat_other(relation, player, normal), EatBehavior::Crash);
+    }
+
+    let eat_mechanics = eat_mechanics.with_team_rules(by_team_relation! {
+        TeamRelation::Teammate => by_segment_type! {
+            _ => EatBehavior::PassOver,
+        },
+        TeamRelation::Enemy => by_segment_type! {
+            _ => EatBehavior::Cut,
+        },
+    });
+    assert_eq!(
+        eat_mechanics.eat_other(TeamRelation::Teammate, player, normal),
+        EatBehavior::PassOver
+    );
+    assert_eq!(
+        eat_mechanics.eat_other(TeamRelation::Enemy, player, normal),
+        EatBehavior::Cut
+    );
+    // neutral AI snakes are resolved by snake type, as before
+    assert_eq!(
+        eat_mechanics.eat_other(TeamRelation::Neutral, competitor, normal),
+        EatBehavior::Crash
+    );
+    assert_eq!(
+        eat_mechanics.eat_other(TeamRelation::Neutral, snake::Type::Rain, normal),
+        EatBehavior::PassUnder
+    );
+    assert_eq!(
+        TeamRelation::between(Some(0), None),
+        TeamRelation::Neutral,
+        "snakes without a team are neutral"
+    );
+}
+
 // what an ai algorithm thinks is gonna happen
 #[derive(Clone, Debug)]
 pub struct Knowledge(EatMechanics);
@@ -198,6 +346,10 @@ impl Knowledge {
         }
     }
 
+    pub fn eat_mechanics(&self) -> &EatMechanics {
+        &self.0
+    }
+
     /// Checks whether the snake can safely pass through a given segment
     /// belonging to itself
     pub fn can_pass_through_self(&self, seg: &Segment) -> bool {
@@ -7,13 +7,17 @@ pub use palette::{Palette, PaletteTemplate};
 
 use crate::app::fps_control::FpsContext;
 use crate::app::game_context::GameContext;
+use crate::app::prefs::Assist;
 use crate::apple::Apple;
-use crate::basic::{Dir, FrameStamp, Frames, HexDim, HexPoint};
+use crate::basic::{Dir, Food, FrameStamp, Frames, HexPoint};
+use crate::rendering::SnakeMeshCache;
 use crate::snake::eat_mechanics::{EatMechanics, Knowledge};
 use crate::snake_control;
+use crate::snake_control::frozen::Frozen;
 use crate::snake_control::{pathfinder, Controller};
 use crate::view::snakes::Snakes;
 
+pub mod assist;
 pub mod builder;
 pub mod eat_mechanics;
 pub mod palette;
@@ -25,13 +29,42 @@ pub enum State {
     Crashed,
 }
 
+/// Identifies a local player, players are numbered from 0
+pub type PlayerId = usize;
+/// Identifies a team, teams are numbered from 0
+pub type TeamId = usize;
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Type {
-    Player,
+    Player {
+        id: PlayerId,
+    },
     Simulated,
-    Competitor { life: Option<Frames> },
-    Killer { life: Option<Frames> },
+    Competitor {
+        life: Option<Frames>,
+    },
+    Killer {
+        life: Option<Frames>,
+    },
     Rain,
+    /// A replay of an earlier run, nothing collides with it
+    Ghost {
+        life: Option<Frames>,
+    },
+}
+
+impl Type {
+    /// Lowercase name of the variant, without its data
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Player { .. } => "player",
+            Type::Simulated => "simulated",
+            Type::Competitor { .. } => "competitor",
+            Type::Killer { .. } => "killer",
+            Type::Rain => "rain",
+            Type::Ghost { .. } => "ghost",
+        }
+    }
 }
 
 // NOTE: if variants are added, the code should be checked for
@@ -69,11 +102,13 @@ pub struct Segment {
     pub z_index: ZIndex,
 }
 
+#[derive(Clone)]
 pub struct SearchTrace {
     pub cells_searched: HashSet<HexPoint>,
     pub current_path: Vec<HexPoint>,
 }
 
+#[derive(Clone)]
 pub struct Body {
     pub segments: VecDeque<Segment>,
 
@@ -119,6 +154,8 @@ impl Body {
 
 pub struct Snake {
     pub snake_type: Type,
+    /// Snakes without a team are neutral towards all other snakes
+    pub team: Option<TeamId>,
     pub eat_mechanics: EatMechanics,
     pub speed: f32,
 
@@ -130,6 +167,12 @@ pub struct Snake {
 
     pub autopilot: Option<Box<dyn Controller + Send + Sync>>,
     pub autopilot_control: bool, // whether autopilot is in control
+    /// How many times the autopilot saved the snake this game
+    pub assist_saves_used: usize,
+    /// Way out the autopilot suggests in the current cell
+    pub assist_suggestion: Option<Dir>,
+
+    pub mesh_cache: SnakeMeshCache,
 }
 
 impl Snake {
@@ -137,48 +180,18 @@ impl Snake {
         &self.body.segments[0]
     }
 
-    // similar to reachable(..), much more efficient, only works in the plane,
-    // doesn't account for the snake itself
-    // pub fn head_neighborhood(&self, radius: usize, board_dim: HexDim) -> Vec<HexPoint> {
-    //     self.head()
-    //         .pos
-    //         .neighborhood(radius)
-    //         .into_iter()
-    //         .filter_map(|point| point.wrap_around(board_dim, self.dir().axis()))
-    //         .collect()
-    // }
-
-    // very inefficient
-    // all points theoretically reachable in 'radius' steps (assumes no cutting)
-    pub fn reachable(&self, radius: usize, board_dim: HexDim) -> Vec<HexPoint> {
-        let mut out = vec![];
-        let mut layer = vec![self.head().pos];
-
-        // excluding the point itself
-        fn immediate_neighborhood(point: HexPoint, board_dim: HexDim) -> Vec<HexPoint> {
-            // could exclude -(current dir) but that might not be worth it overall
-            Dir::iter()
-                .map(|dir| point.wrapping_translate(dir, 1, board_dim))
-                .collect()
-        }
-
-        fn snake_contains(snake: &Snake, point: HexPoint) -> bool {
-            snake.body.segments.iter().any(|segment| segment.pos == point)
-        }
-
-        for _ in 0..radius {
-            let mut new: Vec<_> = layer
-                .iter()
-                .flat_map(|point| immediate_neighborhood(*point, board_dim).into_iter())
-                .collect();
-            new.sort_unstable();
-            new.dedup();
-            new.retain(|x| !out.contains(x) && !snake_contains(self, *x));
-            out.extend_from_slice(&new);
-            layer = new;
+    /// The path the snake is following, if whatever is in
+    /// control of it follows one
+    pub fn planned_path(&self) -> Option<&pathfinder::Path> {
+        match &self.autopilot {
+            Some(autopilot) if self.autopilot_control => autopilot.current_path(),
+            _ => self.controller.current_path(),
         }
+    }
 
-        out
+    /// Whether `update_dir` would ask the controllers for a direction
+    pub fn wants_dir(&self) -> bool {
+        !self.body.dir_grace && self.state == State::Living
     }
 
     pub fn update_dir(
@@ -187,29 +200,97 @@ impl Snake {
         apples: &[Apple],
         gtx: &GameContext,
         ftx: &FpsContext,
-        ctx: &Context,
+        ctx: Option<&Context>,
     ) {
-        if self.body.dir_grace || self.state != State::Living {
+        if !self.wants_dir() {
             return;
         }
 
-        // advance controller
-        let knowledge = Knowledge::accurate(&self.eat_mechanics);
-        let controller_dir =
-            self.controller
-                .next_dir(&mut self.body, Some(&knowledge), &other_snakes, apples, gtx, ftx, ctx);
+        let decision = decide(
+            &mut *self.controller,
+            self.autopilot.as_deref_mut(),
+            &self.body,
+            &self.eat_mechanics,
+            &other_snakes,
+            apples,
+            gtx,
+            ftx,
+            ctx,
+        );
+        self.apply_decision(decision, &other_snakes, gtx, ftx);
+    }
+
+    /// Takes out the controllers to decide on a direction while
+    /// the snake stays where it is, with stand-ins that have the
+    /// path the snake is following, see [`Control`]
+    pub fn take_control(&mut self) -> Control {
+        let frozen = Frozen { path: self.planned_path().cloned() };
+        Control {
+            controller: mem::replace(&mut self.controller, Box::new(frozen)),
+            autopilot: self.autopilot.take(),
+            decision: None,
+        }
+    }
 
-        // advance autopilot
-        let autopilot_dir = self.autopilot.as_mut().map(|autopilot| {
-            autopilot.next_dir(&mut self.body, Some(&knowledge), &other_snakes, apples, gtx, ftx, ctx)
-        });
+    /// Puts back the controllers taken out by [`Self::take_control`]
+    /// and turns the snake the way they decided
+    pub fn return_control(&mut self, control: Control, other_snakes: impl Snakes, gtx: &GameContext, ftx: &FpsContext) {
+        self.controller = control.controller;
+        self.autopilot = control.autopilot;
+        if let Some(decision) = control.decision {
+            self.apply_decision(decision, &other_snakes, gtx, ftx);
+        }
+    }
+
+    fn apply_decision(&mut self, decision: Decision, other_snakes: &dyn Snakes, gtx: &GameContext, ftx: &FpsContext) {
+        let Decision {
+            controller_dir,
+            autopilot_dir,
+            food_eaten,
+        } = decision;
+
+        if let Some(food) = food_eaten {
+            self.body.segments[0].segment_type = SegmentType::Eaten {
+                original_food: food,
+                food_left: food,
+            };
+        }
 
-        let new_dir = if self.autopilot_control {
+        let mut new_dir = if self.autopilot_control {
             autopilot_dir.expect("autopilot_control == true with missing autopilot")
         } else {
             controller_dir
         };
 
+        self.assist_suggestion = None;
+        if !self.autopilot_control && gtx.prefs.assist != Assist::Off {
+            let crashes = |dir| {
+                assist::crashes_ahead(
+                    &self.body,
+                    self.team,
+                    &self.eat_mechanics,
+                    other_snakes,
+                    dir,
+                    gtx.board_dim,
+                )
+            };
+            let way_out = autopilot_dir
+                .flatten()
+                .filter(|&dir| dir != -self.body.dir && !crashes(dir));
+            if let Some(safe_dir) = way_out {
+                if crashes(new_dir.unwrap_or(self.body.dir)) {
+                    if gtx.prefs.assist == Assist::Save && self.assist_saves_used < gtx.prefs.assist_saves {
+                        self.assist_saves_used += 1;
+                        // the controller has to continue from where the autopilot left it
+                        self.controller.reset(safe_dir);
+                        new_dir = Some(safe_dir);
+                    } else {
+                        self.assist_suggestion = Some(safe_dir);
+                    }
+                }
+            }
+        }
+
         match new_dir {
             Some(dir) if dir == -self.body.dir => {
                 eprintln!(
@@ -238,7 +319,7 @@ impl Snake {
         apples: &[Apple],
         gtx: &GameContext,
         ftx: &FpsContext,
-        ctx: &Context,
+        ctx: Option<&Context>,
     ) {
         let last_idx = self.body.visible_len() - 1;
         if let SegmentType::Eaten { food_left, .. } = &mut self.body.segments[last_idx].segment_type {
@@ -304,3 +385,73 @@ impl Snake {
         }
     }
 }
+
+/// What the controllers of a snake came up with
+struct Decision {
+    controller_dir: Option<Dir>,
+    /// `None` without an autopilot
+    autopilot_dir: Option<Option<Dir>>,
+    /// See [`Controller::food_eaten`]
+    food_eaten: Option<Food>,
+}
+
+fn decide(
+    controller: &mut dyn Controller,
+    autopilot: Option<&mut (dyn Controller + Send + Sync)>,
+    body: &Body,
+    eat_mechanics: &EatMechanics,
+    other_snakes: &dyn Snakes,
+    apples: &[Apple],
+    gtx: &GameContext,
+    ftx: &FpsContext,
+    ctx: Option<&Context>,
+) -> Decision {
+    // advance controller
+    let knowledge = Knowledge::accurate(eat_mechanics);
+    let controller_dir = controller.next_dir(body, Some(&knowledge), other_snakes, apples, gtx, ftx, ctx);
+    let food_eaten = controller.food_eaten();
+
+    // advance autopilot
+    let autopilot_dir =
+        autopilot.map(|autopilot| autopilot.next_dir(body, Some(&knowledge), other_snakes, apples, gtx, ftx, ctx));
+
+    Decision {
+        controller_dir,
+        autopilot_dir,
+        food_eaten,
+    }
+}
+
+/// The controllers of a snake, taken out of it so that they can
+/// decide in parallel with the controllers of other snakes while
+/// all the snakes stay the same, see [`Snake::take_control`]
+pub struct Control {
+    controller: Box<dyn Controller + Send + Sync>,
+    autopilot: Option<Box<dyn Controller + Send + Sync>>,
+    decision: Option<Decision>,
+}
+
+impl Control {
+    /// `snake` is the snake the controllers were taken out of
+    pub fn decide(
+        &mut self,
+        snake: &Snake,
+        other_snakes: impl Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+        ftx: &FpsContext,
+        ctx: Option<&Context>,
+    ) {
+        self.decision = Some(decide(
+            &mut *self.controller,
+            self.autopilot.as_deref_mut(),
+            &snake.body,
+            &snake.eat_mechanics,
+            &other_snakes,
+            apples,
+            gtx,
+            ftx,
+            ctx,
+        ));
+    }
+}
@@ -121,6 +121,12 @@ impl PaletteTemplate {
         Self::rgb_gradient(gray!(0.72, opacity), gray!(0.25, opacity), None, persistent)
     }
 
+    /// Translucent, a ghost can be seen through and can't be hit
+    pub fn ghost() -> Self {
+        let eaten = DEFAULT_EATEN_COLOR.with_alpha(0.3);
+        Self::rgb_gradient(gray!(0.72, 0.3), gray!(0.25, 0.15), Some(eaten), true)
+    }
+
     pub fn hsl_gradient(head_hue: f64, tail_hue: f64, lightness: f64, eaten_lightness: f64, persistent: bool) -> Self {
         Self::HSLGradient {
             head_hue,
@@ -188,66 +194,22 @@ impl PaletteTemplate {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum SegmentStyle {
-    Solid(Color),
-    RGBGradient {
-        start_color: Color,
-        end_color: Color,
-    },
-    HSLGradient {
-        start_hue: f64,
-        end_hue: f64,
-        lightness: f64,
-    },
-    OkLabGradient {
-        start_hue: f64,
-        end_hue: f64,
-        lightness: f64,
-    },
-}
-
-impl SegmentStyle {
-    // TODO: deprecate or reimplement in terms of color_at_fraction
-    pub fn first_color(&self) -> Color {
-        match *self {
-            Self::Solid(color) => color,
-            Self::RGBGradient { start_color: start_rgb, .. } => start_rgb,
-            Self::HSLGradient { start_hue, lightness, .. } => HSL { h: start_hue, s: 1., l: lightness }.to_color(),
-            Self::OkLabGradient { start_hue, lightness, .. } => OkLab::from_lch(lightness, 0.5, start_hue).to_color(),
-        }
-    }
-
-    pub fn color_at_fraction(&self) -> Box<dyn Fn(f64) -> Color> {
-        match *self {
-            SegmentStyle::Solid(color) => Box::new(move |_| color),
-            SegmentStyle::RGBGradient { start_color, end_color } => {
-                Box::new(move |f| f * start_color + (1. - f) * end_color)
-            }
-            SegmentStyle::HSLGradient { start_hue, end_hue, lightness } => Box::new(move |f| {
-                HSL {
-                    h: f * start_hue + (1. - f) * end_hue,
-                    s: 1.,
-                    l: lightness,
-                }
-                .to_color()
-            }),
-            SegmentStyle::OkLabGradient { start_hue, end_hue, lightness } => {
-                Box::new(move |f| OkLab::from_lch(lightness, 0.5, f * start_hue + (1. - f) * end_hue).to_color())
-            }
-        }
-    }
-}
-
+/// Colors the body of a snake, sampled lazily wherever a color is
+/// needed rather than producing the colors of every segment
 pub trait Palette: Send + Sync {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a>;
-    // TODO: refactor as
-    //  fn color_at(&mut self, body: &SnakeBody, point: f32, frame_fraction: f32) -> Color;
-    //  this avoids unnecessary work for hex palette and is called exactly as many times as needed
+    /// Called once per frame before any colors are sampled
+    fn prepare(&mut self, _body: &Body) {}
+
+    /// How many color stops a segment needs to look smooth, one
+    /// for solid colors, more the faster a gradient changes
+    fn color_stops(&self, body: &Body) -> usize;
+
+    /// The color at `fraction` of the segment at `segment_idx`,
+    /// from the end closer to the tail (0.) to the end closer
+    /// to the head (1.), see [`SegmentFraction`]
+    ///
+    /// [`SegmentFraction`]: crate::rendering::segments::descriptions::SegmentFraction
+    fn color_at(&self, body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> Color;
 }
 
 impl From<PaletteTemplate> for Box<dyn Palette + Send + Sync> {
@@ -300,9 +262,6 @@ impl From<PaletteTemplate> for Box<dyn Palette + Send + Sync> {
 // if max_len is None, use body.len(), otherwise, update max_len
 //  to be the maximum of itself and body.len() and use that,
 //  this is used to implement persistency
-// TODO: implement variable resolution asking the palette how
-//  much it needs, persistent rainbows don't need high
-//  resolutions even in short snakes
 fn and_update_max_len(max_len: &mut Option<usize>, body_len: usize) -> usize {
     match max_len {
         Some(len) => {
@@ -343,6 +302,30 @@ fn correct_len(len: usize, body: &Body, frame_fraction: f64) -> f64 {
     }
 }
 
+/// How far a point is from the tip of the head, in segments,
+/// including the part of the snake inside a black hole
+fn distance_from_head(body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> f64 {
+    (segment_idx + body.missing_front) as f64 + frame_fraction as f64 + 1. - fraction
+}
+
+// Neighboring color stops closer than this don't show as banding
+const MAX_HUE_STEP: f64 = 1.5;
+const MAX_RGB_STEP: f64 = 2. / 255.;
+
+/// Color stops needed for a color that changes by `change_per_segment`
+/// over each segment, in steps no larger than `max_step`
+fn color_stops_for(change_per_segment: f64, max_step: f64) -> usize {
+    ((change_per_segment.abs() / max_step).ceil() as usize).max(1)
+}
+
+fn max_channel_difference(color1: Color, color2: Color) -> f64 {
+    let difference = color1 - color2;
+    [difference.r, difference.g, difference.b, difference.a]
+        .into_iter()
+        .map(f32::abs)
+        .fold(0., f32::max) as f64
+}
+
 // The palettes...
 
 pub struct Solid {
@@ -351,21 +334,18 @@ pub struct Solid {
 }
 
 impl Palette for Solid {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        _frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
+    fn color_stops(&self, _body: &Body) -> usize {
+        1
+    }
+
+    fn color_at(&self, body: &Body, segment_idx: usize, _fraction: f64, _frame_fraction: f32) -> Color {
         use SegmentType::*;
 
-        Box::new(body.segments.iter().map(|segment| {
-            let color = match segment.segment_type {
-                Normal | BlackHole { .. } => self.color,
-                Eaten { .. } => self.eaten,
-                Crashed => *DEFAULT_CRASHED_COLOR,
-            };
-            SegmentStyle::Solid(color)
-        }))
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } => self.color,
+            Eaten { .. } => self.eaten,
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
@@ -377,33 +357,31 @@ pub struct RGBGradient {
 }
 
 impl Palette for RGBGradient {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
+    fn prepare(&mut self, body: &Body) {
+        and_update_max_len(&mut self.max_len, body.logical_len());
+    }
+
+    fn color_stops(&self, body: &Body) -> usize {
+        let len = self.max_len.unwrap_or(body.logical_len());
+        color_stops_for(
+            max_channel_difference(self.head_color, self.tail_color) / len as f64,
+            MAX_RGB_STEP,
+        )
+    }
+
+    fn color_at(&self, body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> Color {
         use SegmentType::*;
 
-        let logical_len = and_update_max_len(&mut self.max_len, body.logical_len());
+        let logical_len = self.max_len.unwrap_or(body.logical_len());
         let logical_len = correct_len(logical_len, body, frame_fraction as f64);
-        Box::new(body.segments.iter().enumerate().map(move |(i, segment)| {
-            if segment.segment_type == Crashed {
-                SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR)
-            } else {
-                let r = (i + body.missing_front) as f64 + frame_fraction as f64;
-                let start_color = self.head_color + (self.tail_color - self.head_color) * r / logical_len;
-                let end_color = self.head_color + (self.tail_color - self.head_color) * (r + 1.) / logical_len;
-
-                match segment.segment_type {
-                    Normal | BlackHole { .. } => SegmentStyle::RGBGradient { start_color, end_color },
-                    Eaten { .. } => SegmentStyle::RGBGradient {
-                        start_color: invert_rgb(start_color),
-                        end_color: invert_rgb(end_color),
-                    },
-                    Crashed => SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR),
-                }
-            }
-        }))
+        let r = distance_from_head(body, segment_idx, fraction, frame_fraction);
+        let color = self.head_color + (self.tail_color - self.head_color) * r / logical_len;
+
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } => color,
+            Eaten { .. } => invert_rgb(color),
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
@@ -416,50 +394,36 @@ pub struct HSLGradient {
 }
 
 impl Palette for HSLGradient {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
+    fn prepare(&mut self, body: &Body) {
+        and_update_max_len(&mut self.max_len, body.logical_len());
+    }
+
+    fn color_stops(&self, body: &Body) -> usize {
+        let len = self.max_len.unwrap_or(body.logical_len());
+        color_stops_for((self.tail_hue - self.head_hue) / len as f64, MAX_HUE_STEP)
+    }
+
+    fn color_at(&self, body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> Color {
         use SegmentType::*;
 
-        let logical_len = and_update_max_len(&mut self.max_len, body.logical_len());
+        let logical_len = self.max_len.unwrap_or(body.logical_len());
         let logical_len = correct_len(logical_len, body, frame_fraction as f64);
-        Box::new(body.segments.iter().enumerate().map(move |(i, segment)| {
-            if segment.segment_type == Crashed {
-                SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR)
-            } else {
-                let r = (i + body.missing_front) as f64 + frame_fraction as f64;
-                let start_hue = self.head_hue + (self.tail_hue - self.head_hue) * r / logical_len;
-                let end_hue = self.head_hue + (self.tail_hue - self.head_hue) * (r + 1.) / logical_len;
-
-                match segment.segment_type {
-                    Normal | BlackHole { .. } => SegmentStyle::HSLGradient {
-                        start_hue,
-                        end_hue,
-                        lightness: self.lightness,
-                    },
-                    Eaten { .. } => {
-                        // invert lightness twice
-                        let start_hsl = HSL {
-                            h: start_hue,
-                            s: 1.,
-                            l: 1. - self.eaten_lightness,
-                        };
-                        let end_hsl = HSL {
-                            h: end_hue,
-                            s: 1.,
-                            l: 1. - self.eaten_lightness,
-                        };
-                        SegmentStyle::RGBGradient {
-                            start_color: invert_rgb(start_hsl.to_color()),
-                            end_color: invert_rgb(end_hsl.to_color()),
-                        }
-                    }
-                    Crashed => SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR),
+        let r = distance_from_head(body, segment_idx, fraction, frame_fraction);
+        let hue = self.head_hue + (self.tail_hue - self.head_hue) * r / logical_len;
+
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } => HSL { h: hue, s: 1., l: self.lightness }.to_color(),
+            // invert lightness twice
+            Eaten { .. } => invert_rgb(
+                HSL {
+                    h: hue,
+                    s: 1.,
+                    l: 1. - self.eaten_lightness,
                 }
-            }
-        }))
+                .to_color(),
+            ),
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
@@ -472,38 +436,29 @@ pub struct OkLabGradient {
 }
 
 impl Palette for OkLabGradient {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
+    fn prepare(&mut self, body: &Body) {
+        and_update_max_len(&mut self.max_len, body.logical_len());
+    }
+
+    fn color_stops(&self, body: &Body) -> usize {
+        let len = self.max_len.unwrap_or(body.logical_len());
+        color_stops_for((self.tail_hue - self.head_hue) / len as f64, MAX_HUE_STEP)
+    }
+
+    fn color_at(&self, body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> Color {
         use SegmentType::*;
 
-        let frame_fraction = frame_fraction as f64;
-        let logical_len = and_update_max_len(&mut self.max_len, body.logical_len());
-        let logical_len = correct_len(logical_len, body, frame_fraction);
-        Box::new(body.segments.iter().enumerate().map(move |(i, segment)| {
-            let r = (i + body.missing_front) as f64 + frame_fraction;
-            let start_hue = self.head_hue + (self.tail_hue - self.head_hue) * r / logical_len;
-            let end_hue = self.head_hue + (self.tail_hue - self.head_hue) * (r + 1.) / logical_len;
-            match segment.segment_type {
-                Normal | BlackHole { .. } => SegmentStyle::OkLabGradient {
-                    start_hue,
-                    end_hue,
-                    lightness: self.lightness,
-                },
-                Eaten { .. } => {
-                    // invert lightness twice
-                    let start_okl = OkLab::from_lch(1. - self.eaten_lightness, 0.5, start_hue);
-                    let end_okl = OkLab::from_lch(1. - self.eaten_lightness, 0.5, end_hue);
-                    SegmentStyle::RGBGradient {
-                        start_color: invert_rgb(start_okl.to_color()),
-                        end_color: invert_rgb(end_okl.to_color()),
-                    }
-                }
-                Crashed => SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR),
-            }
-        }))
+        let logical_len = self.max_len.unwrap_or(body.logical_len());
+        let logical_len = correct_len(logical_len, body, frame_fraction as f64);
+        let r = distance_from_head(body, segment_idx, fraction, frame_fraction);
+        let hue = self.head_hue + (self.tail_hue - self.head_hue) * r / logical_len;
+
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } => OkLab::from_lch(self.lightness, 0.5, hue).to_color(),
+            // invert lightness twice
+            Eaten { .. } => invert_rgb(OkLab::from_lch(1. - self.eaten_lightness, 0.5, hue).to_color()),
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
@@ -515,39 +470,28 @@ pub struct AlternatingFixed {
 }
 
 impl Palette for AlternatingFixed {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        _frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
-        use SegmentType::*;
-
+    fn prepare(&mut self, body: &Body) {
         let head = Some(body.segments[0].pos);
         if head != self.last_head {
             self.last_head = head;
             self.iteration = !self.iteration;
         }
-        let expected_mod = !self.iteration as usize;
-        Box::new(body.segments.iter().enumerate().map(move |(i, segment)| {
-            let color = match segment.segment_type {
-                Normal | BlackHole { .. } => {
-                    if i % 2 == expected_mod {
-                        self.color1
-                    } else {
-                        self.color2
-                    }
-                }
-                Eaten { .. } => {
-                    if i % 2 == expected_mod {
-                        *DEFAULT_EATEN_COLOR
-                    } else {
-                        self.color2
-                    }
-                }
-                Crashed => *DEFAULT_CRASHED_COLOR,
-            };
-            SegmentStyle::Solid(color)
-        }))
+    }
+
+    fn color_stops(&self, _body: &Body) -> usize {
+        1
+    }
+
+    fn color_at(&self, body: &Body, segment_idx: usize, _fraction: f64, _frame_fraction: f32) -> Color {
+        use SegmentType::*;
+
+        let first_color = segment_idx % 2 == !self.iteration as usize;
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } if first_color => self.color1,
+            Eaten { .. } if first_color => *DEFAULT_EATEN_COLOR,
+            Normal | BlackHole { .. } | Eaten { .. } => self.color2,
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
@@ -557,43 +501,66 @@ pub struct Alternating {
 }
 
 impl Palette for Alternating {
-    fn segment_styles<'a>(
-        &'a mut self,
-        body: &'a Body,
-        frame_fraction: f32,
-    ) -> Box<dyn Iterator<Item = SegmentStyle> + 'a> {
-        use SegmentType::*;
-
-        Box::new(body.segments.iter().enumerate().map(move |(i, segment)| {
-            // How far along the snake we currently are (in units of segments)
-            let r = (i + body.missing_front) as f64 + frame_fraction as f64;
-
-            match segment.segment_type {
-                Normal | BlackHole { .. } => {
-                    // Check whether there is a minimum or maximum within this segment
-                    use std::f64::consts::PI;
-                    if r % PI <= PI && r % PI + 1. >= PI {
-                        let diff = PI - r % PI;
-                        let _cutoff = r + diff;
-
-                        unimplemented!();
-                    }
-
-                    let ratio1_start = (r.cos() + 1.) / 2.;
-                    let ratio1_end = ((r + 1.).cos() + 1.) / 2.;
+    fn color_stops(&self, _body: &Body) -> usize {
+        // the ratio changes by at most 1/2 per segment
+        color_stops_for(max_channel_difference(self.color1, self.color2) / 2., MAX_RGB_STEP)
+    }
 
-                    let start_color = ratio1_start * self.color1 + (1. - ratio1_start) * self.color2;
-                    let end_color = ratio1_end * self.color1 + (1. - ratio1_end) * self.color2;
+    fn color_at(&self, body: &Body, segment_idx: usize, fraction: f64, frame_fraction: f32) -> Color {
+        use SegmentType::*;
 
-                    SegmentStyle::RGBGradient { start_color, end_color }
-                }
-                Eaten { .. } => SegmentStyle::Solid(*DEFAULT_EATEN_COLOR),
-                Crashed => SegmentStyle::Solid(*DEFAULT_CRASHED_COLOR),
+        match body.segments[segment_idx].segment_type {
+            Normal | BlackHole { .. } => {
+                // sampled at every color stop, minima and maxima
+                //  within a segment need no special treatment
+                let r = distance_from_head(body, segment_idx, fraction, frame_fraction);
+                let ratio1 = (r.cos() + 1.) / 2.;
+                ratio1 * self.color1 + (1. - ratio1) * self.color2
             }
-        }))
+            Eaten { .. } => *DEFAULT_EATEN_COLOR,
+            Crashed => *DEFAULT_CRASHED_COLOR,
+        }
     }
 }
 
+#[test]
+fn test_gradient_is_continuous_between_segments() {
+    use crate::basic::Dir;
+    use crate::snake_control::pathfinder::test_body;
+
+    let column: Vec<_> = (0..10).map(|v| HexPoint { h: 0, v }).collect();
+    let body = test_body(&column, Dir::U);
+    let palette: Box<dyn Palette + Send + Sync> =
+        PaletteTemplate::rgb_gradient(Color::WHITE, Color::BLACK, None, false).into();
+    for segment_idx in 0..9 {
+        let tail_end = palette.color_at(&body, segment_idx, 0., 0.3);
+        let next_head_end = palette.color_at(&body, segment_idx + 1, 1., 0.3);
+        assert!(max_channel_difference(tail_end, next_head_end) < 1e-6);
+    }
+}
+
+#[test]
+fn test_color_stops_follow_the_gradient() {
+    use crate::basic::Dir;
+    use crate::snake_control::pathfinder::test_body;
+
+    let column: Vec<_> = (0..100).map(|v| HexPoint { h: 0, v }).collect();
+    let (short, long) = (test_body(&column[..10], Dir::U), test_body(&column, Dir::U));
+
+    let mut rainbow: Box<dyn Palette + Send + Sync> = PaletteTemplate::rainbow(true).into();
+    rainbow.prepare(&short);
+    let short_stops = rainbow.color_stops(&short);
+    rainbow.prepare(&long);
+    let long_stops = rainbow.color_stops(&long);
+    assert!(long_stops < short_stops, "{long_stops} >= {short_stops}");
+    // a persistent gradient keeps its longest length
+    rainbow.prepare(&short);
+    assert_eq!(rainbow.color_stops(&short), long_stops);
+
+    let solid: Box<dyn Palette + Send + Sync> = PaletteTemplate::solid_white_red().into();
+    assert_eq!(solid.color_stops(&short), 1);
+}
+
 // old designs
 // #[allow(dead_code)]
 // impl SnakePalette {
@@ -0,0 +1,461 @@
+//! Treats nearby snakes as adversaries and searches a few moves ahead
+//!
+//! Minimax with alpha-beta pruning over a small copy of the board.
+//! The snake picks its move assuming the nearby snakes pick theirs
+//! together to make things as bad as possible for it, all moves
+//! happen at the same time and collisions are resolved with each
+//! snake's real eat mechanics. The search deepens one move at a
+//! time until it runs out of time for the frame.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use ggez::Context;
+use itertools::Itertools;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::{self, Apple};
+use crate::basic::{Dir, Food, HexDim, HexPoint};
+use crate::snake::eat_mechanics::{EatBehavior, EatMechanics, Knowledge, TeamRelation};
+use crate::snake::{self, Body, SegmentType, State, TeamId};
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+/// At most this many snakes are treated as adversaries
+const MAX_ADVERSARIES: usize = 2;
+/// Free cells counted around the head, more room than this
+/// doesn't make a position any better
+const MAX_ROOM: usize = 40;
+
+const DEATH: f32 = -10_000.;
+const KILL: f32 = 1_000.;
+
+#[derive(Copy, Clone)]
+struct SimSegment {
+    pos: HexPoint,
+    segment_type: SegmentType,
+}
+
+#[derive(Clone)]
+struct SimSnake {
+    snake_type: snake::Type,
+    team: Option<TeamId>,
+    eat_mechanics: EatMechanics,
+    segments: VecDeque<SimSegment>,
+    dir: Dir,
+    grow: usize,
+    alive: bool,
+}
+
+impl SimSnake {
+    fn head(&self) -> HexPoint {
+        self.segments[0].pos
+    }
+
+    fn moves(&self) -> impl Iterator<Item = Dir> + '_ {
+        // trying the current direction first makes for better pruning
+        Dir::iter_from(self.dir).filter(move |&dir| dir != -self.dir)
+    }
+}
+
+/// The part of the game that matters for the search, the snake
+/// being controlled is always the first one
+#[derive(Clone)]
+struct SimState {
+    snakes: Vec<SimSnake>,
+    /// Positions and food of the apples still on the board
+    apples: Vec<(HexPoint, Food)>,
+    /// Indices of the snakes that choose their moves, the others
+    /// keep going straight
+    adversaries: Vec<usize>,
+}
+
+impl SimState {
+    /// Everyone moves at once, `adversary_dirs` has one entry per adversary
+    fn step(&self, dir: Dir, adversary_dirs: &[Dir], board_dim: HexDim) -> Self {
+        let mut next = self.clone();
+
+        next.snakes[0].dir = dir;
+        for (&index, &dir) in next.adversaries.iter().zip(adversary_dirs) {
+            next.snakes[index].dir = dir;
+        }
+
+        // same order of events as Snake::advance
+        for snake in next.snakes.iter_mut().filter(|snake| snake.alive) {
+            let last = snake.segments.len() - 1;
+            if let SegmentType::Eaten { food_left, .. } = &mut snake.segments[last].segment_type {
+                if *food_left == 0 {
+                    snake.segments[last].segment_type = SegmentType::Normal;
+                } else {
+                    snake.grow += 1;
+                    *food_left -= 1;
+                }
+            }
+
+            let pos = snake.head().wrapping_translate(snake.dir, 1, board_dim);
+            snake.segments.push_front(SimSegment {
+                pos,
+                segment_type: SegmentType::Normal,
+            });
+            if snake.grow > 0 {
+                snake.grow -= 1;
+            } else {
+                snake.segments.pop_back();
+            }
+        }
+
+        // same rules as handle_collisions, except that snakes that
+        //  crash or die are taken off the board right away
+        let mut dead = vec![false; next.snakes.len()];
+        // (snake, cut at segment, food eaten)
+        let mut changes = vec![];
+        for (i, snake) in next.snakes.iter().enumerate().filter(|(_, snake)| snake.alive) {
+            let head = snake.head();
+            if let Some(apple) = next.apples.iter().position(|(pos, _)| *pos == head) {
+                let food = next.apples.swap_remove(apple).1;
+                changes.push((i, None, Some(food)));
+            }
+
+            let hit = next
+                .snakes
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| other.alive)
+                .find_map(|(j, other)| {
+                    let skip = usize::from(i == j);
+                    other
+                        .segments
+                        .iter()
+                        .skip(skip)
+                        .position(|seg| seg.pos == head)
+                        .map(|k| (j, k + skip))
+                });
+            let Some((j, k)) = hit else {
+                continue;
+            };
+
+            let segment_type = next.snakes[j].segments[k].segment_type.discriminant();
+            let behavior = if i == j {
+                snake.eat_mechanics.eat_self(segment_type)
+            } else {
+                let relation = TeamRelation::between(snake.team, next.snakes[j].team);
+                snake
+                    .eat_mechanics
+                    .eat_other(relation, next.snakes[j].snake_type, segment_type)
+            };
+            match behavior {
+                EatBehavior::Cut if k == 0 => {
+                    dead[i] = true;
+                    dead[j] = true;
+                }
+                EatBehavior::Cut => changes.push((j, Some(k), None)),
+                EatBehavior::Crash | EatBehavior::Die => dead[i] = true,
+                EatBehavior::PassUnder | EatBehavior::PassOver => {}
+            }
+        }
+
+        for (i, cut, food) in changes {
+            let snake = &mut next.snakes[i];
+            if let Some(food) = food {
+                snake.segments[0].segment_type = SegmentType::Eaten {
+                    original_food: food,
+                    food_left: food,
+                };
+            }
+            if let Some(k) = cut {
+                snake.segments.truncate(k.max(1));
+            }
+        }
+        for (snake, dead) in next.snakes.iter_mut().zip(dead) {
+            if dead {
+                snake.alive = false;
+                snake.segments.clear();
+            }
+        }
+
+        next
+    }
+
+    fn evaluate(&self, board_dim: HexDim) -> f32 {
+        let me = &self.snakes[0];
+        if !me.alive {
+            return DEATH;
+        }
+
+        let kills = self.adversaries.iter().filter(|&&i| !self.snakes[i].alive).count();
+        let len = me.segments.len() + me.grow;
+
+        // room to move around in
+        let occupied: HashSet<_> = self
+            .snakes
+            .iter()
+            .flat_map(|snake| snake.segments.iter().map(|seg| seg.pos))
+            .collect();
+        let mut seen = HashSet::from([me.head()]);
+        let mut queue = VecDeque::from([me.head()]);
+        while let Some(pos) = queue.pop_front() {
+            if seen.len() >= MAX_ROOM {
+                break;
+            }
+            for dir in Dir::iter() {
+                let next = pos.wrapping_translate(dir, 1, board_dim);
+                if !occupied.contains(&next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        // being trapped is almost as bad as dying
+        let room = seen.len() - 1;
+        let trapped = if room < len.min(MAX_ROOM) { DEATH / 2. } else { 0. };
+
+        let apple_distance = self
+            .apples
+            .iter()
+            .map(|(pos, _)| me.head().wrapping_manhattan_distance(*pos, board_dim, 0))
+            .min()
+            .unwrap_or(0);
+
+        KILL * kills as f32 + 10. * len as f32 + 2. * room as f32 + trapped - apple_distance as f32
+    }
+}
+
+struct Search {
+    board_dim: HexDim,
+    deadline: Instant,
+}
+
+impl Search {
+    /// None if the search ran out of time
+    fn max_value(&self, state: &SimState, depth: usize, mut alpha: f32, beta: f32) -> Option<f32> {
+        if Instant::now() >= self.deadline {
+            return None;
+        }
+        if depth == 0 || !state.snakes[0].alive {
+            return Some(state.evaluate(self.board_dim));
+        }
+
+        let mut best = f32::NEG_INFINITY;
+        for dir in state.snakes[0].moves() {
+            let value = self.min_value(state, dir, depth, alpha, beta)?;
+            best = best.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+
+    /// The adversaries' answer to the snake going in `dir`
+    fn min_value(&self, state: &SimState, dir: Dir, depth: usize, alpha: f32, mut beta: f32) -> Option<f32> {
+        let joint_moves: Vec<Vec<Dir>> = if state.adversaries.is_empty() {
+            vec![vec![]]
+        } else {
+            state
+                .adversaries
+                .iter()
+                .map(|&i| state.snakes[i].moves().collect::<Vec<_>>())
+                .multi_cartesian_product()
+                .collect()
+        };
+
+        let mut best = f32::INFINITY;
+        for adversary_dirs in joint_moves {
+            let next = state.step(dir, &adversary_dirs, self.board_dim);
+            let mut value = self.max_value(&next, depth - 1, alpha, beta)?;
+            if !next.snakes[0].alive {
+                // dying sooner is worse
+                value -= depth as f32;
+            }
+            best = best.min(value);
+            beta = beta.min(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+pub struct Adversarial {
+    /// Time the search can take every frame
+    pub time_budget: Duration,
+    /// The search stops deepening at this many moves
+    pub max_depth: usize,
+}
+
+impl Adversarial {
+    fn sim_snake(snake: &snake::Snake) -> SimSnake {
+        SimSnake {
+            snake_type: snake.snake_type,
+            team: snake.team,
+            eat_mechanics: snake.eat_mechanics,
+            segments: snake
+                .body
+                .segments
+                .iter()
+                .map(|seg| SimSegment {
+                    pos: seg.pos,
+                    segment_type: seg.segment_type,
+                })
+                .collect(),
+            dir: snake.body.dir,
+            grow: snake.body.grow,
+            alive: snake.state == State::Living,
+        }
+    }
+}
+
+impl Controller for Adversarial {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+        _ftx: &FpsContext,
+        _ctx: Option<&Context>,
+    ) -> Option<Dir> {
+        let deadline = Instant::now() + self.time_budget;
+        let board_dim = gtx.board_dim;
+        let head = body.segments[0].pos;
+
+        let me = SimSnake {
+            // the controller doesn't know what kind of snake it's
+            //  controlling, to others it looks like a competitor
+            snake_type: snake::Type::Competitor { life: None },
+            team: None,
+            eat_mechanics: knowledge.map_or(EatMechanics::always(EatBehavior::Crash), |pk| *pk.eat_mechanics()),
+            segments: body
+                .segments
+                .iter()
+                .map(|seg| SimSegment {
+                    pos: seg.pos,
+                    segment_type: seg.segment_type,
+                })
+                .collect(),
+            dir: body.dir,
+            grow: body.grow,
+            alive: true,
+        };
+        let mut snakes = vec![me];
+        snakes.extend(other_snakes.iter().map(Self::sim_snake));
+
+        // the closest living snakes that could get in the way within
+        //  the search horizon
+        let reach = 2 * self.max_depth + 2;
+        let adversaries = snakes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, snake)| snake.alive)
+            .filter(|(_, snake)| !matches!(snake.snake_type, snake::Type::Rain | snake::Type::Ghost { .. }))
+            .map(|(i, snake)| (i, snake.head().wrapping_manhattan_distance(head, board_dim, 0)))
+            .filter(|&(_, distance)| distance <= reach)
+            .sorted_by_key(|&(_, distance)| distance)
+            .take(MAX_ADVERSARIES)
+            .map(|(i, _)| i)
+            .collect();
+
+        let root = SimState {
+            snakes,
+            apples: apples
+                .iter()
+                .filter_map(|apple| match apple.apple_type {
+                    apple::Type::Food(food) => Some((apple.pos, food)),
+                    _ => None,
+                })
+                .collect(),
+            adversaries,
+        };
+        let search = Search { board_dim, deadline };
+
+        // iterative deepening, the deepest search that finished wins
+        let mut best_dir = None;
+        'deepening: for depth in 1..=self.max_depth {
+            let mut alpha = f32::NEG_INFINITY;
+            let mut depth_best = None;
+            for dir in root.snakes[0].moves() {
+                let Some(value) = search.min_value(&root, dir, depth, alpha, f32::INFINITY) else {
+                    break 'deepening;
+                };
+                if depth_best.map_or(true, |(_, best)| value > best) {
+                    depth_best = Some((dir, value));
+                }
+                alpha = alpha.max(value);
+            }
+            best_dir = depth_best.map(|(dir, _)| dir);
+        }
+
+        Some(best_dir.unwrap_or(body.dir))
+    }
+}
+
+#[cfg(test)]
+fn test_next_dir(adversarial: &mut Adversarial, body: &[HexPoint], others: Vec<snake::Snake>) -> Option<Dir> {
+    use crate::app::fps_control;
+    use crate::snake_control::pathfinder::{test_body, test_gtx, test_snake};
+    use crate::view::snakes::OtherSnakes;
+
+    let gtx = test_gtx(HexDim { h: 30, v: 20 });
+    let ftx = FpsContext {
+        game_state: fps_control::State::Playing,
+        last_graphics_update: (0, 0.),
+        game_frame_num: 0,
+        elapsed_millis: 0,
+    };
+    let body = test_body(body, Dir::U);
+    let mut snakes = others;
+    // left out of the other snakes
+    snakes.push(test_snake(&[HexPoint { h: 0, v: 0 }], Dir::U));
+    let others = OtherSnakes::excluding(&snakes, snakes.len() - 1);
+    adversarial.next_dir(&body, None, &others, &[], &gtx, &ftx, None)
+}
+
+#[test]
+fn test_stays_within_time_budget() {
+    use crate::snake_control::pathfinder::test_snake;
+
+    let head = HexPoint { h: 10, v: 10 };
+    // two adversaries close by, too many moves to search in time
+    let others = vec![
+        test_snake(&[head.translate(Dir::Ur, 3), head.translate(Dir::Ur, 4)], Dir::Dl),
+        test_snake(&[head.translate(Dir::Ul, 3), head.translate(Dir::Ul, 4)], Dir::Dr),
+    ];
+    let mut adversarial = Adversarial {
+        time_budget: Duration::from_millis(5),
+        max_depth: 100,
+    };
+
+    let start = Instant::now();
+    let dir = test_next_dir(&mut adversarial, &[head, head.translate(Dir::D, 1)], others);
+    let elapsed = start.elapsed();
+    assert!(dir.is_some());
+    // loose, only an overshoot by a whole search would be a problem
+    assert!(elapsed < Duration::from_millis(100), "took {elapsed:?}");
+}
+
+#[test]
+fn test_avoids_forced_crash() {
+    use crate::snake_control::pathfinder::test_snake;
+
+    let head = HexPoint { h: 10, v: 10 };
+    // a long snake whose head is far away walls in every way but one
+    let walls = [Dir::U, Dir::Ur, Dir::Ul, Dir::Dl].map(|dir| head.translate(dir, 1));
+    let far_away = (0..12).map(|h| HexPoint { h, v: 1 });
+    let segments: Vec<_> = [HexPoint { h: 25, v: 18 }]
+        .into_iter()
+        .chain(walls)
+        .chain(far_away)
+        .collect();
+    let others = vec![test_snake(&segments, Dir::D)];
+    let mut adversarial = Adversarial {
+        time_budget: Duration::from_millis(50),
+        max_depth: 3,
+    };
+
+    let body = [head, head.translate(Dir::D, 1), head.translate(Dir::D, 2)];
+    assert_eq!(test_next_dir(&mut adversarial, &body, others), Some(Dir::Dr));
+}
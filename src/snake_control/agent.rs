@@ -0,0 +1,117 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+use ggez::Context;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::Dir;
+use crate::gym::observation::{Encoding, Observation};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+/// What an agent wants to do in the next frame
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum Action {
+    /// Keep going in the current direction
+    #[default]
+    Straight,
+    /// Go in an absolute direction
+    Go(Dir),
+    /// Turn relative to the current direction, in sixths
+    /// of a full turn, positive is clockwise
+    Turn(i8),
+}
+
+impl Action {
+    /// The direction to go in, a 180° turn is not possible
+    /// and is treated as going straight
+    pub fn resolve(self, current: Dir) -> Dir {
+        let dir = match self {
+            Action::Straight => current,
+            Action::Go(dir) => dir,
+            Action::Turn(sixths) => current + sixths.rem_euclid(6) as u8,
+        };
+        if dir == -current {
+            current
+        } else {
+            dir
+        }
+    }
+}
+
+/// Shared between a training environment and the snake it
+/// controls, the environment writes an action before every
+/// frame and the snake reads it
+#[derive(Clone, Debug, Default)]
+pub struct ActionSlot(Arc<Mutex<Action>>);
+
+impl ActionSlot {
+    pub fn set(&self, action: Action) {
+        *self.0.lock().unwrap() = action;
+    }
+
+    fn get(&self) -> Action {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Follows the actions written into an [`ActionSlot`]
+pub struct Agent {
+    pub slot: ActionSlot,
+}
+
+impl Controller for Agent {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        _: Option<&Knowledge>,
+        _: &dyn Snakes,
+        _: &[Apple],
+        _: &GameContext,
+        _: &FpsContext,
+        _: Option<&Context>,
+    ) -> Option<Dir> {
+        Some(self.slot.get().resolve(body.dir))
+    }
+}
+
+/// A trained agent
+pub trait Policy {
+    fn act(&self, observation: &Observation) -> Action;
+}
+
+#[derive(Clone)]
+pub struct SharedPolicy(pub Arc<dyn Policy + Send + Sync>);
+
+impl Debug for SharedPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SharedPolicy")
+    }
+}
+
+/// Plays the game with a trained agent, observing
+/// the board the same way it did during training
+pub struct PolicyController {
+    pub policy: SharedPolicy,
+    pub encoding: Encoding,
+}
+
+impl Controller for PolicyController {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        _: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+        _: &FpsContext,
+        _: Option<&Context>,
+    ) -> Option<Dir> {
+        let observation = self.encoding.encode(body, other_snakes, apples, gtx.board_dim);
+        Some(self.policy.0.act(&observation).resolve(body.dir))
+    }
+}
@@ -2,6 +2,7 @@ use ggez::Context;
 
 use crate::app::fps_control::FpsContext;
 use crate::app::game_context::GameContext;
+use crate::app::profiler::{self, Phase};
 use crate::apple::Apple;
 use crate::basic::{Dir, HexPoint};
 use crate::snake::eat_mechanics::Knowledge;
@@ -30,11 +31,11 @@ impl Algorithm {
         // recalculate the path if there is no target of if the last target isn't there anymore
         let recalculate_path = match &mut self.path {
             None => {
-                println!("recalculate: no path");
+                eprintln!("recalculate: no path");
                 true
             }
             Some(path) if path.is_empty() => {
-                println!("recalculate: path is empty");
+                eprintln!("recalculate: path is empty");
                 true
             }
             Some(path) => 'arm: {
@@ -44,7 +45,7 @@ impl Algorithm {
                 } else if head == path[1] {
                     path.pop_front();
                 } else {
-                    println!("recalculate: not following path");
+                    eprintln!("recalculate: not following path");
                     break 'arm true;
                 }
 
@@ -53,7 +54,7 @@ impl Algorithm {
                 if apples.iter().any(|apple| apple.pos == target) {
                     false
                 } else {
-                    println!("recalculate: target isn't there");
+                    eprintln!("recalculate: target isn't there");
                     true
                 }
             }
@@ -61,11 +62,12 @@ impl Algorithm {
 
         if recalculate_path {
             // find the shortest path to any apple and lock in that apple as the target
+            let _span = profiler::span(Phase::Pathfinding);
             self.path = self.pathfinder.get_path(&apples, body, knowledge, other_snakes, gtx);
 
             if self.path.is_none() {
-                println!("failed to find path");
-                println!("apples: {}", apples.len());
+                eprintln!("failed to find path");
+                eprintln!("apples: {}", apples.len());
             }
         }
     }
@@ -74,13 +76,13 @@ impl Algorithm {
 impl Controller for Algorithm {
     fn next_dir(
         &mut self,
-        body: &mut Body,
+        body: &Body,
         knowledge: Option<&Knowledge>,
         other_snakes: &dyn Snakes,
         apples: &[Apple],
         gtx: &GameContext,
         _ftx: &FpsContext,
-        _ctx: &Context,
+        _ctx: Option<&Context>,
     ) -> Option<Dir> {
         self.recalculate_path(body, knowledge, other_snakes, apples, gtx);
 
@@ -92,8 +94,9 @@ impl Controller for Algorithm {
             return Some(body.dir);
         }
 
+        // paths can wrap around the edges of the board
         let dir = path[0]
-            .dir_to(path[1])
+            .single_step_dir_to(path[1], gtx.board_dim)
             .expect("failed to compute dir between path points");
         Some(dir)
     }
@@ -110,6 +113,10 @@ impl Controller for Algorithm {
         self.path.as_ref()
     }
 
+    fn current_path(&self) -> Option<&Path> {
+        self.path.as_ref()
+    }
+
     fn reset(&mut self, _dir: Dir) {
         self.path = None;
     }
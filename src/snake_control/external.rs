@@ -0,0 +1,415 @@
+//! Lets a program outside the game control a snake
+//!
+//! The bot is either started as a child process (talking over its
+//! stdin/stdout) or reached over a local TCP or Unix socket. The
+//! protocol is line-delimited JSON, every time the snake needs a
+//! direction the game sends the state of the board on one line
+//! and waits for the bot to answer on one line.
+//!
+//! Request:
+//! ```json
+//! {
+//!   "frame": 42,
+//!   "board": { "width": 30, "height": 20, "topology": "hex_wrapping" },
+//!   "you": { "dir": "U", "segments": [{ "h": 3, "v": 4, "type": "normal" }, ...] },
+//!   "snakes": [{ "type": "competitor", "team": null, "dir": "Dr", "state": "living", "segments": [...] }],
+//!   "apples": [{ "h": 10, "v": 2, "type": "food", "food": 1 }]
+//! }
+//! ```
+//!
+//! Reply:
+//! ```json
+//! { "frame": 42, "dir": "Ur" }
+//! ```
+//!
+//! Directions are `U`, `Ur`, `Dr`, `D`, `Dl`, and `Ul`, `"dir": null`
+//! keeps going straight. Cells are hexagons in columns, even columns
+//! (`h % 2 == 0`) sit half a cell higher than odd ones, `v` grows
+//! downwards, and the board wraps around at the edges (`hex_wrapping`).
+//! The head is the first segment. Replies that arrive too late, that
+//! answer an older frame, or that would turn the snake around are
+//! ignored.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use ggez::Context;
+use serde_json::{json, Value};
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::{self, Apple};
+use crate::basic::Dir;
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::{self, Body, Segment, SegmentType};
+use crate::snake_control::{pathfinder, Controller, Template};
+use crate::view::snakes::Snakes;
+
+/// Where to find the bot
+#[derive(Clone, Debug)]
+pub enum Endpoint {
+    /// Start a child process and talk to it over stdin/stdout
+    Process { program: String, args: Vec<String> },
+    /// Connect to a TCP socket, e.g. `"127.0.0.1:4000"`
+    Tcp(String),
+    /// Connect to a Unix domain socket
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// What to do when the bot doesn't answer in time or is gone
+#[derive(Clone, Debug)]
+pub enum Fallback {
+    /// Keep going in the current direction
+    KeepDirection,
+    /// Let a pathfinder steer for that frame
+    Pathfinder(pathfinder::Template),
+}
+
+#[derive(Debug)]
+enum LinkError {
+    Timeout,
+    Disconnected,
+    Io(io::Error),
+    Protocol(String),
+}
+
+/// Warnings about late replies are printed at most this often
+const WARNING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Closed when the link is dropped, which also stops the reader
+enum Connection {
+    Process(Child),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Connection to a running bot, replies are read on a separate
+/// thread so that waiting for them can time out
+struct Link {
+    writer: Box<dyn Write + Send + Sync>,
+    replies: Mutex<Receiver<String>>,
+    connection: Connection,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Link {
+    fn connect(endpoint: &Endpoint) -> io::Result<Self> {
+        let (reader, writer, connection): (Box<dyn BufRead + Send>, Box<dyn Write + Send + Sync>, _) = match endpoint {
+            Endpoint::Process { program, args } => {
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()?;
+                let stdout = child.stdout.take().expect("child stdout not captured");
+                let stdin = child.stdin.take().expect("child stdin not captured");
+                (
+                    Box::new(BufReader::new(stdout)),
+                    Box::new(stdin),
+                    Connection::Process(child),
+                )
+            }
+            Endpoint::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_nodelay(true)?;
+                let reader = BufReader::new(stream.try_clone()?);
+                (Box::new(reader), Box::new(stream.try_clone()?), Connection::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let stream = UnixStream::connect(path)?;
+                let reader = BufReader::new(stream.try_clone()?);
+                (
+                    Box::new(reader),
+                    Box::new(stream.try_clone()?),
+                    Connection::Unix(stream),
+                )
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let reader = thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            writer,
+            replies: Mutex::new(receiver),
+            connection,
+            reader: Some(reader),
+        })
+    }
+
+    /// Send one request and wait for the reply to that frame,
+    /// `Ok(None)` means the bot chose to keep going straight
+    fn exchange(&mut self, frame: usize, request: &Value, timeout: Duration) -> Result<Option<Dir>, LinkError> {
+        writeln!(self.writer, "{request}").map_err(LinkError::Io)?;
+        self.writer.flush().map_err(LinkError::Io)?;
+
+        let deadline = Instant::now() + timeout;
+        let replies = self.replies.get_mut().unwrap();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = replies.recv_timeout(remaining).map_err(|e| match e {
+                RecvTimeoutError::Timeout => LinkError::Timeout,
+                RecvTimeoutError::Disconnected => LinkError::Disconnected,
+            })?;
+
+            let reply: Value = serde_json::from_str(&line).map_err(|e| LinkError::Protocol(e.to_string()))?;
+            if reply["frame"].as_u64() != Some(frame as u64) {
+                // late reply to an earlier request
+                continue;
+            }
+
+            return match &reply["dir"] {
+                Value::Null => Ok(None),
+                Value::String(name) => parse_dir(name)
+                    .map(Some)
+                    .ok_or_else(|| LinkError::Protocol(format!("unknown direction {name:?}"))),
+                other => Err(LinkError::Protocol(format!("expected a direction, got {other}"))),
+            };
+        }
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        match &mut self.connection {
+            Connection::Process(child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Connection::Tcp(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            #[cfg(unix)]
+            Connection::Unix(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+        // the reader sees the end of the stream and stops
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Keeps a late bot from printing a warning on every frame
+#[derive(Default)]
+struct TimeoutWarnings {
+    last: Option<Instant>,
+    /// Timeouts since the last warning
+    missed: usize,
+}
+
+impl TimeoutWarnings {
+    /// Counts a timeout, returns the number of timeouts to warn
+    /// about if it's time for another warning
+    fn timed_out(&mut self, now: Instant) -> Option<usize> {
+        self.missed += 1;
+        if self.last.map_or(false, |last| now < last + WARNING_INTERVAL) {
+            return None;
+        }
+        self.last = Some(now);
+        Some(std::mem::take(&mut self.missed))
+    }
+}
+
+pub struct External {
+    endpoint: Endpoint,
+    /// None if connecting failed or the bot went away
+    link: Option<Link>,
+    timeout: Duration,
+    timeout_warnings: TimeoutWarnings,
+    /// None means keep going in the current direction
+    fallback: Option<Box<dyn Controller + Send + Sync>>,
+}
+
+impl External {
+    pub fn new(endpoint: Endpoint, timeout: Duration, fallback: Fallback, start_dir: Dir) -> Self {
+        let link = match Link::connect(&endpoint) {
+            Ok(link) => Some(link),
+            Err(e) => {
+                eprintln!("warning: failed to connect to external bot {endpoint:?}: {e}");
+                None
+            }
+        };
+
+        let fallback = match fallback {
+            Fallback::KeepDirection => None,
+            Fallback::Pathfinder(template) => Some(Template::Algorithm(template).into_controller(start_dir)),
+        };
+
+        Self {
+            endpoint,
+            link,
+            timeout,
+            timeout_warnings: TimeoutWarnings::default(),
+            fallback,
+        }
+    }
+}
+
+fn dir_name(dir: Dir) -> String {
+    format!("{dir:?}")
+}
+
+pub(crate) fn parse_dir(name: &str) -> Option<Dir> {
+    Dir::iter().find(|dir| dir_name(*dir) == name)
+}
+
+fn segments_json(segments: impl Iterator<Item = Segment>) -> Value {
+    segments
+        .map(|segment| {
+            let mut json = json!({ "h": segment.pos.h, "v": segment.pos.v });
+            match segment.segment_type {
+                SegmentType::Normal => json["type"] = json!("normal"),
+                SegmentType::Eaten { food_left, .. } => {
+                    json["type"] = json!("eaten");
+                    json["food_left"] = json!(food_left);
+                }
+                SegmentType::Crashed => json["type"] = json!("crashed"),
+                SegmentType::BlackHole { .. } => json["type"] = json!("black_hole"),
+            }
+            json
+        })
+        .collect()
+}
+
+fn snake_json(snake: &snake::Snake) -> Value {
+    let state = match snake.state {
+        snake::State::Living => "living",
+        snake::State::Dying => "dying",
+        snake::State::Crashed => "crashed",
+    };
+    json!({
+        "type": snake.snake_type.name(),
+        "team": snake.team,
+        "dir": dir_name(snake.body.dir),
+        "state": state,
+        "segments": segments_json(snake.body.segments.iter().copied()),
+    })
+}
+
+fn apple_json(apple: &Apple) -> Value {
+    let mut json = json!({ "h": apple.pos.h, "v": apple.pos.v });
+    match &apple.apple_type {
+        apple::Type::Food(food) => {
+            json["type"] = json!("food");
+            json["food"] = json!(food);
+        }
+        apple::Type::SpawnSnake(_) => json["type"] = json!("spawn_snake"),
+        apple::Type::SpawnRain => json["type"] = json!("spawn_rain"),
+    }
+    json
+}
+
+impl Controller for External {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+        ftx: &FpsContext,
+        ctx: Option<&Context>,
+    ) -> Option<Dir> {
+        if let Some(link) = &mut self.link {
+            let frame = ftx.game_frame_num;
+            let request = json!({
+                "frame": frame,
+                "board": {
+                    "width": gtx.board_dim.h,
+                    "height": gtx.board_dim.v,
+                    "topology": "hex_wrapping",
+                },
+                "you": {
+                    "dir": dir_name(body.dir),
+                    "segments": segments_json(body.segments.iter().copied()),
+                },
+                "snakes": other_snakes.iter().map(snake_json).collect::<Value>(),
+                "apples": apples.iter().map(apple_json).collect::<Value>(),
+            });
+
+            match link.exchange(frame, &request, self.timeout) {
+                Ok(Some(dir)) if dir == -body.dir => {
+                    eprintln!("warning: external bot tried to turn around on frame {frame}")
+                }
+                Ok(dir) => return Some(dir.unwrap_or(body.dir)),
+                Err(LinkError::Timeout) => {
+                    if let Some(missed) = self.timeout_warnings.timed_out(Instant::now()) {
+                        eprintln!("warning: external bot timed out on {missed} frame(s), last on frame {frame}");
+                    }
+                }
+                Err(e) => {
+                    eprintln!("warning: lost external bot {:?}: {e:?}", self.endpoint);
+                    self.link = None;
+                }
+            }
+        }
+
+        match &mut self.fallback {
+            None => Some(body.dir),
+            Some(fallback) => fallback.next_dir(body, knowledge, other_snakes, apples, gtx, ftx, ctx),
+        }
+    }
+
+    fn reset(&mut self, dir: Dir) {
+        if let Some(fallback) = &mut self.fallback {
+            fallback.reset(dir);
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn echo_bot_round_trip() {
+    // `cat` sends every request straight back, so a request
+    // shaped like a reply is answered with that reply
+    let mut link = Link::connect(&Endpoint::Process {
+        program: "cat".to_string(),
+        args: vec![],
+    })
+    .unwrap();
+    let timeout = Duration::from_secs(1);
+
+    let reply = link.exchange(3, &json!({ "frame": 3, "dir": "Ur" }), timeout);
+    assert!(matches!(reply, Ok(Some(Dir::Ur))), "{reply:?}");
+
+    let reply = link.exchange(4, &json!({ "frame": 4, "dir": null }), timeout);
+    assert!(matches!(reply, Ok(None)), "{reply:?}");
+
+    // a reply to another frame is ignored until the timeout
+    let reply = link.exchange(5, &json!({ "frame": 1, "dir": "D" }), Duration::from_millis(100));
+    assert!(matches!(reply, Err(LinkError::Timeout)), "{reply:?}");
+}
+
+#[test]
+fn test_timeout_warnings_are_rate_limited() {
+    let now = Instant::now();
+    let mut warnings = TimeoutWarnings::default();
+    assert_eq!(warnings.timed_out(now), Some(1));
+    for i in 1..10 {
+        assert_eq!(warnings.timed_out(now + WARNING_INTERVAL * i / 10), None);
+    }
+    assert_eq!(warnings.timed_out(now + WARNING_INTERVAL), Some(10));
+}
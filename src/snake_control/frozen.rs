@@ -0,0 +1,38 @@
+use ggez::Context;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::Dir;
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::snake_control::pathfinder::Path;
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+/// Stands in for a snake's controllers while they are taken out of
+/// the snake to decide on a direction, other snakes still see the
+/// path the snake was following, see [`crate::snake::Control`]
+pub struct Frozen {
+    pub path: Option<Path>,
+}
+
+impl Controller for Frozen {
+    fn next_dir(
+        &mut self,
+        _: &Body,
+        _: Option<&Knowledge>,
+        _: &dyn Snakes,
+        _: &[Apple],
+        _: &GameContext,
+        _: &FpsContext,
+        _: Option<&Context>,
+    ) -> Option<Dir> {
+        // they are never asked, the snake keeps going if they are
+        None
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.path.as_ref()
+    }
+}
@@ -0,0 +1,90 @@
+use std::f32::consts::TAU;
+
+use ggez::event::{Axis, Button};
+use ggez::Context;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::{Dir, Point};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+/// Steers with the left stick of a gamepad, the d-pad can be
+/// used to go straight up or down
+pub struct Gamepad {
+    index: usize,
+    /// Last known position of the left stick (y points up)
+    stick: Point,
+    /// Direction requested with the d-pad, takes precedence
+    /// over the stick until it's consumed
+    pressed: Option<Dir>,
+}
+
+impl Gamepad {
+    /// Stick positions closer than this to the center are ignored
+    const DEAD_ZONE: f32 = 0.5;
+
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            stick: Point::zero(),
+            pressed: None,
+        }
+    }
+}
+
+impl Controller for Gamepad {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        _: Option<&Knowledge>,
+        _: &dyn Snakes,
+        _: &[Apple],
+        _: &GameContext,
+        _: &FpsContext,
+        _: Option<&Context>,
+    ) -> Option<Dir> {
+        if let Some(dir) = self.pressed.take() {
+            if dir != -body.dir {
+                return Some(dir);
+            }
+        }
+
+        if self.stick.magnitude() < Self::DEAD_ZONE {
+            // keep going straight, ask again on the next graphics frame
+            return None;
+        }
+
+        let angle = (self.stick.y.atan2(self.stick.x) + TAU) % TAU;
+        Dir::closest_to_angle(angle).into_iter().find(|dir| *dir != -body.dir)
+    }
+
+    fn reset(&mut self, _dir: Dir) {
+        self.pressed = None;
+    }
+
+    fn gamepad_axis(&mut self, gamepad: usize, axis: Axis, value: f32) {
+        if gamepad != self.index {
+            return;
+        }
+        match axis {
+            Axis::LeftStickX => self.stick.x = value,
+            Axis::LeftStickY => self.stick.y = value,
+            _ => (),
+        }
+    }
+
+    fn gamepad_button(&mut self, gamepad: usize, button: Button) {
+        if gamepad != self.index {
+            return;
+        }
+        match button {
+            Button::DPadUp => self.pressed = Some(Dir::U),
+            Button::DPadDown => self.pressed = Some(Dir::D),
+            _ => (),
+        }
+    }
+}
@@ -51,13 +51,13 @@ impl Keyboard {
 impl Controller for Keyboard {
     fn next_dir(
         &mut self,
-        _: &mut Body,
+        _: &Body,
         _: Option<&Knowledge>,
         _: &dyn Snakes,
         _: &[Apple],
         _: &GameContext,
         ftx: &FpsContext,
-        _: &Context,
+        _: Option<&Context>,
     ) -> Option<Dir> {
         if self.deferred || ftx.last_graphics_update.1 < Self::LAST_ACTIONABLE_THRESHOLD {
             self.deferred = false;
@@ -73,6 +73,10 @@ impl Controller for Keyboard {
         None
     }
 
+    fn queued_dirs(&self) -> Option<&VecDeque<Dir>> {
+        Some(&self.control_queue)
+    }
+
     fn reset(&mut self, dir: Dir) {
         self.control_queue.clear();
         self.dir = dir;
@@ -21,13 +21,13 @@ pub struct KeyboardClock {
 impl Controller for KeyboardClock {
     fn next_dir(
         &mut self,
-        _: &mut Body,
+        _: &Body,
         _: Option<&Knowledge>,
         _: &dyn Snakes,
         _: &[Apple],
         _: &GameContext,
         _: &FpsContext,
-        _: &Context,
+        _: Option<&Context>,
     ) -> Option<Dir> {
         if let Some(new_dir) = self.next_dir.take() {
             self.dir = new_dir;
@@ -1,5 +1,7 @@
+use std::collections::{HashMap, VecDeque};
 use std::f32::consts::TAU;
 
+use enum_rotate::EnumRotate;
 use ggez::Context;
 
 use crate::app::fps_control::FpsContext;
@@ -7,13 +9,62 @@ use crate::app::game_context::GameContext;
 use crate::apple::Apple;
 use crate::basic::{angle_distance, CellDim, Dir, HexDim, HexPoint};
 use crate::snake::eat_mechanics::Knowledge;
-use crate::snake::{self, Body, Segment};
+use crate::snake::{self, Body, Snake};
+use crate::snake_control::pathfinder::occupancy::Occupancy;
+use crate::snake_control::pathfinder::Path;
 use crate::snake_control::Controller;
 use crate::support::partial_min_max::PartialMinMax;
 use crate::view::snakes::Snakes;
 
+/// How good a killer is at catching players
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumRotate)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// What makes a killer harder or easier to escape
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Params {
+    /// Frames between updates of the plan, the killer doesn't
+    /// notice the player changing direction in the meantime
+    pub reaction_delay: usize,
+    /// How many frames ahead the player's movement is predicted
+    pub lookahead: usize,
+}
+
+impl Difficulty {
+    pub fn params(self) -> Params {
+        match self {
+            Difficulty::Easy => Params { reaction_delay: 6, lookahead: 6 },
+            Difficulty::Normal => Params { reaction_delay: 2, lookahead: 15 },
+            Difficulty::Hard => Params { reaction_delay: 0, lookahead: 40 },
+        }
+    }
+}
+
 // tries to kill player
-pub struct Killer;
+// predicts where the nearest player is going and tries to get there
+//  first so that the player runs into the killer's body, if that's not
+//  possible, it heads roughly in the player's direction
+pub struct Killer {
+    params: Params,
+    /// Frame in which the current plan was made
+    planned_at: Option<usize>,
+    /// Cells the killer is going to move through, starting at its head
+    plan: Option<Path>,
+}
+
+impl Killer {
+    pub fn new(params: Params) -> Self {
+        Self {
+            params,
+            planned_at: None,
+            plan: None,
+        }
+    }
+}
 
 // potential alternative to searching for the closest angle
 // would still require searching through a list so not an improvement..
@@ -29,10 +80,21 @@ fn rough_direction(
     other_snakes: impl Snakes,
     board_dim: HexDim,
 ) -> Option<Dir> {
+    // go the short way around the board
+    let wrap = |d: isize, size: isize| {
+        if d > size / 2 {
+            d - size
+        } else if d < -size / 2 {
+            d + size
+        } else {
+            d
+        }
+    };
+
     // dy is scaled to convert from 'hex' coordinates to approximate cartesian coordinates
     let CellDim { sin, .. } = CellDim::from(1.);
-    let dx = (to.h - from.h) as f32;
-    let dy = -(to.v - from.v) as f32 / (2. * sin);
+    let dx = wrap(to.h - from.h, board_dim.h) as f32;
+    let dy = -wrap(to.v - from.v, board_dim.v) as f32 / (2. * sin);
     let angle = (dy.atan2(dx) + TAU) % TAU;
 
     let head_pos = body.segments[0].pos;
@@ -56,42 +118,255 @@ fn distance_to_snake(
     board_dim: HexDim,
     max_dist: Option<usize>, // if not within max_dist, returns max_dist
 ) -> usize {
+    // nothing collides with ghosts
+    let solid = |snake_type| !matches!(snake_type, snake::Type::Ghost { .. });
+    let occupied = |point| match other_snakes.board() {
+        // the board has the snake's own body as well
+        Some(board) => board.segments_at(point).any(|segment| solid(segment.snake_type)),
+        None => {
+            body.segments.iter().any(|segment| segment.pos == point)
+                || other_snakes
+                    .segments_at(point)
+                    .any(|(snake, _)| solid(snake.snake_type))
+        }
+    };
+
     // guaranteed to terminate anyway whenever the head reaches itself again
     let upper_bound = max_dist.unwrap_or(usize::MAX);
     for distance in 1..=upper_bound {
         point = point.wrapping_translate(dir, 1, board_dim);
+        if occupied(point) {
+            return distance;
+        }
+    }
+    upper_bound
+}
+
+/// Where the player's head will be in each of the next `lookahead`
+/// frames, following the player's path if it has one and going
+/// straight after that
+fn predict(player: &Snake, lookahead: usize, board_dim: HexDim) -> Vec<HexPoint> {
+    let head = player.head().pos;
+    let mut predicted = Vec::with_capacity(lookahead);
+    if let Some(path) = player.planned_path() {
+        // the path can still start at the cell the player just left
+        if let Some(start) = path.iter().position(|&pos| pos == head) {
+            predicted.extend(path.iter().skip(start + 1).take(lookahead).copied());
+        }
+    }
+
+    let mut pos = head;
+    let mut dir = player.body.dir;
+    for &next in &predicted {
+        if let Some(next_dir) = pos.single_step_dir_to(next, board_dim) {
+            dir = next_dir;
+        }
+        pos = next;
+    }
+    while predicted.len() < lookahead {
+        pos = pos.wrapping_translate(dir, 1, board_dim);
+        predicted.push(pos);
+    }
+    predicted
+}
+
+/// Shortest path to the earliest predicted cell the killer can reach
+/// before the player, while the killer's body is still there when
+/// the player arrives
+fn plan_intercept(body: &Body, occupancy: &Occupancy, predicted: &[HexPoint], board_dim: HexDim) -> Option<Path> {
+    let head = body.segments[0].pos;
+    let len = body.segments.len() + body.grow;
+
+    // when the player first gets to each cell
+    let mut player_arrives = HashMap::new();
+    for (frame, pos) in predicted.iter().enumerate() {
+        player_arrives.entry(*pos).or_insert(frame + 1);
+    }
+
+    let mut parents: HashMap<HexPoint, HexPoint> = HashMap::new();
+    let mut frontier = vec![head];
+    for frames in 1..=predicted.len() {
+        let mut next_frontier = vec![];
+        for pos in frontier {
+            for dir in Dir::iter() {
+                // no turning around on the spot
+                if frames == 1 && dir == -body.dir {
+                    continue;
+                }
+                let next = pos.wrapping_translate(dir, 1, board_dim);
+                if next == head || parents.contains_key(&next) || !occupancy.is_free(next, frames) {
+                    continue;
+                }
+                parents.insert(next, pos);
 
-        for Segment { pos, .. } in body.segments.iter().chain(other_snakes.iter_segments()) {
-            if *pos == point {
-                return distance;
+                let intercepts = player_arrives
+                    .get(&next)
+                    .map_or(false, |&arrives| frames < arrives && arrives - frames < len);
+                if intercepts {
+                    let mut path = VecDeque::from([next]);
+                    while let Some(&parent) = parents.get(&path[0]) {
+                        path.push_front(parent);
+                    }
+                    return Some(path);
+                }
+                next_frontier.push(next);
             }
         }
+        frontier = next_frontier;
     }
-    upper_bound
+    None
 }
 
 impl Controller for Killer {
     fn next_dir(
         &mut self,
-        body: &mut Body,
+        body: &Body,
         _: Option<&Knowledge>,
         other_snakes: &dyn Snakes,
         _apples: &[Apple],
         gtx: &GameContext,
-        _ftx: &FpsContext,
-        _ctx: &Context,
+        ftx: &FpsContext,
+        _ctx: Option<&Context>,
     ) -> Option<Dir> {
-        let player_snake = other_snakes
+        let board_dim = gtx.board_dim;
+        let head = body.segments[0].pos;
+        let Params { reaction_delay, lookahead } = self.params;
+
+        let Some(player_snake) = other_snakes
             .iter()
-            .filter(|s| s.snake_type == snake::Type::Player)
-            .min_by_key(|s| s.head().pos.manhattan_distance(body.segments[0].pos))
-            .expect("no player snake found");
-
-        let mut target = player_snake.head().pos;
-        // how many cells ahead of the player to target
-        for _ in 0..1 {
-            target = target.wrapping_translate(player_snake.body.dir, 1, gtx.board_dim);
+            .filter(|s| matches!(s.snake_type, snake::Type::Player { .. }))
+            .min_by_key(|s| s.head().pos.wrapping_manhattan_distance(head, board_dim, 0))
+        else {
+            // nobody left to chase
+            return Some(body.dir);
+        };
+
+        let occupancy = Occupancy::new(body, None, other_snakes, board_dim);
+
+        // keep following the plan until it's time to react again
+        if let Some(plan) = &mut self.plan {
+            if plan.len() >= 2 && plan[1] == head {
+                plan.pop_front();
+            }
+        }
+        let plan_usable = self.plan.as_ref().map_or(false, |plan| {
+            plan.len() >= 2 && plan[0] == head && occupancy.is_free(plan[1], 1)
+        });
+        let react = self
+            .planned_at
+            .map_or(true, |planned_at| ftx.game_frame_num >= planned_at + reaction_delay);
+
+        if !plan_usable || react {
+            let predicted = predict(player_snake, lookahead, board_dim);
+            self.plan = plan_intercept(body, &occupancy, &predicted, board_dim);
+            self.planned_at = Some(ftx.game_frame_num);
+
+            if self.plan.is_none() {
+                // can't get there first, at least get closer
+                //  by aiming just ahead of the player
+                let target = player_snake
+                    .head()
+                    .pos
+                    .wrapping_translate(player_snake.body.dir, 1, board_dim);
+                return rough_direction(head, target, body, other_snakes, board_dim);
+            }
         }
-        rough_direction(body.segments[0].pos, target, body, other_snakes, gtx.board_dim)
+
+        let plan = self.plan.as_ref()?;
+        plan[0].single_step_dir_to(plan[1], board_dim)
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.plan.as_ref()
+    }
+
+    fn reset(&mut self, _dir: Dir) {
+        self.plan = None;
+        self.planned_at = None;
+    }
+}
+
+#[test]
+fn test_predict_goes_straight_and_wraps() {
+    use crate::snake_control::pathfinder::test_snake;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let player = test_snake(&[HexPoint { h: 5, v: 1 }, HexPoint { h: 5, v: 2 }], Dir::U);
+    let predicted = predict(&player, 4, board_dim);
+    let expected: Vec<_> = [0, 19, 18, 17].into_iter().map(|v| HexPoint { h: 5, v }).collect();
+    assert_eq!(predicted, expected);
+}
+
+#[test]
+fn test_plan_intercept() {
+    use crate::snake_control::pathfinder::test_body;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let column = |v: std::ops::Range<isize>| v.map(|v| HexPoint { h: 10, v }).collect::<Vec<_>>();
+    let body = test_body(&column(10..13), Dir::U);
+    let occupancy = Occupancy::new(&body, None, &OtherSnakes::empty(), board_dim);
+
+    // the player comes down the same column, the killer gets to a
+    //  cell early enough to be in the way, but not so early that
+    //  its body has moved on
+    let predicted = column(1..10);
+    let plan = plan_intercept(&body, &occupancy, &predicted, board_dim);
+    assert_eq!(
+        plan,
+        Some(VecDeque::from(column(6..11).into_iter().rev().collect::<Vec<_>>()))
+    );
+
+    // the player is gone before the killer gets anywhere near
+    let predicted = column(1..3);
+    assert_eq!(plan_intercept(&body, &occupancy, &predicted, board_dim), None);
+}
+
+#[test]
+fn test_rough_direction_wraps() {
+    use crate::snake_control::pathfinder::test_body;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 30, v: 20 };
+    let cases = [
+        // the short way is across the left edge
+        (HexPoint { h: 1, v: 10 }, HexPoint { h: 28, v: 9 }, Dir::Ul),
+        (HexPoint { h: 1, v: 10 }, HexPoint { h: 4, v: 9 }, Dir::Ur),
+        // and across the top edge
+        (HexPoint { h: 10, v: 1 }, HexPoint { h: 10, v: 18 }, Dir::U),
+        (HexPoint { h: 10, v: 18 }, HexPoint { h: 10, v: 1 }, Dir::D),
+    ];
+    for (from, to, expected) in cases {
+        // facing sideways so that any direction but Dl is allowed
+        let body = test_body(&[from], Dir::Ur);
+        let dir = rough_direction(from, to, &body, OtherSnakes::empty(), board_dim);
+        assert_eq!(dir, Some(expected), "from {from:?} to {to:?}");
+    }
+}
+
+#[test]
+fn test_distance_ignores_ghosts() {
+    use crate::basic::board::Board;
+    use crate::snake_control::pathfinder::{test_body, test_snake};
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let column = |v: std::ops::Range<isize>| v.map(|v| HexPoint { h: 10, v }).collect::<Vec<_>>();
+    let body = test_body(&column(15..18), Dir::U);
+
+    // a ghost right ahead and a snake behind it
+    let mut ghost = test_snake(&column(10..13), Dir::U);
+    ghost.snake_type = snake::Type::Ghost { life: None };
+    let mut snakes = vec![ghost, test_snake(&column(5..8), Dir::U)];
+    snakes.push(test_snake(&column(15..18), Dir::U));
+
+    let mut board = Board::new(board_dim);
+    for snake in &snakes {
+        board.add_snake(snake);
     }
+    let others = OtherSnakes::excluding(&snakes, 2);
+    let head = body.segments[0].pos;
+    assert_eq!(distance_to_snake(head, Dir::U, &body, others, board_dim, None), 8);
+    let others = others.on_board(&board);
+    assert_eq!(distance_to_snake(head, Dir::U, &body, others, board_dim, None), 8);
 }
@@ -1,3 +1,8 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ggez::event::{Axis, Button};
 use ggez::input::keyboard::KeyCode;
 use ggez::Context;
 use itertools::{repeat_n, Itertools};
@@ -7,20 +12,28 @@ use crate::app::fps_control::FpsContext;
 use crate::app::game_context::GameContext;
 use crate::app::keyboard_control::ControlSetup;
 use crate::apple::Apple;
-use crate::basic::{Dir, Dir12, Side};
+use crate::basic::{Dir, Dir12, Food, Side};
+use crate::gym::observation::Encoding;
 use crate::snake::eat_mechanics::Knowledge;
 use crate::snake::Body;
 use crate::snake_control::pathfinder::Path;
 use crate::view::snakes::Snakes;
 
+mod adversarial;
+pub mod agent;
 mod algorithm;
+pub mod external;
+pub mod frozen;
+mod gamepad;
 mod keyboard;
 mod keyboard_clock;
-mod killer;
+pub mod killer;
 mod mouse;
 pub mod pathfinder;
 mod programmed;
 mod rain;
+pub mod replay;
+pub mod timed;
 
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
@@ -30,26 +43,58 @@ pub enum Template {
         knowledge: Knowledge,
     },
     KeyboardClock,
+    Gamepad {
+        /// Index of the gamepad in the order in which gamepads were first used
+        index: usize,
+    },
     Mouse,
     Programmed(Vec<Move>),
-    Killer,
+    Killer(killer::Params),
     Algorithm(pathfinder::Template),
+    /// Searches a few moves ahead against nearby snakes, see [`adversarial`]
+    Adversarial {
+        /// How long the search can take every frame
+        time_budget: Duration,
+        max_depth: usize,
+    },
     Rain,
+    /// Repeats a recorded run, see [`replay`]
+    Replay(Arc<replay::Recording>),
+    /// Controlled by a bot running outside the game, see [`external`]
+    External {
+        endpoint: external::Endpoint,
+        /// How long to wait for the bot each frame
+        timeout: Duration,
+        fallback: external::Fallback,
+    },
+    /// Controlled by a training environment, see [`crate::gym`]
+    Agent(agent::ActionSlot),
+    /// A trained agent, `encoding` has to match the one used in training
+    Policy {
+        policy: agent::SharedPolicy,
+        encoding: Encoding,
+    },
+    /// Records how long `controller` takes to decide
+    Timed {
+        controller: Box<Template>,
+        timings: timed::Timings,
+    },
 }
 
 pub trait Controller {
     // NOTE: there is a difference between returning None and the same dir
     //  returning None will cause the snake to query again on the
     //  next graphics frame, otherwise it will wait until the next game frame
+    // NOTE: `ctx` is None when the game runs headless (without a window)
     fn next_dir(
         &mut self,
-        body: &mut Body,
+        body: &Body,
         knowledge: Option<&Knowledge>,
         other_snakes: &dyn Snakes,
         apples: &[Apple],
         gtx: &GameContext,
         ftx: &FpsContext,
-        ctx: &Context,
+        ctx: Option<&Context>,
     ) -> Option<Dir>;
 
     // only implemented for autopilot-like controllers
@@ -64,10 +109,33 @@ pub trait Controller {
         None
     }
 
+    // the path currently being followed, without recalculating it
+    fn current_path(&self) -> Option<&Path> {
+        None
+    }
+
+    // turns the player asked for that haven't been made yet,
+    //  only implemented for controllers that queue input
+    fn queued_dirs(&self) -> Option<&VecDeque<Dir>> {
+        None
+    }
+
+    // food the snake ate at the end of its last move without eating an
+    //  apple, asked right after next_dir, only implemented for replays
+    fn food_eaten(&self) -> Option<Food> {
+        None
+    }
+
     fn reset(&mut self, _dir: Dir) {}
 
     fn key_pressed(&mut self, _key: KeyCode) {}
 
+    // `gamepad` is the index of the gamepad that produced the event,
+    //  controllers ignore events from gamepads other than their own
+    fn gamepad_axis(&mut self, _gamepad: usize, _axis: Axis, _value: f32) {}
+
+    fn gamepad_button(&mut self, _gamepad: usize, _button: Button) {}
+
     // TODO: deprecate
     fn knowledge(&self) -> Option<&Knowledge> {
         None
@@ -163,13 +231,19 @@ impl Template {
     // TODO: remove start_dir
     pub fn into_controller(self, start_dir: Dir) -> Box<dyn Controller + Send + Sync> {
         // use crate::snake_control::a_star::AStar;
+        use adversarial::Adversarial;
+        use agent::{Agent, PolicyController};
         use algorithm::Algorithm;
+        use external::External;
+        use gamepad::Gamepad;
         use keyboard::Keyboard;
         use keyboard_clock::KeyboardClock;
         use killer::Killer;
         use mouse::Mouse;
         use programmed::Programmed;
         use rain::Rain;
+        use replay::Replay;
+        use timed::Timed;
 
         match self {
             Template::Keyboard { control_setup, knowledge } => {
@@ -180,6 +254,7 @@ impl Template {
                 alternation: false,
                 next_dir: None,
             }),
+            Template::Gamepad { index } => Box::new(Gamepad::new(index)),
             Template::Mouse => Box::new(Mouse),
             Template::Programmed(move_sequence) => Box::new(Programmed {
                 move_sequence,
@@ -187,12 +262,23 @@ impl Template {
                 next_move_idx: 0,
                 wait: 0,
             }),
-            Template::Killer => Box::new(Killer),
+            Template::Killer(params) => Box::new(Killer::new(params)),
             Template::Algorithm(template) => Box::new(Algorithm {
                 pathfinder: template.into_pathfinder(start_dir),
                 path: None,
             }),
+            Template::Adversarial { time_budget, max_depth } => Box::new(Adversarial { time_budget, max_depth }),
             Template::Rain => Box::new(Rain),
+            Template::Replay(recording) => Box::new(Replay { recording, next_frame: 0 }),
+            Template::External { endpoint, timeout, fallback } => {
+                Box::new(External::new(endpoint, timeout, fallback, start_dir))
+            }
+            Template::Agent(slot) => Box::new(Agent { slot }),
+            Template::Policy { policy, encoding } => Box::new(PolicyController { policy, encoding }),
+            Template::Timed { controller, timings } => Box::new(Timed {
+                controller: controller.into_controller(start_dir),
+                timings,
+            }),
         }
     }
 }
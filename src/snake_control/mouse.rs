@@ -16,15 +16,16 @@ pub struct Mouse;
 impl Controller for Mouse {
     fn next_dir(
         &mut self,
-        body: &mut Body,
+        body: &Body,
         _: Option<&Knowledge>,
         _: &dyn Snakes,
         _: &[Apple],
         gtx: &GameContext,
         _ftx: &FpsContext,
-        ctx: &Context,
+        ctx: Option<&Context>,
     ) -> Option<Dir> {
-        let mouse_position: Point = ctx.mouse.position().into();
+        // there is no mouse when running headless
+        let mouse_position: Point = ctx?.mouse.position().into();
         let target = HexPoint::from_cartesian(mouse_position, gtx.cell_dim);
 
         let current = body.segments[0].pos;
@@ -0,0 +1,260 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use super::occupancy::Occupancy;
+use super::{cell_index, Costs, Path, PathFinder};
+use crate::app::game_context::GameContext;
+use crate::basic::{Dir, HexDim, HexPoint};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::view::snakes::Snakes;
+use crate::view::targets::Targets;
+
+/// Finds lowest-cost paths like `WeightedBFS`, exploring the
+/// cheapest-looking positions first. Steps are free, so the
+/// wrap-aware estimate of the remaining length only decides
+/// between positions that cost the same so far.
+///
+/// `WeightedBFS` only keeps one way of getting to each cell, so
+/// around obstacles it can settle for a path that costs more.
+pub struct AStar {
+    pub costs: Costs,
+}
+
+/// A position together with the direction it was entered in, turns
+/// cost extra so the same cell can be worth more or less depending
+/// on the direction
+fn state_index(pos: HexPoint, dir: Dir, board_dim: HexDim) -> usize {
+    cell_index(pos, board_dim) * 6 + dir as usize
+}
+
+impl AStar {
+    /// Also returns how many states were expanded
+    pub(super) fn search(
+        costs: &Costs,
+        targets: &dyn Targets,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
+    ) -> (Option<Path>, usize) {
+        let board_dim = gtx.board_dim;
+        let occupancy = Occupancy::new(body, knowledge, other_snakes, board_dim);
+        let targets: Vec<_> = targets.iter().collect();
+        if targets.is_empty() {
+            return (None, 0);
+        }
+
+        let estimate = |pos: HexPoint| {
+            targets
+                .iter()
+                .map(|target| pos.wrapping_manhattan_distance(*target, board_dim, 0))
+                .min()
+                .unwrap()
+        };
+
+        let num_states = (board_dim.h * board_dim.v) as usize * 6;
+        // lowest known (cost, frames) of getting to each state and where
+        //  it came from, the shorter of two equally costly paths wins
+        let mut best = vec![(usize::MAX, usize::MAX); num_states];
+        let mut parents: Vec<Option<(HexPoint, Dir)>> = vec![None; num_states];
+
+        let start = body.segments[0].pos;
+        best[state_index(start, body.dir, board_dim)] = (1, 0);
+
+        // ((cost so far, estimated total frames), frames so far,
+        //  position, direction), ties go to the state that got further
+        let mut heap = BinaryHeap::new();
+        heap.push((Reverse((1, estimate(start))), 0, start, body.dir));
+
+        let mut expanded = 0;
+        while let Some((Reverse((cost, _)), frames, pos, dir)) = heap.pop() {
+            if (cost, frames) > best[state_index(pos, dir, board_dim)] {
+                // a cheaper way here was already found
+                continue;
+            }
+            expanded += 1;
+
+            if pos != start && targets.contains(&pos) {
+                let mut path = VecDeque::from([pos]);
+                let mut state = (pos, dir);
+                while let Some(parent) = parents[state_index(state.0, state.1, board_dim)] {
+                    path.push_front(parent.0);
+                    state = parent;
+                }
+                return (Some(path), expanded);
+            }
+
+            for new_dir in Dir::iter() {
+                let (new_pos, teleported) = pos.explicit_wrapping_translate(new_dir, 1, board_dim);
+                if !occupancy.is_free(new_pos, frames + 1) {
+                    continue;
+                }
+
+                let new_cost =
+                    cost + if new_dir != dir { costs.turn } else { 0 } + if teleported { costs.teleport } else { 0 };
+                let index = state_index(new_pos, new_dir, board_dim);
+                if (new_cost, frames + 1) < best[index] {
+                    best[index] = (new_cost, frames + 1);
+                    parents[index] = Some((pos, dir));
+                    heap.push((
+                        Reverse((new_cost, frames + 1 + estimate(new_pos))),
+                        frames + 1,
+                        new_pos,
+                        new_dir,
+                    ));
+                }
+            }
+        }
+
+        (None, expanded)
+    }
+}
+
+impl PathFinder for AStar {
+    fn get_path(
+        &self,
+        targets: &dyn Targets,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
+    ) -> Option<Path> {
+        Self::search(&self.costs, targets, body, knowledge, other_snakes, gtx).0
+    }
+}
+
+#[cfg(test)]
+type Search = fn(&Costs, &dyn Targets, &Body, Option<&Knowledge>, &dyn Snakes, &GameContext) -> (Option<Path>, usize);
+
+/// Searches from a snake with the given segments to `targets` with
+/// `others` on the board, checks that the path only goes through
+/// free cells and returns its cost and how many points were searched
+#[cfg(test)]
+fn test_search(
+    search: Search,
+    costs: &Costs,
+    (segments, dir): (&[HexPoint], Dir),
+    mut others: Vec<crate::snake::Snake>,
+    targets: &[HexPoint],
+    board_dim: HexDim,
+) -> (usize, usize) {
+    use crate::view::snakes::OtherSnakes;
+
+    let gtx = super::test_gtx(board_dim);
+    let body = super::test_body(segments, dir);
+    let apples = super::test_apples(targets);
+    // the snake itself is left out
+    others.push(super::test_snake(segments, dir));
+    let others = OtherSnakes::excluding(&others, others.len() - 1);
+
+    let (path, searched) = search(costs, &apples.as_slice(), &body, None, &others, &gtx);
+    let path = path.unwrap_or_else(|| panic!("no path from {segments:?} to {targets:?}"));
+
+    let occupancy = Occupancy::new(&body, None, &others, board_dim);
+    for (frames, pos) in path.iter().enumerate().skip(1) {
+        assert!(
+            occupancy.is_free(*pos, frames),
+            "{pos:?} is taken after {frames} frames in {path:?}"
+        );
+    }
+    assert!(targets.contains(path.back().unwrap()));
+    (super::path_cost(&path, dir, costs, board_dim), searched)
+}
+
+#[test]
+fn test_same_cost_as_weighted_bfs() {
+    use super::weighted_bfs::WeightedBFS;
+
+    let board_dim = HexPoint { h: 30, v: 20 };
+    let costs = Costs::default();
+    let mut corpus = vec![];
+    for head in [HexPoint { h: 10, v: 10 }, HexPoint { h: 11, v: 10 }] {
+        for dir in Dir::iter() {
+            // straight, turning, and turning around
+            for target_dir in Dir::iter() {
+                corpus.push((head, dir, vec![head.translate(target_dir, 6)]));
+            }
+            // far but straight ahead vs close but with a turn
+            corpus.push((head, dir, vec![head.translate(dir, 9), head.translate(dir + 1, 3)]));
+        }
+    }
+
+    for (head, dir, targets) in corpus {
+        let snake = (&[head][..], dir);
+        let (bfs_cost, _) = test_search(WeightedBFS::search, &costs, snake, vec![], &targets, board_dim);
+        let (a_star_cost, _) = test_search(AStar::search, &costs, snake, vec![], &targets, board_dim);
+        assert_eq!(a_star_cost, bfs_cost, "from {head:?} going {dir:?} to {targets:?}");
+    }
+}
+
+#[test]
+fn test_around_obstacles() {
+    use super::weighted_bfs::WeightedBFS;
+
+    let board_dim = HexPoint { h: 30, v: 20 };
+    let costs = Costs::default();
+    let column = |h, v: std::ops::Range<isize>| v.map(move |v| HexPoint { h, v }).collect::<Vec<_>>();
+    let row = |h: std::ops::Range<isize>, v| h.map(move |h| HexPoint { h, v }).collect::<Vec<_>>();
+
+    // the target is behind the snake's own body
+    let body = column(10, 10..16);
+    let target = [HexPoint { h: 10, v: 17 }];
+    let (bfs_cost, _) = test_search(WeightedBFS::search, &costs, (&body, Dir::U), vec![], &target, board_dim);
+    let (a_star_cost, _) = test_search(AStar::search, &costs, (&body, Dir::U), vec![], &target, board_dim);
+    assert!(a_star_cost > 1, "went through its body");
+    assert!(a_star_cost <= bfs_cost);
+
+    // the target is behind another snake lying across the way
+    let body = column(10, 12..15);
+    let wall: Vec<_> = row(6..15, 9).into_iter().rev().collect();
+    let wall = || vec![super::test_snake(&wall, Dir::Dr)];
+    let target = [HexPoint { h: 10, v: 6 }];
+    let (bfs_cost, _) = test_search(WeightedBFS::search, &costs, (&body, Dir::U), wall(), &target, board_dim);
+    let (a_star_cost, _) = test_search(AStar::search, &costs, (&body, Dir::U), wall(), &target, board_dim);
+    assert!(a_star_cost > 1, "went through the other snake");
+    assert!(a_star_cost <= bfs_cost);
+}
+
+#[test]
+fn test_wraps_around() {
+    use super::weighted_bfs::WeightedBFS;
+
+    let board_dim = HexPoint { h: 30, v: 20 };
+    // a teleport is cheaper than a turn
+    let costs = Costs { turn: 5, teleport: 3 };
+    let starts = [
+        (HexPoint { h: 15, v: 1 }, Dir::U),
+        (HexPoint { h: 15, v: 18 }, Dir::D),
+        (HexPoint { h: 1, v: 10 }, Dir::Ul),
+        (HexPoint { h: 28, v: 10 }, Dir::Dr),
+    ];
+    for (head, dir) in starts {
+        // the body is in the way of turning around
+        let body: Vec<_> = (0..3).map(|i| head.wrapping_translate(-dir, i, board_dim)).collect();
+        let target = [head.wrapping_translate(dir, 3, board_dim)];
+        let (bfs_cost, _) = test_search(WeightedBFS::search, &costs, (&body, dir), vec![], &target, board_dim);
+        let (a_star_cost, _) = test_search(AStar::search, &costs, (&body, dir), vec![], &target, board_dim);
+        assert_eq!(a_star_cost, 1 + costs.teleport, "from {head:?} going {dir:?}");
+        assert_eq!(bfs_cost, a_star_cost, "from {head:?} going {dir:?}");
+    }
+}
+
+#[test]
+fn test_fewer_expansions_than_weighted_bfs() {
+    use super::weighted_bfs::WeightedBFS;
+
+    let board_dim = HexPoint { h: 200, v: 150 };
+    let costs = Costs::default();
+    let head = HexPoint { h: 100, v: 75 };
+    let snake = (&[head][..], Dir::U);
+    let targets = [head.translate(Dir::Dr, 40)];
+
+    let (bfs_cost, bfs_searched) = test_search(WeightedBFS::search, &costs, snake, vec![], &targets, board_dim);
+    let (a_star_cost, a_star_searched) = test_search(AStar::search, &costs, snake, vec![], &targets, board_dim);
+    assert_eq!(a_star_cost, bfs_cost);
+    assert!(
+        a_star_searched * 10 < bfs_searched,
+        "A* expanded {a_star_searched} states, weighted BFS created {bfs_searched} search points"
+    );
+}
@@ -0,0 +1,123 @@
+//! Pathfinding on a large board, run with
+//! `cargo bench --features bench`, left out of `cargo test`
+//! because every bench also runs once as a test
+
+use test::{black_box, Bencher};
+
+use super::a_star::AStar;
+use super::weighted_bfs::WeightedBFS;
+use super::{Costs, PathFinder};
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::{Dir, HexDim, HexPoint};
+use crate::snake::Body;
+use crate::view::snakes::OtherSnakes;
+
+const BOARD_DIM: HexDim = HexDim { h: 200, v: 150 };
+
+struct Scenario {
+    gtx: GameContext,
+    body: Body,
+    apples: Vec<Apple>,
+}
+
+impl Scenario {
+    /// A short snake in the middle of the board
+    fn new(apples: &[HexPoint]) -> Self {
+        Self::with_body(&[HexPoint { h: 100, v: 75 }], apples)
+    }
+
+    fn with_body(body: &[HexPoint], apples: &[HexPoint]) -> Self {
+        Self {
+            gtx: super::test_gtx(BOARD_DIM),
+            body: super::test_body(body, Dir::U),
+            apples: super::test_apples(apples),
+        }
+    }
+
+    fn one_far_apple() -> Self {
+        Self::new(&[HexPoint { h: 170, v: 20 }])
+    }
+
+    /// Apples spread over the board, none of them close
+    fn many_apples() -> Self {
+        let apples: Vec<_> = (0..40)
+            .map(|i| HexPoint {
+                h: (i * 37 + 11) % 200,
+                v: (i * 53 + 7) % 150,
+            })
+            .filter(|pos| pos.h.abs_diff(100) > 30 || pos.v.abs_diff(75) > 30)
+            .collect();
+        Self::new(&apples)
+    }
+
+    /// A snake of 2000 segments folded into a block that lies
+    /// between its head and the apple
+    fn coiled_body() -> Self {
+        let body: Vec<_> = (0..50)
+            .flat_map(|row| {
+                let v = 100 - row;
+                (60..100).map(move |h| HexPoint {
+                    h: if row % 2 == 0 { h } else { 159 - h },
+                    v,
+                })
+            })
+            .collect();
+        Self::with_body(&body, &[HexPoint { h: 80, v: 20 }])
+    }
+
+    fn run(&self, pathfinder: &dyn PathFinder) {
+        let path = pathfinder.get_path(
+            &self.apples.as_slice(),
+            &self.body,
+            None,
+            &OtherSnakes::empty(),
+            &self.gtx,
+        );
+        assert!(path.is_some(), "no path found");
+        black_box(path);
+    }
+}
+
+#[bench]
+fn bench_weighted_bfs_one_far_apple(b: &mut Bencher) {
+    let scenario = Scenario::one_far_apple();
+    let pathfinder = WeightedBFS::new(Costs::default());
+    b.iter(|| scenario.run(&pathfinder));
+}
+
+#[bench]
+fn bench_weighted_bfs_many_apples(b: &mut Bencher) {
+    let scenario = Scenario::many_apples();
+    let pathfinder = WeightedBFS::new(Costs::default());
+    b.iter(|| scenario.run(&pathfinder));
+}
+
+#[bench]
+fn bench_weighted_bfs_coiled_body(b: &mut Bencher) {
+    let scenario = Scenario::coiled_body();
+    let pathfinder = WeightedBFS::new(Costs::default());
+    b.iter(|| scenario.run(&pathfinder));
+}
+
+/// Allocates the buffers for every search, like the first
+/// search of every snake
+#[bench]
+fn bench_weighted_bfs_fresh_buffers(b: &mut Bencher) {
+    let scenario = Scenario::one_far_apple();
+    b.iter(|| scenario.run(&WeightedBFS::new(Costs::default())));
+}
+
+#[bench]
+fn bench_a_star_one_far_apple(b: &mut Bencher) {
+    let scenario = Scenario::one_far_apple();
+    let pathfinder = AStar { costs: Costs::default() };
+    b.iter(|| scenario.run(&pathfinder));
+}
+
+#[bench]
+fn bench_a_star_coiled_body(b: &mut Bencher) {
+    let scenario = Scenario::coiled_body();
+    let pathfinder = AStar { costs: Costs::default() };
+    b.iter(|| scenario.run(&pathfinder));
+}
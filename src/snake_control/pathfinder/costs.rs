@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::{fs, io};
+
+use serde_json::{json, Value};
+
+/// What the search-based pathfinders minimize, a path costs
+/// 1 + turn * num_turns + teleport * num_teleports, steps are
+/// free and only decide between paths that cost the same
+///
+/// The defaults were picked by hand, `hex_snake evolve` looks
+/// for better ones and saves them to a genome file.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Costs {
+    /// Extra cost of a step in a different direction than the last one
+    pub turn: usize,
+    /// Extra cost of a step that wraps around the edge of the board
+    pub teleport: usize,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Self { turn: 5, teleport: 15 }
+    }
+}
+
+impl Costs {
+    /// Where `hex_snake evolve` saves its best genome by default
+    pub const GENOME_FILE: &'static str = "genome.json";
+
+    pub fn to_json(self) -> Value {
+        json!({
+            "turn_cost": self.turn,
+            "teleport_cost": self.teleport,
+        })
+    }
+
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let field = |name| json.get(name)?.as_u64().map(|value| value as usize);
+        Some(Self {
+            turn: field("turn_cost")?,
+            teleport: field("teleport_cost")?,
+        })
+    }
+
+    /// Reads a genome file written by `hex_snake evolve`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let json: Value = serde_json::from_str(&text)?;
+        Self::from_json(&json).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a genome"))
+    }
+
+    pub fn save(self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, format!("{:#}\n", self.to_json()))
+    }
+
+    /// The evolved costs if there is a genome file in the working
+    /// directory, otherwise the defaults
+    pub fn evolved_or_default() -> Self {
+        match Self::load(Self::GENOME_FILE) {
+            Ok(costs) => costs,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                eprintln!("warning: ignoring {}: {e}", Self::GENOME_FILE);
+                Self::default()
+            }
+        }
+    }
+}
@@ -1,15 +1,24 @@
+mod a_star;
+#[cfg(all(test, feature = "bench"))]
+mod benches;
+mod costs;
+pub mod occupancy;
 mod space_filling;
+mod survival;
 mod weighted_bfs;
 mod with_backup;
 
 use std::collections::VecDeque;
 
+use a_star::AStar;
+pub use costs::Costs;
 use space_filling::SpaceFilling;
+use survival::Survival;
 use weighted_bfs::WeightedBFS;
 use with_backup::WithBackup;
 
 use crate::app::game_context::GameContext;
-use crate::basic::{Dir, HexPoint};
+use crate::basic::{Dir, HexDim, HexPoint};
 use crate::snake::eat_mechanics::Knowledge;
 use crate::snake::Body;
 use crate::view::snakes::Snakes;
@@ -17,6 +26,28 @@ use crate::view::targets::Targets;
 
 pub type Path = VecDeque<HexPoint>;
 
+/// Position of a cell in flat arrays that cover the board
+fn cell_index(pos: HexPoint, board_dim: HexDim) -> usize {
+    (pos.v * board_dim.h + pos.h) as usize
+}
+
+/// The cost of a path according to `costs`, this is what
+/// the search-based pathfinders minimize (see [`Costs`])
+#[cfg(test)]
+fn path_cost(path: &Path, start_dir: Dir, costs: &Costs, board_dim: HexDim) -> usize {
+    let mut dir = start_dir;
+    let mut cost = 1;
+    for (from, to) in path.iter().zip(path.iter().skip(1)) {
+        let new_dir = from
+            .single_step_dir_to(*to, board_dim)
+            .expect("path points are not adjacent");
+        let (_, teleported) = from.explicit_wrapping_translate(new_dir, 1, board_dim);
+        cost += if new_dir != dir { costs.turn } else { 0 } + if teleported { costs.teleport } else { 0 };
+        dir = new_dir;
+    }
+    cost
+}
+
 pub trait PathFinder {
     fn get_path(
         &self,
@@ -30,20 +61,112 @@ pub trait PathFinder {
 
 #[derive(Clone, Debug)]
 pub enum Template {
-    WeightedBFS,
+    WeightedBFS(Costs),
+    AStar(Costs),
     SpaceFilling,
-    WithBackup { main: Box<Template>, backup: Box<Template> },
+    WithBackup {
+        main: Box<Template>,
+        backup: Box<Template>,
+    },
+    /// Rejects paths that would leave the snake nowhere to go
+    /// after eating and chases its own tail instead
+    Survival {
+        main: Box<Template>,
+    },
 }
 
 impl Template {
     pub fn into_pathfinder(self, _start_dir: Dir) -> Box<dyn PathFinder + Send + Sync> {
         match self {
-            Template::WeightedBFS => Box::new(WeightedBFS),
-            Template::SpaceFilling => Box::new(SpaceFilling),
+            Template::WeightedBFS(costs) => Box::new(WeightedBFS::new(costs)),
+            Template::AStar(costs) => Box::new(AStar { costs }),
+            Template::SpaceFilling => Box::<SpaceFilling>::default(),
             Template::WithBackup { main, backup } => Box::new(WithBackup {
                 main: main.into_pathfinder(_start_dir),
                 backup: backup.into_pathfinder(_start_dir),
             }),
+            Template::Survival { main } => Box::new(Survival {
+                main: main.into_pathfinder(_start_dir),
+            }),
         }
     }
 }
+
+/// A board without any apples or snakes
+#[cfg(test)]
+pub(crate) fn test_gtx(board_dim: HexDim) -> GameContext {
+    use crate::app::screen::Prefs;
+    use crate::app::Palette;
+    use crate::apple::spawn::SpawnPolicy;
+    use crate::basic::CellDim;
+
+    GameContext::new(
+        board_dim,
+        CellDim::from(1.),
+        Palette::dark(),
+        Prefs::default(),
+        SpawnPolicy::Random { apple_count: 0 },
+    )
+}
+
+/// A body with the given segments, head first
+#[cfg(test)]
+pub(crate) fn test_body(segments: &[HexPoint], dir: Dir) -> Body {
+    use crate::snake::{Segment, SegmentType};
+
+    Body {
+        segments: segments
+            .iter()
+            .map(|&pos| Segment {
+                segment_type: SegmentType::Normal,
+                pos,
+                coming_from: -dir,
+                teleported: None,
+                z_index: 0,
+            })
+            .collect(),
+        missing_front: 0,
+        dir,
+        turn_start: None,
+        dir_grace: false,
+        grow: 0,
+        search_trace: None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_apples(positions: &[HexPoint]) -> Vec<crate::apple::Apple> {
+    use crate::apple::{self, Apple};
+
+    positions
+        .iter()
+        .map(|&pos| Apple {
+            pos,
+            apple_type: apple::Type::Food(1),
+        })
+        .collect()
+}
+
+/// A competitor with the given segments, head first
+#[cfg(test)]
+pub(crate) fn test_snake(segments: &[HexPoint], dir: Dir) -> crate::snake::Snake {
+    use crate::app::Palette;
+    use crate::snake;
+    use crate::snake::builder::Builder as SnakeBuilder;
+    use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
+    use crate::snake_control;
+
+    let mut snake = SnakeBuilder::default()
+        .snake_type(snake::Type::Competitor { life: None })
+        .eat_mechanics(EatMechanics::always(EatBehavior::Die))
+        .palette(Palette::dark().palette_competitor)
+        .controller(snake_control::Template::Rain)
+        .speed(1.)
+        .pos(segments[0])
+        .dir(dir)
+        .len(segments.len())
+        .build()
+        .unwrap();
+    snake.body = test_body(segments, dir);
+    snake
+}
@@ -0,0 +1,220 @@
+use super::cell_index;
+use crate::basic::{HexDim, HexPoint};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::{self, Body, Segment, SegmentType, State};
+use crate::view::snakes::Snakes;
+
+/// When each occupied cell will be free again
+///
+/// Snakes leave their cells from the tail, segment k (counting from
+/// the head) of a snake of length n is gone after n - k frames, plus
+/// one frame for every bit of food the snake still has to digest
+/// behind that segment. A cell that is free after t frames can be
+/// entered by a head that arrives t frames from now.
+pub struct Occupancy {
+    board_dim: HexDim,
+    /// Indexed by cell, 0 for cells that are free already
+    free_after: Vec<usize>,
+    /// Cells that were occupied, clearing only touches these
+    occupied: Vec<usize>,
+}
+
+impl Occupancy {
+    /// The snake's own body and all other snakes, `knowledge` decides
+    /// which of the snake's own segments it can pass through
+    pub fn new(body: &Body, knowledge: Option<&Knowledge>, other_snakes: &dyn Snakes, board_dim: HexDim) -> Self {
+        let mut occupancy = Self::empty();
+        occupancy.refill(body, knowledge, other_snakes, board_dim);
+        occupancy
+    }
+
+    /// Only the other snakes, for pathfinders that deal with the
+    /// snake's own body in their own way
+    pub fn others(other_snakes: &dyn Snakes, board_dim: HexDim) -> Self {
+        let mut occupancy = Self::empty();
+        occupancy.clear(board_dim);
+        occupancy.add_others(other_snakes);
+        occupancy
+    }
+
+    /// Occupies nothing until it is refilled
+    pub fn empty() -> Self {
+        Self {
+            board_dim: HexDim { h: 0, v: 0 },
+            free_after: vec![],
+            occupied: vec![],
+        }
+    }
+
+    /// Same as [`Occupancy::new`] but reuses the memory, which
+    /// is only reallocated when the board size changes
+    pub fn refill(&mut self, body: &Body, knowledge: Option<&Knowledge>, other_snakes: &dyn Snakes, board_dim: HexDim) {
+        self.clear(board_dim);
+        self.add_others(other_snakes);
+        self.add_body(
+            body,
+            |seg| knowledge.map_or(false, |pk| pk.can_pass_through_self(seg)),
+            false,
+        );
+    }
+
+    fn clear(&mut self, board_dim: HexDim) {
+        if self.board_dim != board_dim {
+            self.board_dim = board_dim;
+            self.free_after.clear();
+            self.free_after.resize((board_dim.h * board_dim.v) as usize, 0);
+        } else {
+            for &cell in &self.occupied {
+                self.free_after[cell] = 0;
+            }
+        }
+        self.occupied.clear();
+    }
+
+    fn add_others(&mut self, other_snakes: &dyn Snakes) {
+        // ghosts can't get in the way
+        for snake in other_snakes
+            .iter()
+            .filter(|snake| !matches!(snake.snake_type, snake::Type::Ghost { .. }))
+        {
+            // crashed snakes don't go anywhere
+            self.add_body(&snake.body, |_| false, snake.state == State::Crashed);
+
+            // other snakes most likely keep going straight, the cell
+            //  ahead of them is taken from the next frame on
+            if snake.state == State::Living {
+                let ahead = snake.body.segments[0]
+                    .pos
+                    .wrapping_translate(snake.body.dir, 1, self.board_dim);
+                let free_after = 1 + snake.body.segments.len() + Self::growth(&snake.body);
+                self.occupy(ahead, free_after);
+            }
+        }
+    }
+
+    /// Food the snake hasn't digested yet, including growth
+    /// that has already started
+    pub fn growth(body: &Body) -> usize {
+        body.grow
+            + body
+                .segments
+                .iter()
+                .map(|seg| match seg.segment_type {
+                    SegmentType::Eaten { food_left, .. } => food_left as usize,
+                    _ => 0,
+                })
+                .sum::<usize>()
+    }
+
+    fn add_body(&mut self, body: &Body, passable: impl Fn(&Segment) -> bool, stuck: bool) {
+        let len = body.segments.len();
+        // food behind a segment (and the growth in progress) keeps it
+        //  in place for longer
+        let mut delay = body.grow;
+        for (k, seg) in body.segments.iter().enumerate().rev() {
+            if let SegmentType::Eaten { food_left, .. } = seg.segment_type {
+                delay += food_left as usize;
+            }
+            if passable(seg) {
+                continue;
+            }
+            let free_after = if stuck { usize::MAX } else { len - k + delay };
+            self.occupy(seg.pos, free_after);
+        }
+    }
+
+    fn occupy(&mut self, pos: HexPoint, free_after: usize) {
+        // snakes can still be off the board right after it shrinks
+        if !self.board_dim.contains(pos) {
+            return;
+        }
+        let cell = cell_index(pos, self.board_dim);
+        if self.free_after[cell] == 0 {
+            self.occupied.push(cell);
+        }
+        self.free_after[cell] = self.free_after[cell].max(free_after);
+    }
+
+    /// Whether a head arriving `frames` frames from now can enter the cell
+    pub fn is_free(&self, pos: HexPoint, frames: usize) -> bool {
+        !self.board_dim.contains(pos) || frames >= self.free_after[cell_index(pos, self.board_dim)]
+    }
+}
+
+#[cfg(test)]
+fn test_column(len: isize) -> Vec<HexPoint> {
+    (0..len).map(|v| HexPoint { h: 5, v: 5 + v }).collect()
+}
+
+#[test]
+fn test_segments_free_from_the_tail() {
+    use crate::basic::Dir;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let segments = test_column(5);
+    let occupancy = Occupancy::new(
+        &super::test_body(&segments, Dir::U),
+        None,
+        &OtherSnakes::empty(),
+        board_dim,
+    );
+    for (k, &pos) in segments.iter().enumerate() {
+        let free_after = segments.len() - k;
+        assert!(!occupancy.is_free(pos, free_after - 1), "segment {k}");
+        assert!(occupancy.is_free(pos, free_after), "segment {k}");
+    }
+    assert!(occupancy.is_free(HexPoint { h: 6, v: 5 }, 0));
+}
+
+#[test]
+fn test_growth_delays_freeing() {
+    use crate::basic::Dir;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let segments = test_column(5);
+    let mut body = super::test_body(&segments, Dir::U);
+    body.grow = 2;
+    body.segments[2].segment_type = SegmentType::Eaten { original_food: 3, food_left: 3 };
+    let occupancy = Occupancy::new(&body, None, &OtherSnakes::empty(), board_dim);
+
+    // growth in progress holds up everything, the food only the
+    //  segments in front of it
+    for (k, delay) in [(0, 5), (1, 5), (2, 5), (3, 2), (4, 2)] {
+        let free_after = segments.len() - k + delay;
+        assert!(!occupancy.is_free(segments[k], free_after - 1), "segment {k}");
+        assert!(occupancy.is_free(segments[k], free_after), "segment {k}");
+    }
+}
+
+#[test]
+fn test_other_snakes() {
+    use crate::basic::Dir;
+    use crate::view::snakes::OtherSnakes;
+
+    let board_dim = HexDim { h: 20, v: 20 };
+    let segments = test_column(3);
+    let ahead = segments[0].translate(Dir::U, 1);
+    // the last one is the snake looking at the others
+    let mut snakes = [
+        super::test_snake(&segments, Dir::U),
+        super::test_snake(&[HexPoint { h: 15, v: 15 }], Dir::U),
+    ];
+
+    // the cell ahead of a living snake is taken until the
+    //  whole snake could have passed through it
+    let occupancy = Occupancy::others(&OtherSnakes::excluding(&snakes, 1), board_dim);
+    assert!(!occupancy.is_free(ahead, 1));
+    assert!(!occupancy.is_free(ahead, 3));
+    assert!(occupancy.is_free(ahead, 4));
+    assert!(occupancy.is_free(segments[2], 1));
+
+    // crashed snakes stay where they are
+    snakes[0].state = State::Crashed;
+    let occupancy = Occupancy::others(&OtherSnakes::excluding(&snakes, 1), board_dim);
+    for &pos in &segments {
+        assert!(!occupancy.is_free(pos, usize::MAX - 1));
+    }
+    assert!(occupancy.is_free(ahead, 0));
+}
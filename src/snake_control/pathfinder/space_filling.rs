@@ -1,22 +1,294 @@
+//! Follows a Hamiltonian cycle through every cell of the board
+//!
+//! A snake that only ever moves forward along a cycle that visits
+//! every cell exactly once can never run into itself, no matter how
+//! long it gets. To get to apples faster, the snake may skip ahead
+//! along the cycle as long as it stays in the free stretch between
+//! its head and its tail, which keeps the body ordered along the cycle.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use super::occupancy::Occupancy;
 use super::{Path, PathFinder};
 use crate::app::game_context::GameContext;
+use crate::basic::{Dir, HexDim, HexPoint};
 use crate::snake::eat_mechanics::Knowledge;
-use crate::snake::Body;
+use crate::snake::{Body, SegmentType};
 use crate::view::snakes::Snakes;
 use crate::view::targets::Targets;
 
-pub struct SpaceFilling;
+/// Cycle order of every cell on the board
+struct Cycle {
+    board_dim: HexDim,
+    cells: Vec<HexPoint>,
+    /// Position of each cell in `cells`, indexed by `v * board_dim.h + h`
+    index: Vec<usize>,
+}
+
+impl Cycle {
+    /// Column 0 is walked downwards, the remaining columns snake up
+    /// and down through rows 1.., and row 0 leads back to the start.
+    /// Cells in neighbouring columns with the same `v` are always
+    /// adjacent, whatever the column offsets, so this works on
+    /// hexagons just like on squares. With an odd number of columns
+    /// the last one ends at the bottom and wraps around to row 0.
+    fn new(board_dim: HexDim) -> Option<Self> {
+        let HexDim { h: width, v: height } = board_dim;
+        if width < 1 || height < 2 {
+            return None;
+        }
+
+        let mut cells = Vec::with_capacity((width * height) as usize);
+        cells.extend((0..height).map(|v| HexPoint { h: 0, v }));
+        for h in 1..width {
+            if h % 2 == 1 {
+                cells.extend((1..height).rev().map(|v| HexPoint { h, v }));
+            } else {
+                cells.extend((1..height).map(|v| HexPoint { h, v }));
+            }
+        }
+        cells.extend((1..width).rev().map(|h| HexPoint { h, v: 0 }));
+
+        // every step, including the one closing the cycle, has to be
+        //  a single move
+        let closed = cells
+            .iter()
+            .zip(cells.iter().cycle().skip(1))
+            .all(|(a, b)| a.single_step_dir_to(*b, board_dim).is_some());
+        if !closed {
+            return None;
+        }
+
+        let mut index = vec![0; cells.len()];
+        for (i, cell) in cells.iter().enumerate() {
+            index[(cell.v * width + cell.h) as usize] = i;
+        }
+
+        Some(Self { board_dim, cells, index })
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn index_of(&self, pos: HexPoint) -> usize {
+        self.index[(pos.v * self.board_dim.h + pos.h) as usize]
+    }
+
+    /// How many steps forward along the cycle it takes to get from `a` to `b`
+    fn distance(&self, a: HexPoint, b: HexPoint) -> usize {
+        (self.index_of(b) + self.len() - self.index_of(a)) % self.len()
+    }
+}
+
+/// Free cells kept between the head and the tail on top
+/// of the growth the snake still has ahead of it
+const TAIL_MARGIN: usize = 3;
+
+/// Past this fraction of the board the snake only follows the cycle
+const MAX_SHORTCUT_FILL: f32 = 0.5;
+
+#[derive(Default)]
+pub struct SpaceFilling {
+    /// Rebuilt when the board size changes
+    cycle: Mutex<Option<Cycle>>,
+}
+
+impl SpaceFilling {
+    /// The body has to be laid out along the cycle for the cycle
+    /// to be safe, this is not the case when the snake was steered
+    /// by something else before
+    fn follows_cycle(cycle: &Cycle, body: &Body) -> bool {
+        let head = body.segments[0].pos;
+        let tail = body.segments[body.segments.len() - 1].pos;
+        let span = cycle.distance(tail, head);
+        body.segments.iter().all(|seg| cycle.distance(tail, seg.pos) <= span)
+    }
+
+    /// Walk along the cycle from the head, cutting corners towards
+    /// the first target ahead without leaving the free stretch
+    /// before the tail
+    fn cycle_path(
+        cycle: &Cycle,
+        targets: &dyn Targets,
+        body: &Body,
+        other_snakes: &Occupancy,
+        board_dim: HexDim,
+    ) -> Option<Path> {
+        let head = body.segments[0].pos;
+        let tail = body.segments[body.segments.len() - 1].pos;
+
+        let growth = body.grow
+            + body
+                .segments
+                .iter()
+                .map(|seg| match seg.segment_type {
+                    SegmentType::Eaten { food_left, .. } => food_left as usize,
+                    _ => 0,
+                })
+                .sum::<usize>();
+
+        // with a single segment the tail is the head, the whole cycle is free
+        let free = match cycle.distance(head, tail) {
+            0 => cycle.len(),
+            free => free,
+        };
+        let shortcuts = (body.segments.len() + growth) as f32 <= cycle.len() as f32 * MAX_SHORTCUT_FILL;
+        let limit = if shortcuts {
+            free.saturating_sub(growth + TAIL_MARGIN)
+        } else {
+            0
+        };
+
+        // nearest target in cycle order, corners are only cut within
+        //  the limit, beyond it the snake follows the cycle
+        let goal = targets
+            .iter()
+            .map(|pos| cycle.distance(head, pos))
+            .filter(|&d| d > 0)
+            .min()
+            .unwrap_or(1);
+
+        let mut path = VecDeque::from([head]);
+        let mut dist = 0;
+        while dist < goal {
+            let pos = *path.back().unwrap();
+            let frames = path.len();
+            let next = Dir::iter()
+                .map(|dir| pos.wrapping_translate(dir, 1, board_dim))
+                .filter(|&next| other_snakes.is_free(next, frames))
+                .map(|next| (next, cycle.distance(head, next)))
+                .filter(|&(_, d)| d == dist + 1 || d > dist && d <= goal.min(limit))
+                .max_by_key(|&(_, d)| d);
+
+            match next {
+                Some((next, d)) => {
+                    path.push_back(next);
+                    dist = d;
+                }
+                None => break,
+            }
+        }
+
+        (path.len() > 1).then_some(path)
+    }
+
+    /// Used when the body isn't laid out along the cycle (or there
+    /// is no cycle), steps to the neighbour with the most room to
+    /// move around in
+    fn roomiest_step(body: &Body, other_snakes: &HashSet<HexPoint>, board_dim: HexDim) -> Option<Path> {
+        let head = body.segments[0].pos;
+        // the tail moves out of the way unless the snake is growing
+        let keep = if body.grow > 0 {
+            body.segments.len()
+        } else {
+            body.segments.len() - 1
+        };
+        let blocked: HashSet<_> = body
+            .segments
+            .iter()
+            .take(keep)
+            .map(|seg| seg.pos)
+            .chain(other_snakes.iter().copied())
+            .collect();
+
+        let room = |start: HexPoint| {
+            let mut seen = HashSet::from([start]);
+            let mut queue = VecDeque::from([start]);
+            while let Some(pos) = queue.pop_front() {
+                for dir in Dir::iter() {
+                    let next = pos.wrapping_translate(dir, 1, board_dim);
+                    if !blocked.contains(&next) && seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+            seen.len()
+        };
+
+        Dir::iter()
+            .filter(|&dir| dir != -body.dir)
+            .map(|dir| head.wrapping_translate(dir, 1, board_dim))
+            .filter(|next| !blocked.contains(next))
+            .max_by_key(|&next| room(next))
+            .map(|next| VecDeque::from([head, next]))
+    }
+}
 
 impl PathFinder for SpaceFilling {
     fn get_path(
         &self,
-        _targets: &dyn Targets,
-        _body: &Body,
+        targets: &dyn Targets,
+        body: &Body,
         _knowledge: Option<&Knowledge>,
-        _other_snakes: &dyn Snakes,
-        _gtx: &GameContext,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
     ) -> Option<Path> {
-        // TODO: implement
-        None
+        let board_dim = gtx.board_dim;
+        let occupancy = Occupancy::others(other_snakes, board_dim);
+        let other_snakes: HashSet<_> = other_snakes.iter_segments().map(|seg| seg.pos).collect();
+
+        let mut cycle = self.cycle.lock().unwrap();
+        if cycle.as_ref().map_or(true, |cycle| cycle.board_dim != board_dim) {
+            *cycle = Cycle::new(board_dim);
+        }
+
+        match &*cycle {
+            Some(cycle) if Self::follows_cycle(cycle, body) => {
+                Self::cycle_path(cycle, targets, body, &occupancy, board_dim)
+                    .or_else(|| Self::roomiest_step(body, &other_snakes, board_dim))
+            }
+            _ => Self::roomiest_step(body, &other_snakes, board_dim),
+        }
+    }
+}
+
+#[test]
+fn test_cycle_visits_every_cell() {
+    for (h, v) in [(2, 2), (3, 3), (4, 7), (7, 4), (30, 20), (31, 21)] {
+        let board_dim = HexPoint { h, v };
+        let cycle = Cycle::new(board_dim).unwrap_or_else(|| panic!("no cycle for {board_dim:?}"));
+        assert_eq!(cycle.len(), (h * v) as usize);
+
+        let mut cells = cycle.cells.clone();
+        cells.sort_by_key(|pos| (pos.h, pos.v));
+        cells.dedup();
+        assert_eq!(cells.len(), (h * v) as usize, "{board_dim:?}");
+
+        for (i, &cell) in cycle.cells.iter().enumerate() {
+            assert_eq!(cycle.index_of(cell), i);
+        }
+    }
+}
+
+#[test]
+fn test_fills_small_board_without_crashing() {
+    use crate::gym::reward::Event;
+    use crate::gym::{Config, Gym};
+    use crate::snake_control::{pathfinder, Template};
+
+    let board_dim = HexPoint { h: 8, v: 6 };
+    let cells = (board_dim.h * board_dim.v) as usize;
+    let mut gym = Gym::new(Config {
+        board_dim,
+        agents: 0,
+        contestants: vec![Template::Algorithm(pathfinder::Template::SpaceFilling)],
+        agent_len: 3,
+        opponents: vec![],
+        apple_count: 1,
+        max_steps: Some(20_000),
+        ..Config::default()
+    });
+    gym.reset(0).unwrap();
+
+    loop {
+        let step = gym.step(&[]).unwrap();
+        let ate_only = step.info.events[0].iter().all(|event| matches!(event, Event::Ate(_)));
+        assert!(ate_only, "{:?}", step.info);
+        // the board is full, or out of steps
+        if step.info.lengths[0] >= cells || step.done {
+            break;
+        }
     }
 }
@@ -0,0 +1,292 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::occupancy::Occupancy;
+use super::{Path, PathFinder};
+use crate::app::game_context::GameContext;
+use crate::basic::{Dir, Food, HexDim, HexPoint};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::{Body, SegmentType};
+use crate::view::snakes::Snakes;
+use crate::view::targets::Targets;
+
+/// Only accepts paths after which the snake still has somewhere to
+/// go, otherwise it follows its own tail until things clear up
+pub struct Survival {
+    pub main: Box<dyn PathFinder + Send + Sync>,
+}
+
+#[derive(Copy, Clone)]
+struct SimSegment {
+    pos: HexPoint,
+    food_left: Food,
+}
+
+/// Where the body will be while following a path
+struct Simulation {
+    segments: VecDeque<SimSegment>,
+    grow: usize,
+}
+
+impl Simulation {
+    fn new(body: &Body) -> Self {
+        let segments = body
+            .segments
+            .iter()
+            .map(|seg| SimSegment {
+                pos: seg.pos,
+                food_left: match seg.segment_type {
+                    SegmentType::Eaten { food_left, .. } => food_left,
+                    _ => 0,
+                },
+            })
+            .collect();
+        Self { segments, grow: body.grow }
+    }
+
+    fn head(&self) -> HexPoint {
+        self.segments[0].pos
+    }
+
+    fn tail(&self) -> HexPoint {
+        self.segments[self.segments.len() - 1].pos
+    }
+
+    /// Growth that hasn't happened yet
+    fn pending_growth(&self) -> usize {
+        self.grow + self.segments.iter().map(|seg| seg.food_left as usize).sum::<usize>()
+    }
+
+    /// Same order of events as `Snake::advance` followed by collision
+    /// handling, false if the snake runs into itself
+    fn advance(&mut self, pos: HexPoint, food: Option<Food>) -> bool {
+        let last = self.segments.len() - 1;
+        if self.segments[last].food_left > 0 {
+            self.segments[last].food_left -= 1;
+            self.grow += 1;
+        }
+
+        self.segments.push_front(SimSegment { pos, food_left: 0 });
+        if self.grow > 0 {
+            self.grow -= 1;
+        } else {
+            self.segments.pop_back();
+        }
+
+        if let Some(food) = food {
+            self.segments[0].food_left = food;
+        }
+
+        !self.segments.iter().skip(1).any(|seg| seg.pos == pos)
+    }
+
+    /// Whether the snake can get to its tail, or at least has
+    /// as much room as it is long
+    fn has_room(&self, other_snakes: &HashSet<HexPoint>, board_dim: HexDim) -> bool {
+        let tail = self.tail();
+        let needed = self.segments.len() + self.pending_growth();
+        let blocked: HashSet<_> = self.segments.iter().skip(1).map(|seg| seg.pos).collect();
+
+        let mut seen = HashSet::from([self.head()]);
+        let mut queue = VecDeque::from([self.head()]);
+        while let Some(pos) = queue.pop_front() {
+            for dir in Dir::iter() {
+                let next = pos.wrapping_translate(dir, 1, board_dim);
+                if next == tail && self.segments.len() > 1 {
+                    return true;
+                }
+                if blocked.contains(&next) || other_snakes.contains(&next) || !seen.insert(next) {
+                    continue;
+                }
+                if seen.len() >= needed {
+                    return true;
+                }
+                queue.push_back(next);
+            }
+        }
+        false
+    }
+}
+
+impl Survival {
+    fn survives(
+        path: &Path,
+        targets: &dyn Targets,
+        body: &Body,
+        other_snakes: &HashSet<HexPoint>,
+        gtx: &GameContext,
+    ) -> bool {
+        let mut sim = Simulation::new(body);
+        for (i, &pos) in path.iter().enumerate().skip(1) {
+            let food =
+                (i == path.len() - 1 && targets.iter().any(|target| target == pos)).then_some(gtx.prefs.apple_food);
+            if !sim.advance(pos, food) {
+                return false;
+            }
+        }
+        sim.has_room(other_snakes, gtx.board_dim)
+    }
+
+    /// Shortest path to the current position of the tail
+    fn chase_tail(body: &Body, occupancy: &Occupancy, board_dim: HexDim) -> Option<Path> {
+        let len = body.segments.len();
+        if len < 2 {
+            return None;
+        }
+        let head = body.segments[0].pos;
+        let tail = body.segments[len - 1].pos;
+
+        let mut parents = HashMap::new();
+        let mut queue = VecDeque::from([(head, 0)]);
+        while let Some((pos, frames)) = queue.pop_front() {
+            for dir in Dir::iter() {
+                let next = pos.wrapping_translate(dir, 1, board_dim);
+                if next == tail {
+                    let mut path = VecDeque::from([tail, pos]);
+                    while let Some(&parent) = parents.get(path.back().unwrap()) {
+                        path.push_back(parent);
+                    }
+                    path.make_contiguous().reverse();
+
+                    // a tail that's about to grow doesn't move out of the way
+                    let tail_stays = body.grow > 0
+                        || matches!(
+                            body.segments[len - 1].segment_type,
+                            SegmentType::Eaten { food_left, .. } if food_left > 0
+                        );
+                    return (path.len() > 2 || !tail_stays).then_some(path);
+                }
+                if next == head || !occupancy.is_free(next, frames + 1) {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = parents.entry(next) {
+                    entry.insert(pos);
+                    queue.push_back((next, frames + 1));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl PathFinder for Survival {
+    fn get_path(
+        &self,
+        targets: &dyn Targets,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
+    ) -> Option<Path> {
+        let other_positions: HashSet<_> = other_snakes.iter_segments().map(|seg| seg.pos).collect();
+
+        match self.main.get_path(targets, body, knowledge, other_snakes, gtx) {
+            Some(path) if Self::survives(&path, targets, body, &other_positions, gtx) => Some(path),
+            _ => {
+                let occupancy = Occupancy::new(body, None, other_snakes, gtx.board_dim);
+                Self::chase_tail(body, &occupancy, gtx.board_dim)
+            }
+        }
+    }
+}
+
+/// Other snakes take up the neighbours of `pocket` in `walls`,
+/// they're long enough not to move out of the way in time
+#[cfg(test)]
+fn test_pocket(pocket: HexPoint, walls: &[Dir]) -> crate::snake::Snake {
+    let far_away = (0..10).map(|h| HexPoint { h, v: 2 });
+    let segments: Vec<_> = walls
+        .iter()
+        .map(|&dir| pocket.translate(dir, 1))
+        .chain(far_away)
+        .collect();
+    super::test_snake(&segments, Dir::U)
+}
+
+#[test]
+fn test_chases_tail_instead_of_entering_pocket() {
+    use super::weighted_bfs::WeightedBFS;
+    use super::Costs;
+    use crate::view::snakes::OtherSnakes;
+
+    let gtx = super::test_gtx(HexDim { h: 20, v: 20 });
+    let pocket = HexPoint { h: 10, v: 10 };
+    let apples = super::test_apples(&[pocket]);
+    // the snake is right below the pocket, facing into it
+    let segments: Vec<_> = (2..7).map(|i| pocket.translate(Dir::D, i)).collect();
+    let snakes = [
+        test_pocket(pocket, &[Dir::U, Dir::Ur, Dir::Dr, Dir::Dl, Dir::Ul]),
+        super::test_snake(&segments, Dir::U),
+    ];
+    let others = OtherSnakes::excluding(&snakes, 1);
+    let body = &snakes[1].body;
+
+    let bfs = WeightedBFS::new(Costs::default());
+    let main_path = bfs.get_path(&apples.as_slice(), body, None, &others, &gtx).unwrap();
+    assert_eq!(main_path.back(), Some(&pocket));
+
+    let survival = Survival { main: Box::new(bfs) };
+    let path = survival
+        .get_path(&apples.as_slice(), body, None, &others, &gtx)
+        .unwrap();
+    assert!(!path.contains(&pocket), "{path:?}");
+    assert_eq!(path.back(), segments.last());
+}
+
+#[test]
+fn test_pending_growth_makes_path_unsafe() {
+    use super::weighted_bfs::WeightedBFS;
+    use super::Costs;
+    use crate::view::snakes::OtherSnakes;
+
+    let gtx = super::test_gtx(HexDim { h: 20, v: 20 });
+    let pocket = HexPoint { h: 10, v: 10 };
+    let apples = super::test_apples(&[pocket]);
+    // the snake curls around the pocket, after eating its head
+    //  is next to the segment that becomes its tail
+    let segments = [
+        pocket.translate(Dir::D, 1),
+        pocket.translate(Dir::Dl, 1),
+        pocket.translate(Dir::Dl, 2),
+    ];
+    let mut snakes = [
+        test_pocket(pocket, &[Dir::U, Dir::Ur, Dir::Dr, Dir::Ul]),
+        super::test_snake(&segments, Dir::Dr),
+    ];
+    let other_positions: HashSet<_> = snakes[0].body.segments.iter().map(|seg| seg.pos).collect();
+    let survival = Survival {
+        main: Box::new(WeightedBFS::new(Costs::default())),
+    };
+    let path = VecDeque::from([segments[0], pocket]);
+
+    // the tail moves out of the way
+    let body = &snakes[1].body;
+    assert!(Survival::survives(
+        &path,
+        &apples.as_slice(),
+        body,
+        &other_positions,
+        &gtx
+    ));
+    let others = OtherSnakes::excluding(&snakes, 1);
+    assert_eq!(
+        survival.get_path(&apples.as_slice(), body, None, &others, &gtx),
+        Some(path.clone())
+    );
+
+    // the tail stays where it is to grow
+    snakes[1].body.segments[2].segment_type = SegmentType::Eaten { original_food: 1, food_left: 1 };
+    let body = &snakes[1].body;
+    assert!(!Survival::survives(
+        &path,
+        &apples.as_slice(),
+        body,
+        &other_positions,
+        &gtx
+    ));
+    let others = OtherSnakes::excluding(&snakes, 1);
+    let chased = survival
+        .get_path(&apples.as_slice(), body, None, &others, &gtx)
+        .unwrap();
+    assert!(!chased.contains(&pocket), "{chased:?}");
+}
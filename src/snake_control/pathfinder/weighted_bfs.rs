@@ -1,157 +1,250 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::rc::Rc;
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Mutex;
 
-use itertools::Itertools;
-
-use super::{Path, PathFinder};
+use super::occupancy::Occupancy;
+use super::{cell_index, Costs, Path, PathFinder};
 use crate::app::game_context::GameContext;
-use crate::basic::{Dir, HexPoint};
+use crate::basic::{Dir, HexDim, HexPoint};
 use crate::snake::eat_mechanics::Knowledge;
 use crate::snake::Body;
-use crate::support::filter_scan::FilterScan;
 use crate::view::snakes::Snakes;
 use crate::view::targets::Targets;
 
-#[derive(Clone)]
+#[derive(Copy, Clone)]
 struct SearchPoint {
-    parent: Option<Rc<Self>>,
+    /// Index in [`SearchBuffers::points`]
+    parent: Option<u32>,
     pos: HexPoint,
+    /// Direction the point was entered in
     dir: Dir,
     len: usize,
-    /// cost = len + TURN_COST * num_turns + TELEPORT_COST * num_teleports
+    /// See [`Costs`]
     cost: usize,
 }
 
-// reverse order iterator of positions
-struct Iter<'a>(Option<&'a SearchPoint>);
-
-impl Iterator for Iter<'_> {
-    type Item = HexPoint;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = self.0?.pos;
-        self.0 = self.0?.parent.as_ref().map(|rc| rc.as_ref());
-        Some(next)
-    }
+/// Memory for searching that is kept between searches
+///
+/// Cells are indexed like the board, a cell only counts as reached
+/// or as a target if its stamp is the stamp of the current search,
+/// so nothing has to be cleared between searches.
+pub(super) struct SearchBuffers {
+    board_dim: HexDim,
+    stamp: u32,
+    /// Search in which each cell was last reached
+    reached: Vec<u32>,
+    /// Lowest cost of getting to each cell in the current search
+    cost: Vec<usize>,
+    /// Search in which each cell was last a target
+    target: Vec<u32>,
+    /// Every point that was kept, parents come before their children
+    points: Vec<SearchPoint>,
+    generation: Vec<u32>,
+    next_generation: Vec<u32>,
+    occupancy: Occupancy,
 }
 
-impl SearchPoint {
-    const TURN_COST: usize = 5;
-    const TELEPORT_COST: usize = 15;
+impl SearchBuffers {
+    pub fn new() -> Self {
+        Self {
+            board_dim: HexDim { h: 0, v: 0 },
+            stamp: 0,
+            reached: vec![],
+            cost: vec![],
+            target: vec![],
+            points: vec![],
+            generation: vec![],
+            next_generation: vec![],
+            occupancy: Occupancy::empty(),
+        }
+    }
 
-    fn get_path(&self) -> Path {
-        let mut vd = VecDeque::with_capacity(self.len);
-        for pos in Iter(Some(self)) {
-            vd.push_front(pos);
+    /// Starts a new stamp, the arrays are only reset when the board
+    /// size changes or the stamps run out
+    fn start(&mut self, board_dim: HexDim) {
+        if self.board_dim != board_dim || self.stamp == u32::MAX {
+            let cells = (board_dim.h * board_dim.v) as usize;
+            self.board_dim = board_dim;
+            self.stamp = 0;
+            for array in [&mut self.reached, &mut self.target] {
+                array.clear();
+                array.resize(cells, 0);
+            }
+            self.cost.clear();
+            self.cost.resize(cells, 0);
         }
-        vd
+        self.stamp += 1;
+        self.points.clear();
+        self.generation.clear();
     }
-}
 
-pub struct WeightedBFS;
+    fn path_to(&self, index: u32) -> Path {
+        let mut point = &self.points[index as usize];
+        let mut path = VecDeque::with_capacity(point.len);
+        path.push_front(point.pos);
+        while let Some(parent) = point.parent {
+            point = &self.points[parent as usize];
+            path.push_front(point.pos);
+        }
+        path
+    }
 
-impl PathFinder for WeightedBFS {
-    fn get_path(
-        &self,
+    /// Also returns how many search points were created
+    pub fn run(
+        &mut self,
+        costs: &Costs,
         targets: &dyn Targets,
         body: &Body,
         knowledge: Option<&Knowledge>,
         other_snakes: &dyn Snakes,
         gtx: &GameContext,
-    ) -> Option<Path> {
-        let off_limits: &HashSet<_> = &if let Some(pk) = knowledge {
-            body.segments
-                .iter()
-                .chain(other_snakes.iter_segments())
-                .filter(|seg| !pk.can_pass_through_self(seg))
-                .map(|seg| seg.pos)
-                .collect()
-        } else {
-            body.segments
-                .iter()
-                .chain(other_snakes.iter_segments())
-                .map(|seg| seg.pos)
-                .collect()
-        };
-
-        let shortest_paths: RefCell<HashMap<HexPoint, SearchPoint>> = Default::default();
-
-        let mut generation = vec![SearchPoint {
+    ) -> (Option<Path>, usize) {
+        let board_dim = gtx.board_dim;
+        self.start(board_dim);
+        self.occupancy.refill(body, knowledge, other_snakes, board_dim);
+        for target in targets.iter().filter(|&target| board_dim.contains(target)) {
+            self.target[cell_index(target, board_dim)] = self.stamp;
+        }
+
+        self.points.push(SearchPoint {
             parent: None,
             pos: body.segments[0].pos,
             dir: body.dir,
             len: 1,
             cost: 1,
-        }];
+        });
+        self.generation.push(0);
 
-        let best: RefCell<Option<(HexPoint, usize)>> = Default::default();
+        let mut best: Option<u32> = None;
+        let mut created = 0;
 
         // bfs
         loop {
             // advance
-            generation = generation
-                .into_iter()
-                .flat_map(|sp| {
-                    let pos = sp.pos;
-                    let rc = Rc::new(sp);
-
-                    Dir::iter()
-                        .filter_scan(rc, move |rc, dir| {
-                            let (new_pos, teleported) = pos.explicit_wrapping_translate(dir, 1, gtx.board_dim);
-
-                            if off_limits.contains(&new_pos) {
-                                return None;
-                            }
-
-                            let new_sp = SearchPoint {
-                                parent: Some(rc.clone()),
-                                pos: new_pos,
-                                dir,
-                                len: rc.len + 1,
-                                cost: rc.cost
-                                    + if dir != rc.dir { SearchPoint::TURN_COST } else { 0 }
-                                    + if teleported { SearchPoint::TELEPORT_COST } else { 0 },
-                            };
-                            Some(new_sp)
-                        })
-                        .filter(|sp| {
-                            let mut shortest_paths = shortest_paths.borrow_mut();
-                            match shortest_paths.get_mut(&sp.pos) {
-                                Some(other_sp) => {
-                                    if sp.cost < other_sp.cost {
-                                        *other_sp = sp.clone();
-                                    } else {
-                                        return false;
-                                    }
-                                }
-                                None => {
-                                    shortest_paths.insert(sp.pos, sp.clone());
-                                }
-                            };
-                            if targets.iter().contains(&sp.pos) {
-                                match &mut *best.borrow_mut() {
-                                    Some((_, cost)) if *cost <= sp.cost => {}
-                                    other => *other = Some((sp.pos, sp.cost)),
-                                }
-                            }
-                            true
-                        })
-                })
-                .collect();
+            self.next_generation.clear();
+            for i in 0..self.generation.len() {
+                let parent_index = self.generation[i];
+                let parent = self.points[parent_index as usize];
+
+                for dir in Dir::iter() {
+                    let (pos, teleported) = parent.pos.explicit_wrapping_translate(dir, 1, board_dim);
+
+                    // the head gets there after parent.len frames
+                    if !self.occupancy.is_free(pos, parent.len) {
+                        continue;
+                    }
+
+                    let cost = parent.cost
+                        + if dir != parent.dir { costs.turn } else { 0 }
+                        + if teleported { costs.teleport } else { 0 };
+
+                    // only keep the point if it's the cheapest way to get here so far
+                    let cell = cell_index(pos, board_dim);
+                    if self.reached[cell] == self.stamp && cost >= self.cost[cell] {
+                        continue;
+                    }
+                    self.reached[cell] = self.stamp;
+                    self.cost[cell] = cost;
+
+                    let index = self.points.len() as u32;
+                    self.points.push(SearchPoint {
+                        parent: Some(parent_index),
+                        pos,
+                        dir,
+                        len: parent.len + 1,
+                        cost,
+                    });
+                    self.next_generation.push(index);
+
+                    if self.target[cell] == self.stamp
+                        && best.map_or(true, |best| cost < self.points[best as usize].cost)
+                    {
+                        best = Some(index);
+                    }
+                }
+            }
+            mem::swap(&mut self.generation, &mut self.next_generation);
+            created += self.generation.len();
 
             // check exit condition (when the live path with the lowest cost is successful)
-            if let Some((best_pos, best_cost)) = *best.borrow() {
-                let gen_min_cost = generation.iter().map(|sp| sp.cost).min();
+            if let Some(best) = best {
+                let best_cost = self.points[best as usize].cost;
+                let gen_min_cost = self.generation.iter().map(|&i| self.points[i as usize].cost).min();
                 match gen_min_cost {
                     Some(cost) if cost < best_cost - 1 => {}
-                    _ => return Some(shortest_paths.borrow()[&best_pos].get_path()),
+                    _ => return (Some(self.path_to(best)), created),
                 }
             }
 
-            if generation.is_empty() {
-                return None;
+            if self.generation.is_empty() {
+                return (None, created);
             }
         }
     }
 }
+
+pub struct WeightedBFS {
+    pub costs: Costs,
+    /// Reused by every search of the snake
+    buffers: Mutex<SearchBuffers>,
+}
+
+impl WeightedBFS {
+    pub fn new(costs: Costs) -> Self {
+        Self {
+            costs,
+            buffers: Mutex::new(SearchBuffers::new()),
+        }
+    }
+
+    /// A one-off search, also returns how many search points
+    /// were created
+    #[cfg(test)]
+    pub(super) fn search(
+        costs: &Costs,
+        targets: &dyn Targets,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
+    ) -> (Option<Path>, usize) {
+        SearchBuffers::new().run(costs, targets, body, knowledge, other_snakes, gtx)
+    }
+}
+
+impl PathFinder for WeightedBFS {
+    fn get_path(
+        &self,
+        targets: &dyn Targets,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        gtx: &GameContext,
+    ) -> Option<Path> {
+        let mut buffers = self.buffers.lock().unwrap();
+        buffers.run(&self.costs, targets, body, knowledge, other_snakes, gtx).0
+    }
+}
+
+#[test]
+fn test_reused_search_finds_the_same_paths() {
+    use crate::view::snakes::OtherSnakes;
+
+    let costs = Costs::default();
+    let bfs = WeightedBFS::new(costs);
+    // the last board is smaller, the arrays are reset
+    for (h, v) in [(30, 20), (30, 20), (200, 150), (12, 9)] {
+        let board_dim = HexDim { h, v };
+        let gtx = super::test_gtx(board_dim);
+        let head = HexPoint { h: h / 2, v: v / 2 };
+        // a wall of body between the head and the apples
+        let body: Vec<_> = (0..v - 2).map(|i| HexPoint { h: h / 2 - 1, v: i }).collect();
+        let body = super::test_body(&[&[head][..], &body].concat(), Dir::U);
+        let apples = super::test_apples(&[HexPoint { h: 1, v: v / 2 }, HexPoint { h: h / 2 - 3, v: 2 }]);
+
+        let fresh = WeightedBFS::search(&costs, &apples.as_slice(), &body, None, &OtherSnakes::empty(), &gtx).0;
+        let reused = bfs.get_path(&apples.as_slice(), &body, None, &OtherSnakes::empty(), &gtx);
+        assert!(fresh.is_some(), "no path on {board_dim:?}");
+        assert_eq!(reused, fresh, "on {board_dim:?}");
+    }
+}
@@ -39,13 +39,13 @@ pub struct Programmed {
 impl Controller for Programmed {
     fn next_dir(
         &mut self,
-        _: &mut Body,
+        _: &Body,
         _: Option<&Knowledge>,
         _: &dyn Snakes,
         _: &[Apple],
         _: &GameContext,
         _: &FpsContext,
-        _: &Context,
+        _: Option<&Context>,
     ) -> Option<Dir> {
         if self.wait > 0 {
             self.wait -= 1;
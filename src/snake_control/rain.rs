@@ -14,13 +14,13 @@ pub struct Rain;
 impl Controller for Rain {
     fn next_dir(
         &mut self,
-        body: &mut Body,
+        body: &Body,
         _: Option<&Knowledge>,
         other_snakes: &dyn Snakes,
         _: &[Apple],
         gtx: &GameContext,
         _ftx: &FpsContext,
-        _ctx: &Context,
+        _ctx: Option<&Context>,
     ) -> Option<Dir> {
         if body.segments[0].pos.v == gtx.board_dim.v - 1 {
             // todo!("return die")
@@ -34,31 +34,17 @@ impl Controller for Rain {
         let next_dl = body.segments[0].pos.wrapping_translate(Dir::Dl, 1, gtx.board_dim);
         let next_dr = body.segments[0].pos.wrapping_translate(Dir::Dr, 1, gtx.board_dim);
 
-        let mut d_occupied = false;
-        let mut dl_occupied = false;
-        let mut dr_occupied = false;
+        let occupied = |pos| {
+            other_snakes
+                .segments_at(pos)
+                .any(|(snake, _)| snake.snake_type != snake::Type::Rain)
+        };
 
-        other_snakes
-            .iter()
-            .filter(|s| s.snake_type != snake::Type::Rain)
-            .flat_map(|s| s.body.segments.iter().map(|c| c.pos))
-            .for_each(|pos| {
-                if pos == next_d {
-                    d_occupied = true;
-                }
-                if pos == next_dl {
-                    dl_occupied = true;
-                }
-                if pos == next_dr {
-                    dr_occupied = true;
-                }
-            });
-
-        if !d_occupied {
+        if !occupied(next_d) {
             Some(Dir::D)
-        } else if !dl_occupied {
+        } else if !occupied(next_dl) {
             Some(Dir::Dl)
-        } else if !dr_occupied {
+        } else if !occupied(next_dr) {
             Some(Dir::Dr)
         } else {
             None
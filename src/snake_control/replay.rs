@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use ggez::Context;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::{Dir, Food};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+/// What a snake did in one game frame
+#[derive(Copy, Clone, Debug)]
+pub struct RecordedFrame {
+    /// The direction the snake moved in
+    pub dir: Dir,
+    /// Food the snake ate at the end of the move
+    pub food: Option<Food>,
+}
+
+/// Every frame of a run, from the first move on
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// Repeats a recorded run move by move, food that was eaten in the
+/// recording is digested the same way without eating any apples
+pub struct Replay {
+    pub recording: Arc<Recording>,
+    pub next_frame: usize,
+}
+
+impl Controller for Replay {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        _: Option<&Knowledge>,
+        _: &dyn Snakes,
+        _: &[Apple],
+        _: &GameContext,
+        _: &FpsContext,
+        _: Option<&Context>,
+    ) -> Option<Dir> {
+        // called once per frame, before the snake moves
+        let dir = self
+            .recording
+            .frames
+            .get(self.next_frame)
+            .map_or(body.dir, |frame| frame.dir);
+        self.next_frame += 1;
+        Some(dir)
+    }
+
+    fn food_eaten(&self) -> Option<Food> {
+        // eaten at the end of the move before the one next_dir just made
+        let prev = self.next_frame.checked_sub(2)?;
+        self.recording.frames.get(prev)?.food
+    }
+
+    fn reset(&mut self, _dir: Dir) {
+        self.next_frame = 0;
+    }
+}
+
+#[cfg(test)]
+fn test_replay_snake(frames: Vec<RecordedFrame>) -> crate::snake::Snake {
+    use crate::app::Palette;
+    use crate::basic::HexPoint;
+    use crate::snake;
+    use crate::snake::builder::Builder as SnakeBuilder;
+    use crate::snake::eat_mechanics::{EatBehavior, EatMechanics};
+    use crate::snake_control::Template;
+
+    SnakeBuilder::default()
+        .snake_type(snake::Type::Ghost { life: None })
+        .eat_mechanics(EatMechanics::always(EatBehavior::PassOver))
+        .palette(Palette::dark().palette_ghost)
+        .controller(Template::Replay(Arc::new(Recording { frames })))
+        .pos(HexPoint { h: 5, v: 5 })
+        .dir(Dir::U)
+        .len(2)
+        .speed(1.)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_reproduces_recorded_run() {
+    use crate::app::fps_control::FpsControl;
+    use crate::basic::HexDim;
+    use crate::snake::{SegmentType, Snake};
+    use crate::snake_control::pathfinder::test_gtx;
+    use crate::view::snakes::OtherSnakes;
+    use Dir::*;
+
+    let gtx = test_gtx(HexDim { h: 10, v: 10 });
+    let ftx = FpsControl::new(1.);
+    let dirs = [U, U, Ur, Dr, Dr, D, D, Dl, Ul, Ul, U, Ur, Ur, Dr, D, D];
+    let frames: Vec<_> = dirs
+        .iter()
+        .enumerate()
+        .map(|(i, &dir)| RecordedFrame {
+            dir,
+            food: match i {
+                1 => Some(2),
+                4 => Some(1),
+                _ => None,
+            },
+        })
+        .collect();
+    let moves_only: Vec<_> = frames
+        .iter()
+        .map(|&frame| RecordedFrame { food: None, ..frame })
+        .collect();
+
+    // the recorded run, food is handed out the way the game does after a move
+    let mut original = test_replay_snake(moves_only.clone());
+    let mut replay = test_replay_snake(frames.clone());
+    let mut hungry = test_replay_snake(moves_only);
+
+    let positions = |snake: &Snake| {
+        snake
+            .body
+            .segments
+            .iter()
+            .map(|segment| segment.pos)
+            .collect::<Vec<_>>()
+    };
+    // the replay marks the head as eaten when it makes the next move
+    let types = |snake: &Snake| {
+        let segments = snake.body.segments.iter().skip(1);
+        segments.map(|segment| segment.segment_type).collect::<Vec<_>>()
+    };
+
+    for (i, frame) in frames.iter().enumerate() {
+        for snake in [&mut original, &mut replay, &mut hungry] {
+            snake.advance(OtherSnakes::empty(), &[], &gtx, ftx.context(), None);
+        }
+        if let Some(food) = frame.food {
+            original.body.segments[0].segment_type = SegmentType::Eaten {
+                original_food: food,
+                food_left: food,
+            };
+        }
+        assert_eq!(positions(&replay), positions(&original), "frame {i}");
+        assert_eq!(types(&replay), types(&original), "frame {i}");
+    }
+
+    // all the food has been digested
+    assert_eq!(replay.body.visible_len(), hungry.body.visible_len() + 3);
+}
@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ggez::event::{Axis, Button};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+
+use crate::app::fps_control::FpsContext;
+use crate::app::game_context::GameContext;
+use crate::apple::Apple;
+use crate::basic::{Dir, Food};
+use crate::snake::eat_mechanics::Knowledge;
+use crate::snake::Body;
+use crate::snake_control::pathfinder::Path;
+use crate::snake_control::Controller;
+use crate::view::snakes::Snakes;
+
+#[derive(Default, Debug)]
+struct Totals {
+    time: Duration,
+    calls: usize,
+}
+
+/// How long a controller took to decide, shared with whoever
+/// wants to read the measurements
+#[derive(Clone, Default, Debug)]
+pub struct Timings(Arc<Mutex<Totals>>);
+
+impl Timings {
+    fn record(&self, time: Duration) {
+        let mut totals = self.0.lock().unwrap();
+        totals.time += time;
+        totals.calls += 1;
+    }
+
+    /// Average time per call to `next_dir`, None if it was never called
+    pub fn average(&self) -> Option<Duration> {
+        let totals = self.0.lock().unwrap();
+        (totals.calls > 0).then(|| totals.time / totals.calls as u32)
+    }
+}
+
+/// Measures how long another controller takes to decide on a direction
+pub struct Timed {
+    pub controller: Box<dyn Controller + Send + Sync>,
+    pub timings: Timings,
+}
+
+impl Controller for Timed {
+    fn next_dir(
+        &mut self,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+        ftx: &FpsContext,
+        ctx: Option<&Context>,
+    ) -> Option<Dir> {
+        let start = Instant::now();
+        let dir = self
+            .controller
+            .next_dir(body, knowledge, other_snakes, apples, gtx, ftx, ctx);
+        self.timings.record(start.elapsed());
+        dir
+    }
+
+    fn get_path(
+        &mut self,
+        body: &Body,
+        knowledge: Option<&Knowledge>,
+        other_snakes: &dyn Snakes,
+        apples: &[Apple],
+        gtx: &GameContext,
+    ) -> Option<&Path> {
+        self.controller.get_path(body, knowledge, other_snakes, apples, gtx)
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.controller.current_path()
+    }
+
+    fn queued_dirs(&self) -> Option<&VecDeque<Dir>> {
+        self.controller.queued_dirs()
+    }
+
+    fn food_eaten(&self) -> Option<Food> {
+        self.controller.food_eaten()
+    }
+
+    fn reset(&mut self, dir: Dir) {
+        self.controller.reset(dir)
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) {
+        self.controller.key_pressed(key)
+    }
+
+    fn gamepad_axis(&mut self, gamepad: usize, axis: Axis, value: f32) {
+        self.controller.gamepad_axis(gamepad, axis, value)
+    }
+
+    fn gamepad_button(&mut self, gamepad: usize, button: Button) {
+        self.controller.gamepad_button(gamepad, button)
+    }
+
+    fn knowledge(&self) -> Option<&Knowledge> {
+        self.controller.knowledge()
+    }
+}
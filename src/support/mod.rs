@@ -1,4 +1,3 @@
-pub mod filter_scan;
 pub mod flip;
 pub mod invert;
 pub mod partial_min_max;
@@ -0,0 +1,507 @@
+//! Runs many headless games in parallel to compare AIs
+//!
+//! Every game puts all contestants on the same board, the same
+//! seeds and board sizes are used for every run so that results
+//! are comparable. Run `hex_snake tournament --help` for options.
+
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fs, process};
+
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use crate::basic::{HexDim, HexPoint};
+use crate::error::Result;
+use crate::gym::reward::{Cause, Event};
+use crate::gym::{self, Gym};
+use crate::snake_control::external::{Endpoint, Fallback};
+use crate::snake_control::killer::Difficulty;
+use crate::snake_control::pathfinder::{self, Costs};
+use crate::snake_control::timed::Timings;
+use crate::snake_control::Template;
+
+pub struct Contestant {
+    pub name: String,
+    pub controller: Template,
+}
+
+impl Contestant {
+    fn new(name: &str, controller: Template) -> Self {
+        Self { name: name.to_string(), controller }
+    }
+
+    /// The built-in AIs
+    pub fn roster() -> Vec<Self> {
+        use pathfinder::Template::*;
+        vec![
+            Self::new("weighted_bfs", Template::Algorithm(WeightedBFS(Costs::default()))),
+            Self::new("a_star", Template::Algorithm(AStar(Costs::default()))),
+            Self::new("space_filling", Template::Algorithm(SpaceFilling)),
+            Self::new(
+                "weighted_bfs+space_filling",
+                Template::Algorithm(WithBackup {
+                    main: Box::new(WeightedBFS(Costs::default())),
+                    backup: Box::new(SpaceFilling),
+                }),
+            ),
+            Self::new(
+                "survival(weighted_bfs)",
+                Template::Algorithm(Survival {
+                    main: Box::new(WeightedBFS(Costs::default())),
+                }),
+            ),
+            Self::new(
+                "adversarial",
+                Template::Adversarial {
+                    time_budget: Duration::from_millis(5),
+                    max_depth: 6,
+                },
+            ),
+            Self::new("killer", Template::Killer(Difficulty::Normal.params())),
+            Self::new("killer_hard", Template::Killer(Difficulty::Hard.params())),
+        ]
+    }
+}
+
+pub struct Settings {
+    pub contestants: Vec<Contestant>,
+    pub board_dims: Vec<HexDim>,
+    pub seeds: Range<u64>,
+    /// Games are stopped after this many frames
+    pub max_steps: usize,
+    pub apple_count: usize,
+    pub start_len: usize,
+}
+
+/// How one contestant did in one game
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub board_dim: HexDim,
+    pub seed: u64,
+    pub contestant: usize,
+    /// Number of frames the snake stayed alive
+    pub survival: usize,
+    pub apples: usize,
+    pub max_len: usize,
+    /// How the snake went out, None if it lasted the whole game
+    pub out: Option<Event>,
+    /// Average time the controller took to choose a direction
+    pub decision_time: Option<Duration>,
+}
+
+impl Record {
+    fn crashed(&self) -> Option<bool> {
+        match self.out {
+            Some(Event::Crashed(_)) => Some(true),
+            Some(Event::Died(_)) => Some(false),
+            _ => None,
+        }
+    }
+
+    fn cause(&self) -> Option<Cause> {
+        match self.out {
+            Some(Event::Crashed(cause) | Event::Died(cause)) => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+fn play(settings: &Settings, board_dim: HexDim, seed: u64) -> Result<Vec<Record>> {
+    let timings: Vec<_> = settings.contestants.iter().map(|_| Timings::default()).collect();
+    let contestants = settings
+        .contestants
+        .iter()
+        .zip(&timings)
+        .map(|(contestant, timings)| Template::Timed {
+            controller: Box::new(contestant.controller.clone()),
+            timings: timings.clone(),
+        })
+        .collect();
+
+    let mut gym = Gym::new(gym::Config {
+        board_dim,
+        agents: 0,
+        contestants,
+        agent_len: settings.start_len,
+        opponents: vec![],
+        apple_count: settings.apple_count,
+        special_apples: false,
+        max_steps: Some(settings.max_steps),
+        ..gym::Config::default()
+    });
+    gym.reset(seed)?;
+
+    let mut records: Vec<_> = (0..settings.contestants.len())
+        .map(|contestant| Record {
+            board_dim,
+            seed,
+            contestant,
+            survival: 0,
+            apples: 0,
+            max_len: settings.start_len,
+            out: None,
+            decision_time: None,
+        })
+        .collect();
+
+    loop {
+        let step = gym.step(&[])?;
+        for (record, (events, len)) in records.iter_mut().zip(step.info.events.iter().zip(&step.info.lengths)) {
+            record.max_len = record.max_len.max(*len);
+            for event in events {
+                match event {
+                    Event::Ate(_) | Event::AteSpecial => record.apples += 1,
+                    Event::Crashed(_) | Event::Died(_) if record.out.is_none() => {
+                        record.out = Some(*event);
+                        record.survival = step.info.step;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if step.done {
+            for record in records.iter_mut().filter(|record| record.out.is_none()) {
+                record.survival = step.info.step;
+            }
+            break;
+        }
+    }
+
+    for (record, timings) in records.iter_mut().zip(&timings) {
+        record.decision_time = timings.average();
+    }
+    Ok(records)
+}
+
+/// Play every seed on every board size, in parallel
+pub fn run(settings: &Settings) -> Vec<Record> {
+    let games: Vec<_> = settings
+        .board_dims
+        .iter()
+        .flat_map(|board_dim| settings.seeds.clone().map(move |seed| (*board_dim, seed)))
+        .collect();
+
+    games
+        .into_par_iter()
+        .flat_map_iter(|(board_dim, seed)| {
+            play(settings, board_dim, seed).unwrap_or_else(|e| {
+                eprintln!("warning: game with seed {seed} on {board_dim:?} failed: {e}");
+                vec![]
+            })
+        })
+        .collect()
+}
+
+fn board_name(HexPoint { h, v }: HexDim) -> String {
+    format!("{h}x{v}")
+}
+
+fn out_name(record: &Record) -> &'static str {
+    match record.crashed() {
+        None => "survived",
+        Some(true) => "crashed",
+        Some(false) => "died",
+    }
+}
+
+fn cause_name(cause: Option<Cause>) -> &'static str {
+    match cause {
+        None => "",
+        Some(Cause::Itself) => "itself",
+        Some(Cause::OtherBody) => "other_body",
+        Some(Cause::HeadOn) => "head_on",
+        Some(Cause::Other) => "other",
+    }
+}
+
+fn micros(duration: Option<Duration>) -> Option<f64> {
+    duration.map(|duration| duration.as_secs_f64() * 1e6)
+}
+
+/// Quotes a field if it contains a separator, a quote, or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn csv_report(settings: &Settings, records: &[Record]) -> String {
+    let mut csv = String::from("board,seed,contestant,survival,apples,max_len,out,cause,avg_decision_us\n");
+    for record in records {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{}",
+            board_name(record.board_dim),
+            record.seed,
+            csv_field(&settings.contestants[record.contestant].name),
+            record.survival,
+            record.apples,
+            record.max_len,
+            out_name(record),
+            cause_name(record.cause()),
+            micros(record.decision_time).map_or(String::new(), |us| format!("{us:.1}")),
+        )
+        .unwrap();
+    }
+    csv
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0., 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Aggregated results of each contestant
+fn summary(settings: &Settings, records: &[Record]) -> Vec<Value> {
+    settings
+        .contestants
+        .iter()
+        .enumerate()
+        .map(|(index, contestant)| {
+            let records: Vec<_> = records.iter().filter(|record| record.contestant == index).collect();
+            let causes: serde_json::Map<_, _> = records
+                .iter()
+                .filter_map(|record| record.cause())
+                .counts_by(|cause| cause_name(Some(cause)))
+                .into_iter()
+                .map(|(cause, count)| (cause.to_string(), json!(count)))
+                .collect();
+            json!({
+                "contestant": contestant.name,
+                "games": records.len(),
+                "mean_survival": mean(records.iter().map(|record| record.survival as f64)),
+                "mean_apples": mean(records.iter().map(|record| record.apples as f64)),
+                "mean_max_len": mean(records.iter().map(|record| record.max_len as f64)),
+                "best_max_len": records.iter().map(|record| record.max_len).max(),
+                "survived": records.iter().filter(|record| record.out.is_none()).count(),
+                "causes": causes,
+                "avg_decision_us": mean(records.iter().filter_map(|record| micros(record.decision_time))),
+            })
+        })
+        .collect()
+}
+
+pub fn json_report(settings: &Settings, records: &[Record]) -> Value {
+    let games: Vec<_> = records
+        .iter()
+        .map(|record| {
+            json!({
+                "board": board_name(record.board_dim),
+                "seed": record.seed,
+                "contestant": settings.contestants[record.contestant].name,
+                "survival": record.survival,
+                "apples": record.apples,
+                "max_len": record.max_len,
+                "out": out_name(record),
+                "cause": record.cause().map(|cause| cause_name(Some(cause))),
+                "avg_decision_us": micros(record.decision_time),
+            })
+        })
+        .collect();
+
+    json!({
+        "max_steps": settings.max_steps,
+        "games": games,
+        "summary": summary(settings, records),
+    })
+}
+
+const USAGE: &str = "\
+usage: hex_snake tournament [options]
+
+options:
+  --games N              number of seeds to play on each board (default 100)
+  --seed N               first seed (default 0)
+  --board WxH            board size, can be repeated (default 30x20)
+  --steps N              maximum length of a game in frames (default 5000)
+  --apples N             number of apples on the board (default 5)
+  --len N                starting length of the snakes (default 5)
+  --only NAME,...        only use these built-in AIs
+  --bot NAME=COMMAND     add an external bot, started with COMMAND
+  --timeout MS           time external bots have to answer (default 100)
+  --genome PATH          add weighted_bfs with costs evolved by
+                         `hex_snake evolve`
+  --format csv|json      report format (default csv)
+  --output PATH          where to write the report (default tournament.csv
+                         or tournament.json), `-` for stdout
+
+built-in AIs: weighted_bfs, a_star, space_filling, weighted_bfs+space_filling,
+  survival(weighted_bfs), adversarial, killer, killer_hard";
+
+fn fail(message: String) -> ! {
+    eprintln!("error: {message}\n\n{USAGE}");
+    process::exit(2)
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(format!("missing value for {option}")));
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value for {option}: {value:?}")))
+}
+
+pub(crate) fn parse_board(value: &str) -> Option<HexDim> {
+    let (h, v) = value.split_once('x')?;
+    let board_dim = HexPoint {
+        h: h.parse().ok()?,
+        v: v.parse().ok()?,
+    };
+    (board_dim.h > 0 && board_dim.v > 0).then_some(board_dim)
+}
+
+/// Entry point of `hex_snake tournament`, `args` excludes the subcommand
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut games = 100;
+    let mut first_seed = 0;
+    let mut board_dims = vec![];
+    let mut max_steps = 5000;
+    let mut apple_count = 5;
+    let mut start_len = 5;
+    let mut only: Option<Vec<String>> = None;
+    let mut bots = vec![];
+    let mut timeout = 100;
+    let mut genome: Option<PathBuf> = None;
+    let mut as_json = false;
+    let mut output: Option<PathBuf> = None;
+
+    while let Some(option) = args.next() {
+        match option.as_str() {
+            "--games" => games = parse(&option, args.next()),
+            "--seed" => first_seed = parse(&option, args.next()),
+            "--board" => {
+                let value: String = parse(&option, args.next());
+                let board_dim = parse_board(&value).unwrap_or_else(|| fail(format!("invalid board size {value:?}")));
+                board_dims.push(board_dim);
+            }
+            "--steps" => max_steps = parse(&option, args.next()),
+            "--apples" => apple_count = parse(&option, args.next()),
+            "--len" => start_len = parse(&option, args.next()),
+            "--only" => {
+                let value: String = parse(&option, args.next());
+                only = Some(value.split(',').map(str::to_string).collect());
+            }
+            "--bot" => {
+                let value: String = parse(&option, args.next());
+                let (name, command) = value
+                    .split_once('=')
+                    .unwrap_or_else(|| fail(format!("expected NAME=COMMAND, got {value:?}")));
+                bots.push((name.to_string(), command.to_string()));
+            }
+            "--timeout" => timeout = parse(&option, args.next()),
+            "--genome" => genome = Some(parse(&option, args.next())),
+            "--format" => {
+                as_json = match parse::<String>(&option, args.next()).as_str() {
+                    "csv" => false,
+                    "json" => true,
+                    other => fail(format!("unknown format {other:?}")),
+                }
+            }
+            "--output" => output = Some(parse(&option, args.next())),
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            other => fail(format!("unknown option {other:?}")),
+        }
+    }
+
+    if board_dims.is_empty() {
+        board_dims.push(HexPoint { h: 30, v: 20 });
+    }
+
+    let mut contestants = Contestant::roster();
+    if let Some(only) = only {
+        for name in &only {
+            if !contestants.iter().any(|contestant| contestant.name == *name) {
+                fail(format!("unknown AI {name:?}"));
+            }
+        }
+        contestants.retain(|contestant| only.contains(&contestant.name));
+    }
+    for (name, command) in bots {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words
+            .next()
+            .unwrap_or_else(|| fail(format!("empty command for bot {name:?}")));
+        let controller = Template::External {
+            endpoint: Endpoint::Process { program, args: words.collect() },
+            timeout: Duration::from_millis(timeout),
+            fallback: Fallback::KeepDirection,
+        };
+        contestants.push(Contestant { name, controller });
+    }
+    if let Some(genome) = genome {
+        let costs = Costs::load(&genome).unwrap_or_else(|e| fail(format!("failed to read {}: {e}", genome.display())));
+        let controller = Template::Algorithm(pathfinder::Template::WeightedBFS(costs));
+        contestants.push(Contestant {
+            name: "weighted_bfs(evolved)".to_string(),
+            controller,
+        });
+    }
+
+    if contestants.is_empty() {
+        fail("no contestants".to_string());
+    }
+    for board_dim in &board_dims {
+        if board_dim.h < contestants.len() as isize {
+            fail(format!(
+                "board {} is too narrow for {} snakes",
+                board_name(*board_dim),
+                contestants.len()
+            ));
+        }
+    }
+
+    let settings = Settings {
+        contestants,
+        board_dims,
+        seeds: first_seed..first_seed + games,
+        max_steps,
+        apple_count,
+        start_len,
+    };
+
+    let records = run(&settings);
+
+    for row in summary(&settings, &records) {
+        eprintln!("{row}");
+    }
+
+    let report = if as_json {
+        json_report(&settings, &records).to_string()
+    } else {
+        csv_report(&settings, &records)
+    };
+    // the summary and AI logs go to stderr, so `-` leaves stdout to the report
+    let output = output.unwrap_or_else(|| PathBuf::from(if as_json { "tournament.json" } else { "tournament.csv" }));
+    if output == PathBuf::from("-") {
+        print!("{report}");
+    } else if let Err(e) = fs::write(&output, report) {
+        fail(format!("failed to write {}: {e}", output.display()))
+    }
+}
+
+#[test]
+fn test_usage_lists_roster() {
+    for contestant in Contestant::roster() {
+        assert!(
+            USAGE.contains(&contestant.name),
+            "{} is missing from the usage",
+            contestant.name
+        );
+    }
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!(csv_field("weighted_bfs+space_filling"), "weighted_bfs+space_filling");
+    assert_eq!(csv_field("bot,2"), "\"bot,2\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
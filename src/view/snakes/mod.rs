@@ -1,6 +1,8 @@
 pub use other_snakes::OtherSnakes;
 use rayon::prelude::ParallelIterator;
 
+use crate::basic::board::Board;
+use crate::basic::HexPoint;
 use crate::snake::{Segment, Snake};
 
 mod all_snakes;
@@ -46,6 +48,18 @@ pub trait Snakes {
     fn iter_segments(&self) -> Box<dyn Iterator<Item = &Segment> + '_> {
         Box::new(self.iter().flat_map(|snake| snake.body.segments.iter()))
     }
+
+    /// The board the snakes are on, if it's being tracked, it also
+    /// has whichever snake is looking at the others
+    fn board(&self) -> Option<&Board> {
+        None
+    }
+
+    /// Snakes with a segment at `pos` and the index of the segment,
+    /// a lookup on the board if there is one, a scan otherwise
+    fn segments_at(&self, pos: HexPoint) -> Box<dyn Iterator<Item = (&Snake, usize)> + '_> {
+        scan_segments_at(self.iter(), pos)
+    }
     //
     // fn iter_segments_mut<'a>(&'a mut self) -> Self::IterSegmentsMut<'a> {
     //     self.iter_mut().flat_map(|snake| snake.body.segments.iter_mut())
@@ -58,6 +72,16 @@ pub trait Snakes {
     // }
 }
 
+fn scan_segments_at<'a>(
+    snakes: impl Iterator<Item = &'a Snake> + 'a,
+    pos: HexPoint,
+) -> Box<dyn Iterator<Item = (&'a Snake, usize)> + 'a> {
+    Box::new(snakes.flat_map(move |snake| {
+        let segments = snake.body.segments.iter().enumerate();
+        segments.filter(move |(_, segment)| segment.pos == pos).map(move |(k, _)| (snake, k))
+    }))
+}
+
 impl Snakes for &dyn Snakes {
     fn iter(&self) -> Box<dyn Iterator<Item = &Snake> + '_> {
         (*self).iter()
@@ -66,4 +90,12 @@ impl Snakes for &dyn Snakes {
     fn par_iter(&self) -> Box<dyn ObjectSafeParallelIterator<Item = &Snake> + '_> {
         (*self).par_iter()
     }
+
+    fn board(&self) -> Option<&Board> {
+        (*self).board()
+    }
+
+    fn segments_at(&self, pos: HexPoint) -> Box<dyn Iterator<Item = (&Snake, usize)> + '_> {
+        (*self).segments_at(pos)
+    }
 }
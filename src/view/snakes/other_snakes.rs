@@ -1,25 +1,40 @@
 use rayon::prelude::*;
 
+use crate::basic::board::Board;
+use crate::basic::HexPoint;
 use crate::snake::Snake;
-use crate::view::snakes::{ObjectSafeParallelIterator, Snakes};
+use crate::view::snakes::{scan_segments_at, ObjectSafeParallelIterator, Snakes};
 
+/// All snakes but one, the one in between the two slices
 #[derive(Copy, Clone)]
-pub struct OtherSnakes<'a>(&'a [Snake], &'a [Snake]);
+pub struct OtherSnakes<'a>(&'a [Snake], &'a [Snake], Option<&'a Board>);
 
 #[allow(dead_code)]
 impl<'a> OtherSnakes<'a> {
     pub fn empty() -> Self {
-        Self(&[], &[])
+        Self(&[], &[], None)
     }
 
     // pub fn new(a: &'a [Snake], b: &'a [Snake]) -> Self {
     //     Self(a, b)
     // }
 
+    /// All snakes except the one at `idx`
+    pub fn excluding(snakes: &'a [Snake], idx: usize) -> Self {
+        Self(&snakes[..idx], &snakes[idx + 1..], None)
+    }
+
     pub fn split_snakes(snakes: &mut [Snake], idx: usize) -> (&mut Snake, OtherSnakes) {
         let (other_snakes1, rest) = snakes.split_at_mut(idx);
         let (snake, other_snakes2) = rest.split_first_mut().unwrap();
-        (snake, OtherSnakes(other_snakes1, other_snakes2))
+        (snake, OtherSnakes(other_snakes1, other_snakes2, None))
+    }
+
+    /// Uses the board for lookups, it has to track all the snakes
+    /// including the one that is left out
+    pub fn on_board(self, board: &'a Board) -> Self {
+        debug_assert_eq!(board.num_snakes(), self.0.len() + self.1.len() + 1);
+        Self(self.0, self.1, Some(board))
     }
 
     // pub fn par_iter_snakes(&self) -> impl ParallelIterator<Item = &Snake> {
@@ -53,6 +68,26 @@ impl Snakes for OtherSnakes<'_> {
         Box::new(self.0.par_iter().chain(self.1.par_iter()))
     }
 
+    fn board(&self) -> Option<&Board> {
+        self.2
+    }
+
+    fn segments_at(&self, pos: HexPoint) -> Box<dyn Iterator<Item = (&Snake, usize)> + '_> {
+        let Some(board) = self.2 else {
+            return scan_segments_at(self.iter(), pos);
+        };
+        // board indices count the left out snake too
+        let left_out = self.0.len();
+        Box::new(board.segments_at(pos).filter_map(move |segment| {
+            let snake = match segment.snake_index {
+                i if i < left_out => &self.0[i],
+                i if i == left_out => return None,
+                i => &self.1[i - left_out - 1],
+            };
+            Some((snake, segment.segment_index))
+        }))
+    }
+
     // fn nth(&self, n: usize) -> &Snake {
     //     if n < self.0.len() {
     //         &self.0[n]